@@ -1,33 +1,236 @@
 use crate::api::AppState;
 use crate::error::ApiError;
-use application::dtos::{LoginRequestDto, LoginResponseDto, RegisterRequestDto, RegisterResponseDto};
-use axum::{extract::State, Json};
-use tracing::{info, instrument};
+use crate::middleware::client_ip::ClientIp;
+use application::dtos::{
+    ChangePasswordRequestDto, EmailVerificationConfirmDto, EmailVerificationRequestDto, LoginOutcome, LoginRequestDto,
+    LoginResponseDto, MfaCodeDto, MfaDisableDto, MfaEnrollmentDto, MfaLoginDto, MfaRecoveryCodesDto, MfaStatusDto,
+    PasswordResetConfirmDto, PasswordResetRequestDto, RegisterOutcome, RegisterRequestDto,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde_json::json;
+use tracing::info;
 
 /// Login user
 ///
 /// Login with username and password to get a JWT token.
+/// Repeated failures lock out the account and the client address for a while.
+/// Users with a second factor get `202 Accepted` and a challenge to complete
+/// at `/api/auth/mfa/login` instead.
 pub async fn login(
     State(state): State<AppState>,
+    client_ip: Option<ClientIp>,
     Json(login_request): Json<LoginRequestDto>,
-) -> Result<Json<LoginResponseDto>, ApiError> {
+) -> Result<Response, ApiError> {
     info!("Login request received for user: {}", login_request.username);
     
-    let response = state.auth_use_cases.login(login_request).await?;
+    let client_ip = client_ip.map(|ClientIp(ip)| ip);
+    let response = match state.auth_use_cases.login(login_request, client_ip).await? {
+        LoginOutcome::Authenticated(login) => Json(login).into_response(),
+        LoginOutcome::MfaRequired(challenge) => (StatusCode::ACCEPTED, Json(challenge)).into_response(),
+    };
     
+    Ok(response)
+}
+
+/// Complete login with a second factor
+///
+/// Exchange the challenge from the login and a code of the authenticator app,
+/// or an unused recovery code, for a JWT token. Wrong codes count towards the
+/// login lockout.
+pub async fn complete_mfa_login(
+    State(state): State<AppState>,
+    client_ip: Option<ClientIp>,
+    Json(mfa_request): Json<MfaLoginDto>,
+) -> Result<Json<LoginResponseDto>, ApiError> {
+    info!("MFA login request received");
+
+    let client_ip = client_ip.map(|ClientIp(ip)| ip);
+    let response = state.auth_use_cases.complete_mfa_login(mfa_request, client_ip).await?;
+
     Ok(Json(response))
 }
 
 /// Register new user
 ///
 /// Register a new user with username, email, and password.
+/// With uniform registration responses enabled, answers `202 Accepted` in
+/// every case and emails the outcome to the given address.
 pub async fn register(
     State(state): State<AppState>,
     Json(register_request): Json<RegisterRequestDto>,
-) -> Result<Json<RegisterResponseDto>, ApiError> {
+) -> Result<Response, ApiError> {
     info!("Registration request received for user: {}", register_request.username);
     
-    let response = state.auth_use_cases.register(register_request).await?;
+    let response = match state.auth_use_cases.register(register_request).await? {
+        RegisterOutcome::Registered(user) => Json(user).into_response(),
+        RegisterOutcome::Accepted => (
+            StatusCode::ACCEPTED,
+            Json(json!({ "message": "Registration received. Check your email to continue." })),
+        )
+            .into_response(),
+    };
     
+    Ok(response)
+}
+
+/// Change password
+///
+/// Change the authenticated user's password, given the current one. Also
+/// accepts the token issued for an expired password. All of the user's
+/// tokens are revoked and a new one is returned. Wrong current passwords
+/// count towards the login lockout.
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<String>,
+    client_ip: Option<ClientIp>,
+    Json(change_request): Json<ChangePasswordRequestDto>,
+) -> Result<Json<LoginResponseDto>, ApiError> {
+    info!("Change password request received for user: {}", user_id);
+
+    let client_ip = client_ip.map(|ClientIp(ip)| ip);
+    let response = state
+        .auth_use_cases
+        .change_password(&user_id, change_request, client_ip)
+        .await?;
+
     Ok(Json(response))
 }
+
+/// Request password reset
+///
+/// Email a single-use password reset token to the given address. Answers
+/// `202 Accepted` whether or not the address belongs to a user.
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(reset_request): Json<PasswordResetRequestDto>,
+) -> Result<Response, ApiError> {
+    info!("Password reset request received");
+
+    state.auth_use_cases.request_password_reset(reset_request).await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "If the address belongs to an account, a reset token has been sent to it." })),
+    )
+        .into_response())
+}
+
+/// Confirm password reset
+///
+/// Set a new password with a token received by email. All of the user's
+/// tokens are revoked, so they log in again with the new password.
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(confirm_request): Json<PasswordResetConfirmDto>,
+) -> Result<StatusCode, ApiError> {
+    info!("Password reset confirmation received");
+
+    state.auth_use_cases.confirm_password_reset(confirm_request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Request email verification
+///
+/// Email a new verification token to the given address. Answers
+/// `202 Accepted` whether or not the address belongs to an unverified user.
+pub async fn request_email_verification(
+    State(state): State<AppState>,
+    Json(verification_request): Json<EmailVerificationRequestDto>,
+) -> Result<Response, ApiError> {
+    info!("Email verification request received");
+
+    state.auth_use_cases.request_email_verification(verification_request).await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "If the address awaits verification, a token has been sent to it." })),
+    )
+        .into_response())
+}
+
+/// Confirm email verification
+///
+/// Confirm an email address with a token received by email. A changed
+/// address becomes the user's email only now.
+pub async fn confirm_email_verification(
+    State(state): State<AppState>,
+    Json(confirm_request): Json<EmailVerificationConfirmDto>,
+) -> Result<StatusCode, ApiError> {
+    info!("Email verification confirmation received");
+
+    state.auth_use_cases.confirm_email_verification(confirm_request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Second factor status
+///
+/// Whether the authenticated user has a second factor, how many recovery
+/// codes are left and whether their role requires one.
+pub async fn mfa_status(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<MfaStatusDto>, ApiError> {
+    info!("MFA status request received for user: {}", user_id);
+
+    let status = state.auth_use_cases.mfa_status(&user_id).await?;
+
+    Ok(Json(status))
+}
+
+/// Begin second factor enrollment
+///
+/// Create a secret for an authenticator app, returned as text, as an
+/// `otpauth://` URI and as a QR code. Nothing changes for the login until
+/// the enrollment is confirmed.
+pub async fn begin_mfa_enrollment(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<MfaEnrollmentDto>, ApiError> {
+    info!("MFA enrollment request received for user: {}", user_id);
+
+    let enrollment = state.auth_use_cases.begin_mfa_enrollment(&user_id).await?;
+
+    Ok(Json(enrollment))
+}
+
+/// Confirm second factor enrollment
+///
+/// Enable the second factor with a code of the authenticator app. Returns
+/// recovery codes, each usable once instead of a code; they are not shown again.
+pub async fn confirm_mfa_enrollment(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<String>,
+    Json(code_request): Json<MfaCodeDto>,
+) -> Result<Json<MfaRecoveryCodesDto>, ApiError> {
+    info!("MFA enrollment confirmation received for user: {}", user_id);
+
+    let recovery_codes = state.auth_use_cases.confirm_mfa_enrollment(&user_id, code_request).await?;
+
+    Ok(Json(recovery_codes))
+}
+
+/// Disable second factor
+///
+/// Remove the second factor, given the password and a code of the
+/// authenticator app or a recovery code. Wrong ones count towards the login
+/// lockout.
+pub async fn disable_mfa(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<String>,
+    client_ip: Option<ClientIp>,
+    Json(disable_request): Json<MfaDisableDto>,
+) -> Result<StatusCode, ApiError> {
+    info!("MFA disable request received for user: {}", user_id);
+
+    let client_ip = client_ip.map(|ClientIp(ip)| ip);
+    state.auth_use_cases.disable_mfa(&user_id, disable_request, client_ip).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
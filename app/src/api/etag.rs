@@ -0,0 +1,60 @@
+//! Entity tags derived from resource versions, for conditional requests.
+
+use axum::http::{
+    header::{HeaderName, IF_MATCH, IF_NONE_MATCH},
+    HeaderMap, HeaderValue,
+};
+
+/// Strong entity tag of a resource at `version`
+pub fn etag(version: i64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).expect("a quoted number is a valid header value")
+}
+
+/// Versions accepted by the `If-Match` header, or `None` if any version is
+/// (no header, or `*`).
+///
+/// `If-Match` uses strong comparison, so weak and malformed tags never match.
+pub fn if_match(headers: &HeaderMap) -> Option<Vec<i64>> {
+    let tags = entity_tags(headers, &IF_MATCH)?;
+
+    if tags.iter().any(|tag| tag == "*") {
+        return None;
+    }
+
+    Some(tags.iter().filter_map(|tag| parse_tag(tag)).collect())
+}
+
+/// Whether the `If-None-Match` header matches `version`, i.e. the client's
+/// copy is current. Uses weak comparison.
+pub fn if_none_match(headers: &HeaderMap, version: i64) -> bool {
+    let Some(tags) = entity_tags(headers, &IF_NONE_MATCH) else {
+        return false;
+    };
+
+    tags.iter().any(|tag| {
+        tag == "*" || parse_tag(tag.strip_prefix("W/").unwrap_or(tag)) == Some(version)
+    })
+}
+
+/// Comma separated tags of all `name` headers, `None` if there are none
+fn entity_tags(headers: &HeaderMap, name: &HeaderName) -> Option<Vec<String>> {
+    let values: Vec<_> = headers.get_all(name).iter().collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(
+        values
+            .into_iter()
+            // An unreadable header lists no usable tags
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+    )
+}
+
+fn parse_tag(tag: &str) -> Option<i64> {
+    tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
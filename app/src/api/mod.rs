@@ -1,30 +1,35 @@
 pub mod auth;
+pub mod etag;
 pub mod users;
 
 use std::sync::Arc;
 
 use application::use_cases::{AuthUseCases, UserUseCases};
-use application::services::JwtService;
+use domain::entities::RoleName;
+use domain::entities::RateLimitQuota;
+use domain::repositories::{IdempotencyRepository, RateLimitRepository};
 use axum::{
     middleware,
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
-use infrastructure::config::ConfigProvider;
-use tower_http::cors::{Any, CorsLayer};
+use infrastructure::config::{ConfigProvider, IpAccessRules, RateLimitConfig, RateLimitPolicy};
+use tower_http::cors::{AllowHeaders, AllowMethods, CorsLayer};
 use tracing::info;
 
 use crate::middleware::{
-    auth::{auth_middleware, AuthState},
-    tracing::{create_tracing_layer, request_tracing_middleware},
+    auth_middleware, client_ip_middleware, create_tracing_layer, idempotency_middleware, ip_access_middleware,
+    rate_limit_middleware, request_tracing_middleware, require_role, AuthState, ClientIpState, IdempotencyState,
+    IpAccessState, RateLimitState,
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub auth_use_cases: Arc<AuthUseCases>,
     pub user_use_cases: Arc<UserUseCases>,
-    pub jwt_service: Arc<dyn JwtService>,
     pub config_provider: Arc<dyn ConfigProvider>,
+    pub idempotency_repository: Arc<dyn IdempotencyRepository>,
+    pub rate_limit_repository: Arc<dyn RateLimitRepository>,
 }
 
 pub fn create_router(app_state: AppState) -> Router {
@@ -32,51 +37,199 @@ pub fn create_router(app_state: AppState) -> Router {
 
     // Create the auth state for the auth middleware
     let auth_state = AuthState {
-        jwt_service: Arc::clone(&app_state.jwt_service),
+        auth_use_cases: Arc::clone(&app_state.auth_use_cases),
+        accept_password_change_tokens: false,
+        allow_unverified_email: false,
+        accept_mfa_enrollment_tokens: false,
     };
 
-    // Create the CORS layer
     let config = app_state.config_provider.get_config();
+
+    // Create the state for the idempotency middleware
+    let idempotency_state = IdempotencyState {
+        repository: Arc::clone(&app_state.idempotency_repository),
+        ttl: chrono::Duration::seconds(config.idempotency.ttl as i64),
+        fingerprint_key: config.idempotency.fingerprint_key.clone(),
+    };
+
+    // Create the state for resolving client addresses behind proxies
+    let client_ip_state = ClientIpState {
+        trusted_proxies: config.network.trusted_proxies.clone().into(),
+    };
+    let access = &config.network.access;
+
+    // Create the CORS layer
     let cors_layer = CorsLayer::new()
         .allow_origin(config.cors.allowed_origins.iter().map(|origin| origin.parse().unwrap()).collect::<Vec<_>>())
-        .allow_methods(Any)
-        .allow_headers(Any)
+        // Wildcards cannot be combined with credentials, so echo what was asked for
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
         .allow_credentials(config.cors.allow_credentials);
 
+    // Auth routes (no authentication required). Only registration takes an
+    // Idempotency-Key; the other responses carry tokens that must not be stored
+    let mut auth_routes = Router::new()
+        .route("/login", post(auth::login))
+        .route("/mfa/login", post(auth::complete_mfa_login))
+        .route(
+            "/register",
+            post(auth::register).route_layer(middleware::from_fn_with_state(
+                idempotency_state.clone(),
+                idempotency_middleware,
+            )),
+        )
+        .route("/password-reset/request", post(auth::request_password_reset))
+        .route("/password-reset/confirm", post(auth::confirm_password_reset))
+        .route("/email-verification/request", post(auth::request_email_verification))
+        .route("/email-verification/confirm", post(auth::confirm_email_verification));
+    // Runs before the idempotency middleware, so refused requests never claim a key
+    if let Some(state) = rate_limit_state(&app_state, &config.rate_limit, &config.rate_limit.auth, "auth") {
+        auth_routes = auth_routes.route_layer(middleware::from_fn_with_state(state, rate_limit_middleware));
+    }
+
+    // Password changes need authentication, also with a token issued for an
+    // expired password or an unverified email address
+    let mut password_routes = Router::new().route("/password", post(auth::change_password));
+    if let Some(state) = rate_limit_state(&app_state, &config.rate_limit, &config.rate_limit.auth, "auth") {
+        password_routes = password_routes.route_layer(middleware::from_fn_with_state(state, rate_limit_middleware));
+    }
+    let password_routes = password_routes.route_layer(middleware::from_fn_with_state(
+        AuthState {
+            accept_password_change_tokens: true,
+            allow_unverified_email: true,
+            ..auth_state.clone()
+        },
+        auth_middleware,
+    ));
+
+    // Second factor management needs authentication, also with a token issued
+    // to users who must enroll one before anything else
+    let mut mfa_routes = Router::new()
+        .route("/mfa", get(auth::mfa_status))
+        .route("/mfa/enroll", post(auth::begin_mfa_enrollment))
+        .route("/mfa/verify", post(auth::confirm_mfa_enrollment))
+        .route("/mfa/disable", post(auth::disable_mfa));
+    if let Some(state) = rate_limit_state(&app_state, &config.rate_limit, &config.rate_limit.auth, "auth") {
+        mfa_routes = mfa_routes.route_layer(middleware::from_fn_with_state(state, rate_limit_middleware));
+    }
+    let mfa_routes = mfa_routes.route_layer(middleware::from_fn_with_state(
+        AuthState {
+            accept_mfa_enrollment_tokens: true,
+            ..auth_state.clone()
+        },
+        auth_middleware,
+    ));
+    let auth_routes = with_ip_access(auth_routes.merge(password_routes).merge(mfa_routes), &access.auth);
+
+    // User routes (authentication required)
+    let mut user_routes = Router::new()
+        // Runs inside the auth middleware, so unauthenticated requests never claim a key
+        .route(
+            "/",
+            get(users::get_all_users).merge(post(users::create_user).route_layer(
+                middleware::from_fn_with_state(idempotency_state, idempotency_middleware),
+            )),
+        )
+        .route(
+            "/:id",
+            get(users::get_user)
+                .put(users::update_user)
+                .patch(users::patch_user)
+                .delete(users::delete_user),
+        )
+        // Account status, restores, lockouts, password and second factor resets and imports are restricted to admins
+        .merge(with_ip_access(
+            Router::new()
+                .route("/import", post(users::import_users))
+                .route("/locked", get(users::get_locked_accounts))
+                .route("/:id/restore", post(users::restore_user))
+                .route("/:id/suspend", post(users::suspend_user))
+                .route("/:id/reactivate", post(users::reactivate_user))
+                .route("/:id/password", post(users::reset_password))
+                .route("/:id/mfa", delete(users::reset_mfa))
+                .route_layer(middleware::from_fn(|request, next| {
+                    require_role(RoleName::Admin, request, next)
+                })),
+            &access.admin,
+        ));
+    // Also inside the auth middleware, so requests can be counted per user
+    if let Some(state) = rate_limit_state(&app_state, &config.rate_limit, &config.rate_limit.users, "users") {
+        user_routes = user_routes.route_layer(middleware::from_fn_with_state(state, rate_limit_middleware));
+    }
+    let user_routes = user_routes.route_layer(middleware::from_fn_with_state(
+        auth_state.clone(),
+        auth_middleware,
+    ));
+    let user_routes = with_ip_access(user_routes, &access.users);
+
     // Create the router
-    Router::new()
+    let mut router = Router::new()
         // API routes
         .nest(
             "/api",
             Router::new()
-                // Auth routes (no authentication required)
-                .nest(
-                    "/auth",
-                    Router::new()
-                        .route("/login", post(auth::login))
-                        .route("/register", post(auth::register)),
-                )
-                // User routes (authentication required)
-                .nest(
-                    "/users",
-                    Router::new()
-                        .route("/", get(users::get_all_users).post(users::create_user))
-                        .route("/:id", get(users::get_user).put(users::update_user).delete(users::delete_user))
-                        .route_layer(middleware::from_fn_with_state(
-                            auth_state.clone(),
-                            auth_middleware,
-                        )),
-                ),
+                .nest("/auth", auth_routes)
+                .nest("/users", user_routes),
         )
         // Health check route
         .route("/health", get(health_check))
         // Add middleware
         .layer(create_tracing_layer())
         .layer(middleware::from_fn(request_tracing_middleware))
-        .layer(cors_layer)
+        .layer(cors_layer);
+    if !access.global.is_empty() {
+        router = router.layer(middleware::from_fn_with_state(
+            IpAccessState {
+                rules: Arc::new(access.global.clone()),
+            },
+            ip_access_middleware,
+        ));
+    }
+
+    router
+        // Outermost, so every other middleware sees the client address
+        .layer(middleware::from_fn_with_state(client_ip_state, client_ip_middleware))
         .with_state(app_state)
 }
 
+/// Applies a route group's IP access rules, unless it has none.
+fn with_ip_access(router: Router<AppState>, rules: &IpAccessRules) -> Router<AppState> {
+    if rules.is_empty() {
+        return router;
+    }
+
+    router.route_layer(middleware::from_fn_with_state(
+        IpAccessState {
+            rules: Arc::new(rules.clone()),
+        },
+        ip_access_middleware,
+    ))
+}
+
+/// State for the rate limit middleware of a route group, or `None` when the
+/// group is not limited.
+fn rate_limit_state(
+    app_state: &AppState,
+    config: &RateLimitConfig,
+    policy: &RateLimitPolicy,
+    group: &'static str,
+) -> Option<RateLimitState> {
+    if !config.enabled || policy.requests == 0 {
+        return None;
+    }
+
+    Some(RateLimitState {
+        repository: Arc::clone(&app_state.rate_limit_repository),
+        group,
+        quota: RateLimitQuota {
+            limit: policy.requests,
+            period: chrono::Duration::seconds(policy.period.max(1) as i64),
+            burst: policy.burst,
+        },
+        key: policy.key,
+    })
+}
+
 async fn health_check() -> &'static str {
     "OK"
 }
@@ -1,11 +1,18 @@
+use crate::api::etag::{etag, if_match, if_none_match};
 use crate::api::AppState;
 use crate::error::ApiError;
-use application::dtos::{CreateUserDto, UpdateUserDto, UserDto};
+use application::dtos::{CreateUserDto, ImportUserDto, LockedAccountDto, UpdateUserDto, UserDto, UserPatch};
 use axum::{
-    extract::{Path, State},
-    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{
+        header::{CONTENT_TYPE, ETAG},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
-use domain::entities::role::RoleName;
+use domain::entities::RoleName;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
@@ -25,29 +32,63 @@ pub struct UpdateUserRequest {
     pub role: Option<RoleName>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub password: String,
+    /// Logged with the reset under the `audit` target
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChangeStatusRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListUsersQuery {
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
 /// Get user by ID
 ///
-/// Get a user by their ID. Requires authentication.
+/// Get a user by their ID. Requires authentication. The response carries an
+/// `ETag`; with a matching `If-None-Match` it is `304 Not Modified`.
 pub async fn get_user(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<UserDto>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     info!("Get user request received for ID: {}", id);
     
     let user = state.user_use_cases.get_user(&id).await?;
+    let etag = etag(user.version);
+
+    if if_none_match(&headers, user.version) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
     
-    Ok(Json(user))
+    Ok(([(ETAG, etag)], Json(user)).into_response())
 }
 
 /// Get all users
 ///
-/// Get a list of all users. Requires authentication.
+/// Get a list of all users. Requires authentication. Soft-deleted users are
+/// only listed with `?include_deleted=true`, which requires the admin role.
 pub async fn get_all_users(
     State(state): State<AppState>,
+    Extension(role): Extension<RoleName>,
+    Query(query): Query<ListUsersQuery>,
 ) -> Result<Json<Vec<UserDto>>, ApiError> {
     info!("Get all users request received");
     
-    let users = state.user_use_cases.get_all_users().await?;
+    if query.include_deleted && role != RoleName::Admin {
+        return Err(ApiError::AuthorizationError(
+            "Listing deleted users requires the admin role".to_string(),
+        ));
+    }
+    
+    let users = state.user_use_cases.get_all_users(query.include_deleted).await?;
     
     Ok(Json(users))
 }
@@ -73,31 +114,111 @@ pub async fn create_user(
     Ok(Json(user))
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportUsersRequest {
+    pub users: Vec<ImportUserDto>,
+}
+
+/// Import users
+///
+/// Create users migrated from another system with their existing password
+/// hashes. Either all users are created or none. Requires authentication and
+/// admin role.
+pub async fn import_users(
+    State(state): State<AppState>,
+    Json(import_request): Json<ImportUsersRequest>,
+) -> Result<(StatusCode, Json<Vec<UserDto>>), ApiError> {
+    info!("Import users request received for {} users", import_request.users.len());
+
+    let users = state.user_use_cases.import_users(import_request.users).await?;
+
+    Ok((StatusCode::CREATED, Json(users)))
+}
+
 /// Update user
 ///
 /// Update an existing user. Requires authentication and appropriate role.
+/// With `If-Match`, fails with `412 Precondition Failed` unless the user is
+/// still at one of the listed versions. Passwords are not changed here but
+/// with `POST /api/auth/password` or an admin's password reset.
 pub async fn update_user(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(user_request): Json<UpdateUserRequest>,
-) -> Result<Json<UserDto>, ApiError> {
+) -> Result<Response, ApiError> {
     info!("Update user request received for ID: {}", id);
+
+    if user_request.password.is_some() {
+        return Err(ApiError::ValidationError(
+            "Passwords are changed with POST /api/auth/password, or reset by an admin with POST /api/users/:id/password"
+                .to_string(),
+        ));
+    }
     
     let update_user_dto = UpdateUserDto {
         username: user_request.username,
         email: user_request.email,
-        password: user_request.password,
         role: user_request.role,
     };
     
-    let user = state.user_use_cases.update_user(&id, update_user_dto).await?;
+    let if_match = if_match(&headers);
+    let user = state
+        .user_use_cases
+        .update_user(&id, update_user_dto, if_match.as_deref())
+        .await?;
     
-    Ok(Json(user))
+    Ok(([(ETAG, etag(user.version))], Json(user)).into_response())
+}
+
+/// Patch user
+///
+/// Partially update a user with a JSON Merge Patch (`application/merge-patch+json`)
+/// or a JSON Patch (`application/json-patch+json`) applied to its `username`,
+/// `email` and `role`. Requires authentication and appropriate role.
+/// `If-Match` is honoured as for updates.
+pub async fn patch_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    info!("Patch user request received for ID: {}", id);
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let patch = match content_type.as_str() {
+        "application/merge-patch+json" => UserPatch::Merge(parse_body(&body)?),
+        "application/json-patch+json" => UserPatch::Json(parse_body(&body)?),
+        _ => {
+            return Err(ApiError::UnsupportedMediaType(
+                "Expected application/merge-patch+json or application/json-patch+json".to_string(),
+            ))
+        }
+    };
+
+    let if_match = if_match(&headers);
+    let user = state
+        .user_use_cases
+        .patch_user(&id, patch, if_match.as_deref())
+        .await?;
+
+    Ok(([(ETAG, etag(user.version))], Json(user)).into_response())
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| ApiError::ValidationError(format!("Invalid patch document: {}", e)))
 }
 
 /// Delete user
 ///
-/// Delete a user by their ID. Requires authentication and admin role.
+/// Soft-delete a user by their ID. Requires authentication and admin role.
+/// The user can be restored until the retention period has passed.
 pub async fn delete_user(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -108,3 +229,104 @@ pub async fn delete_user(
     
     Ok(())
 }
+
+/// Restore user
+///
+/// Restore a soft-deleted user. Requires authentication and admin role.
+pub async fn restore_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    info!("Restore user request received for ID: {}", id);
+
+    let user = state.user_use_cases.restore_user(&id).await?;
+
+    Ok(([(ETAG, etag(user.version))], Json(user)).into_response())
+}
+
+/// Suspend user
+///
+/// Block a user from logging in and from using existing tokens, optionally
+/// with a `reason`. Requires authentication and admin role.
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    request: Option<Json<ChangeStatusRequest>>,
+) -> Result<Response, ApiError> {
+    info!("Suspend user request received for ID: {}", id);
+
+    let reason = request.and_then(|Json(request)| request.reason);
+    let user = state.user_use_cases.suspend_user(&id, reason).await?;
+
+    Ok(([(ETAG, etag(user.version))], Json(user)).into_response())
+}
+
+/// Reactivate user
+///
+/// Return a suspended, locked or deactivated user to active. Requires
+/// authentication and admin role.
+pub async fn reactivate_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    request: Option<Json<ChangeStatusRequest>>,
+) -> Result<Response, ApiError> {
+    info!("Reactivate user request received for ID: {}", id);
+
+    let reason = request.and_then(|Json(request)| request.reason);
+    let user = state.user_use_cases.reactivate_user(&id, reason).await?;
+
+    Ok(([(ETAG, etag(user.version))], Json(user)).into_response())
+}
+
+/// Get locked accounts
+///
+/// List accounts that are temporarily locked after repeated failed logins.
+/// Requires authentication and admin role.
+pub async fn get_locked_accounts(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<LockedAccountDto>>, ApiError> {
+    info!("Get locked accounts request received");
+
+    let accounts = state.auth_use_cases.locked_accounts().await?;
+
+    Ok(Json(accounts))
+}
+
+/// Reset password
+///
+/// Set a new password for a user without knowing the current one, e.g. when
+/// they are locked out. All of the user's tokens are revoked and the reset is
+/// logged under the `audit` tracing target with the acting admin; it is not
+/// stored. Requires authentication and admin role.
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Extension(actor_id): Extension<String>,
+    Json(reset_request): Json<ResetPasswordRequest>,
+) -> Result<Response, ApiError> {
+    info!("Reset password request received for ID: {}", id);
+
+    let user = state
+        .user_use_cases
+        .reset_password(&id, reset_request.password, reset_request.reason, &actor_id)
+        .await?;
+
+    Ok(([(ETAG, etag(user.version))], Json(user)).into_response())
+}
+
+/// Reset second factor
+///
+/// Remove the second factor of a user who lost it, so they log in with
+/// their password alone and can enroll again. Every reset is logged under
+/// the `audit` tracing target with the acting admin.
+pub async fn reset_mfa(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Extension(actor_id): Extension<String>,
+) -> Result<StatusCode, ApiError> {
+    info!("Reset MFA request received for ID: {}", id);
+
+    state.auth_use_cases.reset_mfa(&id, &actor_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
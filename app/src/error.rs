@@ -1,6 +1,9 @@
+use application::dtos::PasswordViolationDto;
 use application::errors::ApplicationError;
+use domain::entities::UserStatus;
+use domain::errors::DomainError;
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -15,12 +18,51 @@ pub enum ApiError {
     #[error("Authorization error: {0}")]
     AuthorizationError(String),
 
+    #[error("Account is {0}")]
+    AccountUnavailable(UserStatus),
+
+    /// The token was issued for an expired password and only permits changing it
+    #[error("Password change required")]
+    PasswordChangeRequired,
+
+    /// The user must verify their email address before logging in or changing data
+    #[error("Email verification required")]
+    EmailNotVerified,
+
+    /// The user's role requires a second factor, which they must enroll first
+    #[error("MFA enrollment required")]
+    MfaEnrollmentRequired,
+
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    #[error("Password rejected")]
+    PasswordRejected(Vec<PasswordViolationDto>),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error("Unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+
+    #[error("Too many requests: {message}")]
+    TooManyRequests { message: String, retry_after: u64 },
+
+    #[error("Rate limit exceeded, retry after {retry_after}s")]
+    RateLimitExceeded { retry_after: u64 },
+
+    #[error("Service unavailable: {message}")]
+    ServiceUnavailable { message: String, retry_after: u64 },
+
     #[error("Internal server error: {0}")]
     InternalServerError(String),
 }
@@ -31,7 +73,21 @@ impl From<ApplicationError> for ApiError {
             ApplicationError::AuthenticationError(msg) => ApiError::AuthenticationError(msg),
             ApplicationError::AuthorizationError(msg) => ApiError::AuthorizationError(msg),
             ApplicationError::ValidationError(msg) => ApiError::ValidationError(msg),
+            ApplicationError::PasswordRejected(violations) => ApiError::PasswordRejected(violations),
             ApplicationError::NotFound(msg) => ApiError::NotFound(msg),
+            ApplicationError::Conflict(msg) => ApiError::Conflict(msg),
+            ApplicationError::PreconditionFailed(msg) => ApiError::PreconditionFailed(msg),
+            ApplicationError::AccountUnavailable(status) => ApiError::AccountUnavailable(status),
+            ApplicationError::EmailNotVerified => ApiError::EmailNotVerified,
+            ApplicationError::TooManyRequests { message, retry_after } => {
+                ApiError::TooManyRequests { message, retry_after }
+            }
+            ApplicationError::ServiceUnavailable { message, retry_after } => {
+                ApiError::ServiceUnavailable { message, retry_after }
+            }
+            ApplicationError::DomainError(DomainError::Conflict(msg)) => ApiError::Conflict(msg),
+            // E.g. a username that is still reserved by a soft-deleted user
+            ApplicationError::DomainError(DomainError::ValidationError(msg)) => ApiError::ValidationError(msg),
             ApplicationError::DomainError(domain_error) => {
                 ApiError::InternalServerError(format!("Domain error: {}", domain_error))
             }
@@ -40,23 +96,88 @@ impl From<ApplicationError> for ApiError {
     }
 }
 
+impl ApiError {
+    /// Machine-readable code for errors that clients need to tell apart
+    /// beyond the HTTP status
+    fn code(&self) -> Option<&'static str> {
+        match self {
+            ApiError::AccountUnavailable(UserStatus::Suspended) => Some("account_suspended"),
+            ApiError::AccountUnavailable(UserStatus::Locked) => Some("account_locked"),
+            ApiError::AccountUnavailable(UserStatus::Deactivated) => Some("account_deactivated"),
+            ApiError::TooManyRequests { .. } => Some("too_many_attempts"),
+            ApiError::RateLimitExceeded { .. } => Some("rate_limited"),
+            ApiError::ServiceUnavailable { .. } => Some("overloaded"),
+            ApiError::PasswordRejected(_) => Some("password_rejected"),
+            ApiError::PasswordChangeRequired => Some("password_change_required"),
+            ApiError::EmailNotVerified => Some("email_not_verified"),
+            ApiError::MfaEnrollmentRequired => Some("mfa_enrollment_required"),
+            _ => None,
+        }
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let code = self.code();
+        let retry_after = match &self {
+            ApiError::TooManyRequests { retry_after, .. }
+            | ApiError::RateLimitExceeded { retry_after }
+            | ApiError::ServiceUnavailable { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        };
+        let violations = match &self {
+            ApiError::PasswordRejected(violations) => Some(violations.clone()),
+            _ => None,
+        };
         let (status, error_message) = match self {
             ApiError::AuthenticationError(msg) => (StatusCode::UNAUTHORIZED, msg),
             ApiError::AuthorizationError(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::AccountUnavailable(status) => (StatusCode::FORBIDDEN, format!("Account is {}", status)),
+            ApiError::PasswordChangeRequired => (
+                StatusCode::FORBIDDEN,
+                "The password has expired and must be changed first".to_string(),
+            ),
+            ApiError::EmailNotVerified => (
+                StatusCode::FORBIDDEN,
+                "The email address must be verified first".to_string(),
+            ),
+            ApiError::MfaEnrollmentRequired => (
+                StatusCode::FORBIDDEN,
+                "A second factor must be enrolled first".to_string(),
+            ),
             ApiError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::PasswordRejected(_) => {
+                (StatusCode::BAD_REQUEST, "Password does not meet the password policy".to_string())
+            }
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            ApiError::PreconditionFailed(msg) => (StatusCode::PRECONDITION_FAILED, msg),
+            ApiError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg),
+            ApiError::UnprocessableEntity(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg),
+            ApiError::TooManyRequests { message, .. } => (StatusCode::TOO_MANY_REQUESTS, message),
+            ApiError::RateLimitExceeded { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded".to_string())
+            }
+            ApiError::ServiceUnavailable { message, .. } => (StatusCode::SERVICE_UNAVAILABLE, message),
             ApiError::InternalServerError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
         };
 
-        let body = Json(json!({
-            "error": {
-                "message": error_message,
-                "status": status.as_u16()
-            }
-        }));
+        let mut error = json!({
+            "message": error_message,
+            "status": status.as_u16()
+        });
+        if let Some(code) = code {
+            error["code"] = json!(code);
+        }
+        if let Some(violations) = violations {
+            error["violations"] = json!(violations);
+        }
+        let body = Json(json!({ "error": error }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(retry_after) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after.into());
+        }
+        response
     }
 }
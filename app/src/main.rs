@@ -1,5 +1,4 @@
 mod api;
-mod config;
 mod error;
 mod middleware;
 #[cfg(test)]
@@ -7,12 +6,21 @@ mod tests;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::Router;
-use infrastructure::config::EnvConfigProvider;
-use infrastructure::persistence::memory::InMemoryUserRepository;
-use infrastructure::persistence::postgres::create_postgres_pool;
-use infrastructure::security::{BcryptPasswordService, JwtServiceImpl};
+use application::services::{
+    AuthService, AuthServiceImpl, EmailVerificationRule, EmailVerificationService, EmailVerificationServiceImpl,
+    JwtService, LockoutPolicy, LoginThrottle, MfaService, MfaServiceImpl, PasswordPolicy, PasswordResetService,
+    PasswordResetServiceImpl, PasswordRotationPolicy, PasswordService, PasswordValidator, UserService, UserServiceImpl,
+};
+use domain::entities::RoleName;
+use infrastructure::config::{ConfigProvider, EmailVerificationRequirement, EnvConfigProvider};
+use infrastructure::email::create_notifier;
+use infrastructure::persistence::create_storage;
+use infrastructure::security::{
+    AesGcmSecretCipher, Argon2Hasher, HashingPoolStats, HibpRangeDirectory, HmacTotpService, JwtServiceImpl,
+    PooledPasswordService, RandomTokenService,
+};
 use infrastructure::tracing::init_tracing;
 use tokio::signal;
 use tracing::info;
@@ -25,58 +33,169 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting server...");
 
     // Load configuration
-    let config_provider = Arc::new(EnvConfigProvider::new()?);
+    let config_provider: Arc<dyn ConfigProvider> = Arc::new(EnvConfigProvider::new()?);
     let config = config_provider.get_config();
 
-    // Create PostgreSQL connection pool
-    let pg_pool = create_postgres_pool(Arc::clone(&config_provider)).await?;
-
-    // Create repositories
-    let postgres_user_repo = Arc::new(infrastructure::persistence::postgres::PostgresUserRepository::new(pg_pool));
-    let memory_user_repo = Arc::new(InMemoryUserRepository::new());
+    // Create the repositories for the configured storage backend
+    let storage = create_storage(Arc::clone(&config_provider)).await?;
+    let user_repository = Arc::clone(&storage.user_repository);
 
     // Create services
-    let password_service = Arc::new(BcryptPasswordService::new(None));
-    let jwt_service = Arc::new(JwtServiceImpl::new(Arc::clone(&config_provider)));
-
-    // Determine which repository to use based on environment variable
-    let use_memory_repo = std::env::var("USE_MEMORY_REPO")
-        .unwrap_or_else(|_| "false".to_string())
-        .parse::<bool>()
-        .unwrap_or(false);
-
-    // Create application services with the appropriate repository
-    let auth_service = if use_memory_repo {
-        info!("Using in-memory repository");
-        Arc::new(application::services::AuthServiceImpl::new(
-            Arc::clone(&memory_user_repo),
-            Arc::clone(&jwt_service),
-            Arc::clone(&password_service),
-        ))
-    } else {
-        info!("Using PostgreSQL repository");
-        Arc::new(application::services::AuthServiceImpl::new(
-            Arc::clone(&postgres_user_repo),
-            Arc::clone(&jwt_service),
-            Arc::clone(&password_service),
-        ))
+    let pooled_password_service = Arc::new(PooledPasswordService::new(
+        Argon2Hasher::new(&config.password_hashing)?,
+        &config.password_hashing,
+    )?);
+    let password_service: Arc<dyn PasswordService> = Arc::clone(&pooled_password_service) as _;
+    let hashing_stats_task = (config.password_hashing.stats_interval > 0).then(|| {
+        let pooled_password_service = Arc::clone(&pooled_password_service);
+        let period = Duration::from_secs(config.password_hashing.stats_interval);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                log_hashing_stats(&pooled_password_service.stats());
+            }
+        })
+    });
+    let jwt_service: Arc<dyn JwtService> = Arc::new(JwtServiceImpl::new(Arc::clone(&config_provider)));
+
+    let lockout = &config.lockout;
+    let login_throttle = Arc::new(LoginThrottle::new(
+        Arc::clone(&storage.login_attempt_repository),
+        LockoutPolicy {
+            account_threshold: lockout.account_threshold,
+            ip_threshold: lockout.ip_threshold,
+            base_delay: chrono::Duration::seconds(lockout.base_delay as i64),
+            max_delay: chrono::Duration::seconds(lockout.max_delay as i64),
+            reset_after: chrono::Duration::seconds(lockout.reset_after as i64),
+        },
+    ));
+
+    let policy = &config.password_policy;
+    let mut password_validator = PasswordValidator::new(PasswordPolicy {
+        min_length: policy.min_length,
+        max_length: policy.max_length,
+        require_lowercase: policy.require_lowercase,
+        require_uppercase: policy.require_uppercase,
+        require_digit: policy.require_digit,
+        require_symbol: policy.require_symbol,
+        reject_account_names: policy.reject_account_names,
+        min_strength: policy.min_strength,
+    });
+    if let Some(directory) = &policy.breached_passwords_dir {
+        password_validator = password_validator
+            .with_breached_check(Arc::new(HibpRangeDirectory::new(directory)), policy.breached_min_count);
+    }
+    let max_age_days = [
+        (RoleName::Admin, policy.max_age_days.admin),
+        (RoleName::Manager, policy.max_age_days.manager),
+        (RoleName::User, policy.max_age_days.user),
+        (RoleName::Guest, policy.max_age_days.guest),
+    ];
+    let password_validator = Arc::new(password_validator.with_rotation(PasswordRotationPolicy {
+        reuse_limit: policy.reuse_limit,
+        max_age: max_age_days
+            .into_iter()
+            .filter(|(_, days)| *days > 0)
+            .map(|(role, days)| (role, chrono::Duration::days(days as i64)))
+            .collect(),
+    }));
+
+    let notifier = create_notifier(&config.email)?;
+    let one_time_tokens = Arc::new(RandomTokenService);
+
+    // Create application services
+    let mut email_verification_service_impl = EmailVerificationServiceImpl::new(
+        Arc::clone(&user_repository),
+        Arc::clone(&storage.email_verification_repository),
+        Arc::clone(&one_time_tokens) as _,
+        Arc::clone(&notifier),
+    )
+    .with_token_ttl(chrono::Duration::seconds(config.email_verification.token_ttl as i64));
+    if let Some(url) = &config.email_verification.url {
+        email_verification_service_impl = email_verification_service_impl.with_verification_url(url.clone());
+    }
+    let email_verification_service: Arc<dyn EmailVerificationService> = Arc::new(email_verification_service_impl);
+    let email_verification_rule = match config.email_verification.required {
+        EmailVerificationRequirement::None => EmailVerificationRule::Optional,
+        EmailVerificationRequirement::Writes => EmailVerificationRule::RequiredForWrites,
+        EmailVerificationRequirement::Login => EmailVerificationRule::RequiredForLogin,
     };
 
-    let user_service = if use_memory_repo {
-        Arc::new(application::services::UserServiceImpl::new(
-            Arc::clone(&memory_user_repo),
+    let mfa = &config.mfa;
+    let mfa_required_roles = [
+        (RoleName::Admin, mfa.required.admin),
+        (RoleName::Manager, mfa.required.manager),
+        (RoleName::User, mfa.required.user),
+        (RoleName::Guest, mfa.required.guest),
+    ];
+    let mfa_service: Arc<dyn MfaService> = Arc::new(
+        MfaServiceImpl::new(
+            Arc::clone(&user_repository),
+            Arc::clone(&storage.mfa_repository),
+            Arc::new(HmacTotpService::new(mfa.issuer.clone(), mfa.skew)),
+            Arc::new(AesGcmSecretCipher::new(&mfa.encryption_key)),
+            Arc::clone(&one_time_tokens) as _,
             Arc::clone(&password_service),
-        ))
-    } else {
-        Arc::new(application::services::UserServiceImpl::new(
-            Arc::clone(&postgres_user_repo),
+            Arc::clone(&login_throttle),
+        )
+        .with_required_roles(
+            mfa_required_roles
+                .into_iter()
+                .filter(|(_, required)| *required)
+                .map(|(role, _)| role)
+                .collect(),
+        )
+        .with_recovery_code_count(mfa.recovery_codes),
+    );
+
+    let mut auth_service_impl = AuthServiceImpl::new(
+        Arc::clone(&user_repository),
+        Arc::clone(&jwt_service),
+        config.jwt.expiration,
+        Arc::clone(&password_service),
+        login_throttle,
+        Arc::clone(&password_validator),
+    )
+    .with_email_verification(Arc::clone(&email_verification_service), email_verification_rule)
+    .with_mfa(Arc::clone(&mfa_service));
+    if config.registration.uniform_responses {
+        auth_service_impl = auth_service_impl.with_uniform_registration(Arc::clone(&notifier));
+    }
+    let auth_service: Arc<dyn AuthService> = Arc::new(auth_service_impl);
+
+    let mut password_reset_service_impl = PasswordResetServiceImpl::new(
+        Arc::clone(&user_repository),
+        Arc::clone(&storage.password_reset_repository),
+        one_time_tokens,
+        Arc::clone(&password_service),
+        Arc::clone(&password_validator),
+        notifier,
+    )
+    .with_token_ttl(chrono::Duration::seconds(config.password_reset.token_ttl as i64));
+    if let Some(url) = &config.password_reset.url {
+        password_reset_service_impl = password_reset_service_impl.with_reset_url(url.clone());
+    }
+    let password_reset_service: Arc<dyn PasswordResetService> = Arc::new(password_reset_service_impl);
+
+    let user_service: Arc<dyn UserService> = Arc::new(
+        UserServiceImpl::new(
+            Arc::clone(&user_repository),
+            Arc::clone(&storage.unit_of_work),
             Arc::clone(&password_service),
-        ))
-    };
+            password_validator,
+        )
+        .with_email_verification(Arc::clone(&email_verification_service)),
+    );
 
     // Create use cases
     let auth_use_cases = Arc::new(application::use_cases::AuthUseCases::new(
         Arc::clone(&auth_service),
+        Arc::clone(&password_reset_service),
+        email_verification_service,
+        mfa_service,
     ));
 
     let user_use_cases = Arc::new(application::use_cases::UserUseCases::new(
@@ -87,8 +206,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let app_state = api::AppState {
         auth_use_cases,
         user_use_cases,
-        jwt_service: Arc::clone(&jwt_service),
         config_provider: Arc::clone(&config_provider),
+        idempotency_repository: Arc::clone(&storage.idempotency_repository),
+        rate_limit_repository: Arc::clone(&storage.rate_limit_repository),
     };
 
     // Build the router
@@ -98,15 +218,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
     info!("Listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    // The peer address is needed to lock out clients by IP
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await?;
 
+    if let Some(task) = hashing_stats_task {
+        task.abort();
+    }
+    log_hashing_stats(&pooled_password_service.stats());
+
+    storage.shutdown().await?;
+
     info!("Server shutdown complete");
     Ok(())
 }
 
+fn log_hashing_stats(stats: &HashingPoolStats) {
+    info!(
+        queue_depth = stats.queue_depth,
+        running = stats.running,
+        completed = stats.completed,
+        rejected = stats.rejected,
+        mean_latency_ms = stats.mean_latency.as_millis() as u64,
+        max_latency_ms = stats.max_latency.as_millis() as u64,
+        mean_queue_wait_ms = stats.mean_queue_wait.as_millis() as u64,
+        "Password hashing statistics"
+    );
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
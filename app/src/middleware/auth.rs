@@ -1,25 +1,35 @@
 use crate::error::ApiError;
-use application::services::JwtService;
+use application::errors::ApplicationError;
+use application::use_cases::AuthUseCases;
 use axum::{
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{Request, State},
     middleware::Next,
     response::Response,
 };
-use domain::entities::role::RoleName;
+use domain::entities::RoleName;
+use domain::value_objects::TokenScope;
 use std::sync::Arc;
 use tracing::{info, instrument};
 
 #[derive(Clone)]
 pub struct AuthState {
-    pub jwt_service: Arc<dyn JwtService>,
+    pub auth_use_cases: Arc<AuthUseCases>,
+    /// Whether tokens issued for an expired password are accepted; only for
+    /// the password change route
+    pub accept_password_change_tokens: bool,
+    /// Whether read-only tokens of users with an unverified email address may
+    /// write; only for the password change route
+    pub allow_unverified_email: bool,
+    /// Whether tokens of users who must enroll a second factor first are
+    /// accepted; only for the MFA routes
+    pub accept_mfa_enrollment_tokens: bool,
 }
 
 #[instrument(skip(state, request, next))]
-pub async fn auth_middleware<B>(
+pub async fn auth_middleware(
     State(state): State<AuthState>,
-    mut request: Request<B>,
-    next: Next<B>,
+    mut request: Request,
+    next: Next,
 ) -> Result<Response, ApiError> {
     info!("Running auth middleware");
 
@@ -40,26 +50,42 @@ pub async fn auth_middleware<B>(
     // Extract the token
     let token = &auth_header[7..];
 
-    // Validate the token
+    // Validate the token and the status of its user
     let claims = state
-        .jwt_service
+        .auth_use_cases
         .validate_token(token)
         .await
-        .map_err(|e| ApiError::AuthenticationError(format!("Invalid token: {}", e)))?;
+        .map_err(|e| match e {
+            ApplicationError::AuthenticationError(_) => {
+                ApiError::AuthenticationError(format!("Invalid token: {}", e))
+            }
+            e => ApiError::from(e),
+        })?;
 
-    // Add the user ID and role to the request extensions
+    if claims.scope == TokenScope::PasswordChange && !state.accept_password_change_tokens {
+        return Err(ApiError::PasswordChangeRequired);
+    }
+    if claims.scope == TokenScope::ReadOnly && !request.method().is_safe() && !state.allow_unverified_email {
+        return Err(ApiError::EmailNotVerified);
+    }
+    if claims.scope == TokenScope::MfaEnrollment && !state.accept_mfa_enrollment_tokens {
+        return Err(ApiError::MfaEnrollmentRequired);
+    }
+
+    // Add the user ID, role and token scope to the request extensions
     request.extensions_mut().insert(claims.sub.clone());
     request.extensions_mut().insert(claims.role);
+    request.extensions_mut().insert(claims.scope);
 
     // Continue with the request
     Ok(next.run(request).await)
 }
 
 #[instrument(skip(request, next))]
-pub async fn require_role<B>(
+pub async fn require_role(
     role: RoleName,
-    mut request: Request<B>,
-    next: Next<B>,
+    request: Request,
+    next: Next,
 ) -> Result<Response, ApiError> {
     info!("Checking role requirement: {:?}", role);
 
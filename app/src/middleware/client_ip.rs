@@ -0,0 +1,123 @@
+use crate::error::ApiError;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use infrastructure::config::IpCidr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Address of the client that made the request, as opposed to the proxy that
+/// forwarded it. Resolved by [`client_ip_middleware`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ClientIp>()
+            .copied()
+            .ok_or_else(|| ApiError::InternalServerError("Client address is not known".to_string()))
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientIpState {
+    pub trusted_proxies: Arc<[IpCidr]>,
+}
+
+/// Resolves the [`ClientIp`] of every request from the peer address and, if the
+/// peer is a trusted proxy, the forwarding headers.
+pub async fn client_ip_middleware(
+    State(state): State<ClientIpState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let client_ip = resolve_client_ip(peer.ip(), request.headers(), &state.trusted_proxies);
+        request.extensions_mut().insert(ClientIp(client_ip));
+    }
+
+    next.run(request).await
+}
+
+/// Walks the forwarding chain from the nearest hop outwards and returns the
+/// first address that is not a trusted proxy. Only trusted proxies can add
+/// hops, so anything a client put into the headers itself is never reached.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpCidr]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|cidr| cidr.contains(ip));
+
+    let mut client = peer;
+    if !is_trusted(&peer) {
+        return client;
+    }
+
+    for hop in forwarded_for(headers).into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = ip;
+                if !is_trusted(&ip) {
+                    break;
+                }
+            }
+            // An obfuscated or malformed hop: the last trusted proxy is as far as we can see
+            None => break,
+        }
+    }
+
+    client
+}
+
+/// Hops listed in `Forwarded`, or if there is none in `X-Forwarded-For`,
+/// nearest last. Hops without a usable address are `None`.
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>()
+    };
+
+    let forwarded = values(header::FORWARDED);
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+
+    values(header::HeaderName::from_static("x-forwarded-for"))
+        .into_iter()
+        .map(parse_node)
+        .collect()
+}
+
+/// Parses `192.0.2.1`, `192.0.2.1:4711`, `2001:db8::1` or `"[2001:db8::1]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(rest) = node.strip_prefix('[') {
+        let (address, _port) = rest.split_once(']')?;
+        return address.parse().ok();
+    }
+
+    node.parse().ok().or_else(|| {
+        let (address, _port) = node.rsplit_once(':')?;
+        address.parse::<std::net::Ipv4Addr>().ok().map(IpAddr::V4)
+    })
+}
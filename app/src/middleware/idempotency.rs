@@ -0,0 +1,193 @@
+use crate::error::ApiError;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{OriginalUri, Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use domain::entities::{IdempotencyRecord, StoredResponse};
+use domain::repositories::IdempotencyRepository;
+use hmac::{Hmac, Mac};
+use infrastructure::config::Secret;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses replayed from the idempotency store
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LENGTH: usize = 255;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// After this long a request that never finished (e.g. the server crashed
+/// while handling it) no longer blocks its key
+const ABANDON_AFTER_SECS: i64 = 300;
+/// Response headers kept for replay
+const STORED_HEADERS: [HeaderName; 3] = [header::CONTENT_TYPE, header::ETAG, header::LOCATION];
+
+#[derive(Clone)]
+pub struct IdempotencyState {
+    pub repository: Arc<dyn IdempotencyRepository>,
+    /// How long a key and its response are kept
+    pub ttl: chrono::Duration,
+    /// Keys the HMAC that fingerprints request bodies
+    pub fingerprint_key: Secret,
+}
+
+/// Executes a mutating request carrying an `Idempotency-Key` at most once.
+///
+/// Retries with the same key and body get the stored response; the same key
+/// with a different body is rejected with 422. Keys are scoped to the caller's
+/// `Authorization` header and the endpoint. Server errors are not stored, so
+/// the request can be retried.
+///
+/// Only for endpoints whose responses hold no credentials: bodies are only
+/// stored as a keyed fingerprint, but responses are stored as they are.
+#[instrument(skip(state, request, next))]
+pub async fn idempotency_middleware(
+    State(state): State<IdempotencyState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(next.run(request).await);
+    }
+
+    let Some(key) = request.headers().get(&IDEMPOTENCY_KEY) else {
+        return Ok(next.run(request).await);
+    };
+
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .ok_or_else(|| {
+            ApiError::ValidationError(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LENGTH
+            ))
+        })?;
+
+    let scope = scoped_key(&request, key);
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|e| ApiError::ValidationError(format!("Failed to read request body: {}", e)))?;
+    let fingerprint = fingerprint(&state.fingerprint_key, &body);
+
+    let record = IdempotencyRecord::new(scope.clone(), fingerprint, state.ttl);
+    let abandoned_before = record.created_at - chrono::Duration::seconds(ABANDON_AFTER_SECS);
+
+    match state.repository.try_begin(&record, abandoned_before).await {
+        Ok(None) => {}
+        Ok(Some(existing)) if existing.fingerprint != record.fingerprint => {
+            return Err(ApiError::UnprocessableEntity(
+                "Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+        Ok(Some(existing)) => {
+            return match existing.response {
+                Some(stored) => {
+                    info!("Replaying stored response for idempotency key");
+                    Ok(replay(stored))
+                }
+                None => Err(ApiError::Conflict(
+                    "A request with this Idempotency-Key is still being processed".to_string(),
+                )),
+            };
+        }
+        Err(e) => {
+            return Err(ApiError::InternalServerError(format!("Idempotency store error: {}", e)));
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    Ok(store(&state, &scope, response).await)
+}
+
+/// Keyed hash of a request body. Bodies may contain passwords, which a plain
+/// hash would expose to fast offline guessing.
+fn fingerprint(key: &Secret, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.expose().as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Key under which the request is stored: the client's key is only unique
+/// for one caller and endpoint.
+fn scoped_key(request: &Request, key: &str) -> String {
+    let mut hasher = Sha256::new();
+    if let Some(authorization) = request.headers().get(header::AUTHORIZATION) {
+        hasher.update(authorization.as_bytes());
+    }
+    hasher.update(b"\n");
+    hasher.update(request.method().as_str());
+    hasher.update(b" ");
+    // Nested routers see the URI with their prefix stripped
+    let path = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path(),
+        None => request.uri().path(),
+    };
+    hasher.update(path);
+    hasher.update(b"\n");
+    hasher.update(key);
+    hex::encode(hasher.finalize())
+}
+
+/// Records `response` for `key` and returns it.
+async fn store(state: &IdempotencyState, key: &str, response: Response) -> Response {
+    let (parts, body) = response.into_parts();
+
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) if !parts.status.is_server_error() => body,
+        result => {
+            // Let the client retry the request
+            if let Err(e) = state.repository.remove(key).await {
+                error!("Failed to release idempotency key: {}", e);
+            }
+            return match result {
+                Ok(body) => Response::from_parts(parts, Body::from(body)),
+                Err(e) => ApiError::InternalServerError(format!("Failed to read response body: {}", e))
+                    .into_response(),
+            };
+        }
+    };
+
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: STORED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = parts.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect(),
+        body: body.to_vec(),
+    };
+
+    if let Err(e) = state.repository.complete(key, &stored).await {
+        error!("Failed to store idempotent response: {}", e);
+        if let Err(e) = state.repository.remove(key).await {
+            error!("Failed to release idempotency key: {}", e);
+        }
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+
+    response
+}
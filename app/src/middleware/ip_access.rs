@@ -0,0 +1,36 @@
+use crate::error::ApiError;
+use crate::middleware::client_ip::ClientIp;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use infrastructure::config::IpAccessRules;
+use std::sync::Arc;
+use tracing::warn;
+
+#[derive(Clone)]
+pub struct IpAccessState {
+    pub rules: Arc<IpAccessRules>,
+}
+
+/// Refuses requests whose [`ClientIp`] the rules do not permit with 403. A
+/// request whose client address is not known is refused as well.
+pub async fn ip_access_middleware(
+    State(state): State<IpAccessState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    match request.extensions().get::<ClientIp>() {
+        Some(ClientIp(ip)) if state.rules.permits(ip) => Ok(next.run(request).await),
+        client_ip => {
+            let client_ip = client_ip
+                .map(|ClientIp(ip)| ip.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            warn!(client_ip = %client_ip, "Refusing request by IP access rules");
+            Err(ApiError::AuthorizationError(
+                "Access from this address is not allowed".to_string(),
+            ))
+        }
+    }
+}
@@ -1,5 +1,13 @@
 mod auth;
+pub mod client_ip;
+pub mod idempotency;
+pub mod ip_access;
+pub mod rate_limit;
 mod tracing;
 
 pub use auth::*;
+pub use client_ip::*;
+pub use idempotency::*;
+pub use ip_access::*;
+pub use rate_limit::*;
 pub use tracing::*;
@@ -0,0 +1,100 @@
+use crate::error::ApiError;
+use crate::middleware::client_ip::ClientIp;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use domain::entities::{RateLimitDecision, RateLimitQuota};
+use domain::repositories::RateLimitRepository;
+use infrastructure::config::RateLimitKey;
+use std::sync::Arc;
+use tracing::{error, instrument, warn};
+
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+pub const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+#[derive(Clone)]
+pub struct RateLimitState {
+    pub repository: Arc<dyn RateLimitRepository>,
+    /// Prefix of the stored keys, so that every route group has its own budget
+    pub group: &'static str,
+    pub quota: RateLimitQuota,
+    pub key: RateLimitKey,
+}
+
+/// Limits requests per client address or user with the configured quota.
+///
+/// Every response carries `RateLimit-Limit`, `RateLimit-Remaining`,
+/// `RateLimit-Reset` and `RateLimit-Policy`; refused requests get 429 with
+/// `Retry-After`. When the store fails the request is let through, so an
+/// outage of the store does not take the API down with it.
+#[instrument(skip(state, request, next), fields(group = state.group))]
+pub async fn rate_limit_middleware(
+    State(state): State<RateLimitState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(subject) = subject(&state, &request) else {
+        warn!("Cannot identify the client, not rate limiting the request");
+        return Ok(next.run(request).await);
+    };
+    let key = format!("{}:{}", state.group, subject);
+
+    let decision = match state.repository.acquire(&key, &state.quota, Utc::now()).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            error!("Rate limit store error: {}", e);
+            return Ok(next.run(request).await);
+        }
+    };
+
+    let mut response = match decision.retry_after {
+        Some(retry_after) if !decision.allowed => ApiError::RateLimitExceeded {
+            retry_after: ceil_seconds(retry_after).max(1),
+        }
+        .into_response(),
+        _ => next.run(request).await,
+    };
+    set_headers(response.headers_mut(), &state.quota, &decision);
+
+    Ok(response)
+}
+
+/// Who the request is counted against
+fn subject(state: &RateLimitState, request: &Request) -> Option<String> {
+    if state.key == RateLimitKey::User {
+        // Set by the auth middleware
+        if let Some(user_id) = request.extensions().get::<String>() {
+            return Some(format!("user:{}", user_id));
+        }
+    }
+
+    let ClientIp(ip) = request.extensions().get::<ClientIp>()?;
+    Some(format!("ip:{}", ip))
+}
+
+fn set_headers(headers: &mut HeaderMap, quota: &RateLimitQuota, decision: &RateLimitDecision) {
+    headers.insert(RATELIMIT_LIMIT, decision.limit.into());
+    headers.insert(RATELIMIT_REMAINING, decision.remaining.into());
+    headers.insert(RATELIMIT_RESET, ceil_seconds(decision.reset_after).into());
+
+    let policy = format!(
+        "{};w={};burst={}",
+        quota.limit,
+        quota.period.num_seconds(),
+        quota.burst
+    );
+    if let Ok(policy) = HeaderValue::from_str(&policy) {
+        headers.insert(RATELIMIT_POLICY, policy);
+    }
+}
+
+fn ceil_seconds(duration: Duration) -> u64 {
+    let millis = duration.num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000)
+}
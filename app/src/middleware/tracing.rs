@@ -1,5 +1,6 @@
 use axum::{
-    http::{Request, StatusCode},
+    extract::Request,
+    http::StatusCode,
     middleware::Next,
     response::Response,
 };
@@ -7,6 +8,8 @@ use std::time::Instant;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer};
 use tracing::{info, instrument, Level};
 
+use crate::middleware::client_ip::ClientIp;
+
 pub fn create_tracing_layer() -> TraceLayer<tower_http::classify::SharedClassifier<tower_http::classify::ServerErrorsAsFailures>> {
     info!("Creating tracing layer");
     
@@ -25,16 +28,22 @@ pub fn create_tracing_layer() -> TraceLayer<tower_http::classify::SharedClassifi
 }
 
 #[instrument(skip(request, next))]
-pub async fn request_tracing_middleware<B>(
-    request: Request<B>,
-    next: Next<B>,
+pub async fn request_tracing_middleware(
+    request: Request,
+    next: Next,
 ) -> Result<Response, StatusCode> {
     let path = request.uri().path().to_owned();
     let method = request.method().clone();
+    let client_ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
     
     info!(
         method = %method,
         path = %path,
+        client_ip = %client_ip,
         "Request started"
     );
     
@@ -45,6 +54,7 @@ pub async fn request_tracing_middleware<B>(
     info!(
         method = %method,
         path = %path,
+        client_ip = %client_ip,
         status = %response.status(),
         duration = ?duration,
         "Request completed"
@@ -1,38 +1,118 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use std::sync::Arc;
 
-    use infrastructure::config::EnvConfigProvider;
-    use infrastructure::persistence::memory::InMemoryUserRepository;
-    use infrastructure::security::{BcryptPasswordService, JwtServiceImpl};
+    use application::services::{
+        AuthService, AuthServiceImpl, EmailVerificationService, EmailVerificationServiceImpl, JwtService,
+        LockoutPolicy, LoginThrottle, MfaService, MfaServiceImpl, PasswordPolicy, PasswordResetService,
+        PasswordResetServiceImpl, PasswordService, PasswordValidator, UserService, UserServiceImpl,
+    };
+    use domain::repositories::{UnitOfWork, UserRepository};
+    use infrastructure::config::{ConfigProvider, EnvConfigProvider, Secret};
+    use infrastructure::email::LogNotifier;
+    use infrastructure::persistence::{
+        InMemoryEmailVerificationTokenRepository, InMemoryIdempotencyRepository, InMemoryLoginAttemptRepository,
+        InMemoryMfaRepository, InMemoryPasswordResetTokenRepository, InMemoryRateLimitRepository, InMemoryUnitOfWork,
+        InMemoryUserRepository,
+    };
+    use infrastructure::security::{
+        AesGcmSecretCipher, BcryptHasher, HmacTotpService, JwtServiceImpl, PooledPasswordService, RandomTokenService,
+    };
 
-    #[tokio::test]
-    async fn test_app_state_creation() {
-        // Load configuration
-        let config_provider = Arc::new(EnvConfigProvider::new().unwrap());
+    /// The application state over in-memory repositories
+    fn app_state() -> crate::api::AppState {
+        // Load configuration, with the secrets that have no default
+        std::env::set_var("IDEMPOTENCY_SECRET", "test");
+        std::env::set_var("MFA_ENCRYPTION_KEY", "test");
+        let config_provider: Arc<dyn ConfigProvider> = Arc::new(EnvConfigProvider::new().unwrap());
 
         // Create repositories
-        let memory_user_repo = Arc::new(InMemoryUserRepository::new());
+        let memory_repo = Arc::new(InMemoryUserRepository::new());
+        let memory_user_repo: Arc<dyn UserRepository> = memory_repo.clone();
+        let unit_of_work: Arc<dyn UnitOfWork> = Arc::new(InMemoryUnitOfWork::new(memory_repo));
 
         // Create services
-        let password_service = Arc::new(BcryptPasswordService::new(None));
-        let jwt_service = Arc::new(JwtServiceImpl::new(Arc::clone(&config_provider)));
+        let password_service: Arc<dyn PasswordService> = Arc::new(
+            PooledPasswordService::new(BcryptHasher::new(None), &config_provider.get_config().password_hashing)
+                .unwrap(),
+        );
+        let jwt_service: Arc<dyn JwtService> = Arc::new(JwtServiceImpl::new(Arc::clone(&config_provider)));
+
+        let login_throttle = Arc::new(LoginThrottle::new(
+            Arc::new(InMemoryLoginAttemptRepository::new()),
+            LockoutPolicy {
+                account_threshold: 5,
+                ip_threshold: 20,
+                base_delay: chrono::Duration::seconds(30),
+                max_delay: chrono::Duration::minutes(15),
+                reset_after: chrono::Duration::days(1),
+            },
+        ));
+        let password_validator = Arc::new(PasswordValidator::new(PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_account_names: true,
+            min_strength: 2,
+        }));
 
         // Create application services
-        let auth_service = Arc::new(application::services::AuthServiceImpl::new(
-            Arc::clone(&memory_user_repo), // Use in-memory repository for testing
-            Arc::clone(&jwt_service),
+        let mfa_service: Arc<dyn MfaService> = Arc::new(MfaServiceImpl::new(
+            Arc::clone(&memory_user_repo),
+            Arc::new(InMemoryMfaRepository::new()),
+            Arc::new(HmacTotpService::new("test".to_string(), 1)),
+            Arc::new(AesGcmSecretCipher::new(&Secret::new("test"))),
+            Arc::new(RandomTokenService),
+            Arc::clone(&password_service),
+            Arc::clone(&login_throttle),
+        ));
+
+        let auth_service: Arc<dyn AuthService> = Arc::new(
+            AuthServiceImpl::new(
+                Arc::clone(&memory_user_repo), // Use in-memory repository for testing
+                Arc::clone(&jwt_service),
+                config_provider.get_config().jwt.expiration,
+                Arc::clone(&password_service),
+                Arc::clone(&login_throttle),
+                Arc::clone(&password_validator),
+            )
+            .with_mfa(Arc::clone(&mfa_service)),
+        );
+
+        let password_reset_service: Arc<dyn PasswordResetService> = Arc::new(PasswordResetServiceImpl::new(
+            Arc::clone(&memory_user_repo),
+            Arc::new(InMemoryPasswordResetTokenRepository::new()),
+            Arc::new(RandomTokenService),
             Arc::clone(&password_service),
+            Arc::clone(&password_validator),
+            Arc::new(LogNotifier),
         ));
 
-        let user_service = Arc::new(application::services::UserServiceImpl::new(
+        let email_verification_service: Arc<dyn EmailVerificationService> =
+            Arc::new(EmailVerificationServiceImpl::new(
+                Arc::clone(&memory_user_repo),
+                Arc::new(InMemoryEmailVerificationTokenRepository::new()),
+                Arc::new(RandomTokenService),
+                Arc::new(LogNotifier),
+            ));
+
+        let user_service: Arc<dyn UserService> = Arc::new(UserServiceImpl::new(
             Arc::clone(&memory_user_repo), // Use in-memory repository for testing
+            Arc::clone(&unit_of_work),
             Arc::clone(&password_service),
+            password_validator,
         ));
 
         // Create use cases
         let auth_use_cases = Arc::new(application::use_cases::AuthUseCases::new(
             Arc::clone(&auth_service),
+            Arc::clone(&password_reset_service),
+            Arc::clone(&email_verification_service),
+            Arc::clone(&mfa_service),
         ));
 
         let user_use_cases = Arc::new(application::use_cases::UserUseCases::new(
@@ -40,17 +120,98 @@ mod tests {
         ));
 
         // Build the application state
-        let app_state = crate::api::AppState {
+        crate::api::AppState {
             auth_use_cases,
             user_use_cases,
-            jwt_service: Arc::clone(&jwt_service),
             config_provider: Arc::clone(&config_provider),
-        };
+            idempotency_repository: Arc::new(InMemoryIdempotencyRepository::new()),
+            rate_limit_repository: Arc::new(InMemoryRateLimitRepository::new()),
+        }
+    }
 
+    #[tokio::test]
+    async fn test_app_state_creation() {
         // Build the router
-        let _app = crate::api::create_router(app_state);
+        let _app = crate::api::create_router(app_state());
+    }
+
+    #[tokio::test]
+    async fn test_update_user_rejects_passwords() {
+        use axum::extract::{Path, State};
+        use axum::http::HeaderMap;
+        use axum::Json;
+
+        use crate::api::users::{update_user, UpdateUserRequest};
+        use crate::error::ApiError;
+
+        let request = UpdateUserRequest {
+            username: None,
+            email: None,
+            password: Some("new long password".to_string()),
+            role: None,
+        };
+        let result = update_user(
+            State(app_state()),
+            Path("00000000-0000-0000-0000-000000000001".to_string()),
+            HeaderMap::new(),
+            Json(request),
+        )
+        .await;
+
+        // Rejected before the user is even looked up
+        assert!(matches!(result, Err(ApiError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_conditional_request_headers() {
+        use axum::http::{HeaderMap, HeaderValue};
+
+        use crate::api::etag::{etag, if_match, if_none_match};
+
+        assert_eq!(etag(3), HeaderValue::from_static("\"3\""));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(if_match(&headers), None);
+        assert!(!if_none_match(&headers, 3));
+
+        headers.insert("if-match", HeaderValue::from_static("\"2\", W/\"3\", \"4\""));
+        headers.insert("if-none-match", HeaderValue::from_static("W/\"3\""));
+        // Weak tags never satisfy If-Match but do satisfy If-None-Match
+        assert_eq!(if_match(&headers), Some(vec![2, 4]));
+        assert!(if_none_match(&headers, 3));
+        assert!(!if_none_match(&headers, 4));
+
+        headers.insert("if-match", HeaderValue::from_static("*"));
+        assert_eq!(if_match(&headers), None);
+    }
+
+    #[test]
+    fn test_client_ip_resolution() {
+        use axum::http::{HeaderMap, HeaderValue};
+        use infrastructure::config::IpCidr;
+        use std::net::IpAddr;
+
+        use crate::middleware::client_ip::resolve_client_ip;
+
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+        let trusted: Vec<IpCidr> = vec!["10.0.0.0/8".parse().unwrap(), "2001:db8::/32".parse().unwrap()];
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("6.6.6.6, 203.0.113.7, 10.1.1.1"));
+        // Headers from an untrusted peer are ignored
+        assert_eq!(resolve_client_ip(ip("198.51.100.1"), &headers, &trusted), ip("198.51.100.1"));
+        // Behind trusted proxies the nearest untrusted hop is the client, not what it claimed
+        assert_eq!(resolve_client_ip(ip("10.0.0.1"), &headers, &trusted), ip("203.0.113.7"));
+
+        // Forwarded takes precedence, with quoted IPv6 and ports
+        headers.insert(
+            "forwarded",
+            HeaderValue::from_static(r#"for=203.0.113.9:4711;proto=https, For="[2001:db8::1]:80""#),
+        );
+        assert_eq!(resolve_client_ip(ip("::ffff:10.0.0.1"), &headers, &trusted), ip("203.0.113.9"));
 
-        // If we get here without panicking, the test passes
-        assert!(true);
+        // An obfuscated hop stops the walk at the last trusted proxy
+        headers.insert("forwarded", HeaderValue::from_static("for=203.0.113.9, for=_hidden"));
+        assert_eq!(resolve_client_ip(ip("10.0.0.1"), &headers, &trusted), ip("10.0.0.1"));
     }
 }
@@ -1,4 +1,4 @@
-use domain::entities::role::RoleName;
+use domain::entities::RoleName;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +14,76 @@ pub struct LoginResponseDto {
     pub user_id: String,
     pub username: String,
     pub role: RoleName,
+    /// The password has expired; `token` only permits changing it
+    #[serde(default)]
+    pub password_change_required: bool,
+    /// The email address is not verified yet; `token` only permits reading
+    #[serde(default)]
+    pub email_verification_required: bool,
+    /// The user's role requires a second factor; `token` only permits enrolling one
+    #[serde(default)]
+    pub mfa_enrollment_required: bool,
+}
+
+/// What a login with a correct password results in
+#[derive(Debug, Clone)]
+pub enum LoginOutcome {
+    Authenticated(LoginResponseDto),
+    /// The user has a second factor, which must be given with
+    /// `AuthService::complete_mfa_login` to finish the login
+    MfaRequired(MfaChallengeDto),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaChallengeDto {
+    /// Only permits completing this login
+    pub challenge_token: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaLoginDto {
+    pub challenge_token: String,
+    /// Current code of the authenticator, or an unused recovery code
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaCodeDto {
+    /// Current code of the authenticator, or an unused recovery code where accepted
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaDisableDto {
+    pub password: String,
+    /// Current code of the authenticator, or an unused recovery code
+    pub code: String,
+}
+
+/// What an authenticator app needs to be set up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaEnrollmentDto {
+    /// Base32 encoded, for entering by hand
+    pub secret: String,
+    pub otpauth_uri: String,
+    /// `otpauth_uri` as a QR code in SVG
+    pub qr_code_svg: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaRecoveryCodesDto {
+    /// Each can be used once instead of a code; they are not shown again
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaStatusDto {
+    pub enabled: bool,
+    pub enabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub recovery_codes_remaining: u64,
+    /// Whether the user's role requires a second factor
+    pub required: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,3 +101,58 @@ pub struct RegisterResponseDto {
     pub email: String,
     pub role: RoleName,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangePasswordRequestDto {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordResetRequestDto {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordResetConfirmDto {
+    /// As received by email
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailVerificationRequestDto {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailVerificationConfirmDto {
+    /// As received by email
+    pub token: String,
+}
+
+/// What a registration request results in
+#[derive(Debug, Clone)]
+pub enum RegisterOutcome {
+    Registered(RegisterResponseDto),
+    /// The request was accepted without telling whether an account was
+    /// created; the outcome is sent to the email address instead
+    Accepted,
+}
+
+/// One reason a password was rejected by the password policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasswordViolationDto {
+    /// Stable, machine-readable reason such as `too_short` or `breached`
+    pub code: String,
+    pub message: String,
+}
+
+/// An account that is temporarily locked after repeated failed logins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedAccountDto {
+    pub username: String,
+    pub failures: u32,
+    pub last_failed_at: chrono::DateTime<chrono::Utc>,
+    pub locked_until: chrono::DateTime<chrono::Utc>,
+}
@@ -1,4 +1,6 @@
-use domain::entities::role::RoleName;
+use crate::errors::ApplicationError;
+use domain::entities::RoleName;
+use domain::entities::UserStatus;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +11,19 @@ pub struct UserDto {
     pub role: RoleName,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub version: i64,
+    /// Only present on soft-deleted users
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub status: UserStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_changed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_changed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,10 +34,88 @@ pub struct CreateUserDto {
     pub role: RoleName,
 }
 
+/// A user migrated from another system, whose password is only known as a hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportUserDto {
+    pub username: String,
+    pub email: String,
+    /// In any format the password service can verify; replaced with a current
+    /// hash on the user's first login
+    pub password_hash: String,
+    pub role: RoleName,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateUserDto {
     pub username: Option<String>,
     pub email: Option<String>,
-    pub password: Option<String>,
     pub role: Option<RoleName>,
 }
+
+impl UpdateUserDto {
+    /// Checks the fields that are set, without looking at other users.
+    pub fn validate(&self) -> Result<(), ApplicationError> {
+        if let Some(username) = &self.username {
+            if username.trim().is_empty() {
+                return Err(ApplicationError::ValidationError("Username must not be empty".to_string()));
+            }
+        }
+        if let Some(email) = &self.email {
+            let valid = email.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty() && !domain.is_empty() && !domain.contains('@')
+            });
+            if !valid || email.contains(char::is_whitespace) {
+                return Err(ApplicationError::ValidationError("Email is not valid".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The fields of a `UserDto` a PATCH request may change; patches are applied
+/// to this document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatchableUserDto {
+    pub username: String,
+    pub email: String,
+    pub role: RoleName,
+}
+
+impl From<&UserDto> for PatchableUserDto {
+    fn from(user: &UserDto) -> Self {
+        Self {
+            username: user.username.clone(),
+            email: user.email.clone(),
+            role: user.role.clone(),
+        }
+    }
+}
+
+/// A PATCH request body
+#[derive(Debug, Clone)]
+pub enum UserPatch {
+    /// `application/merge-patch+json` (RFC 7386)
+    Merge(serde_json::Value),
+    /// `application/json-patch+json` (RFC 6902)
+    Json(json_patch::Patch),
+}
+
+impl UserPatch {
+    /// Applies the patch to `user`. The result only has to be a well-formed
+    /// user; its values are validated when it is stored, like any update.
+    pub fn apply(&self, user: &PatchableUserDto) -> Result<PatchableUserDto, ApplicationError> {
+        let mut document = serde_json::to_value(user)
+            .map_err(|e| ApplicationError::UnexpectedError(format!("Serialization error: {}", e)))?;
+
+        match self {
+            UserPatch::Merge(patch) => json_patch::merge(&mut document, patch),
+            UserPatch::Json(patch) => json_patch::patch(&mut document, &patch.0)
+                .map_err(|e| ApplicationError::ValidationError(format!("Invalid JSON Patch: {}", e)))?,
+        }
+
+        serde_json::from_value(document)
+            .map_err(|e| ApplicationError::ValidationError(format!("Invalid user after patch: {}", e)))
+    }
+}
@@ -1,3 +1,5 @@
+use crate::dtos::PasswordViolationDto;
+use domain::entities::UserStatus;
 use domain::errors::DomainError;
 use thiserror::Error;
 
@@ -15,9 +17,35 @@ pub enum ApplicationError {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    /// A new password does not meet the password policy, for every listed reason
+    #[error("Password rejected: {}", .0.iter().map(|v| v.message.as_str()).collect::<Vec<_>>().join("; "))]
+    PasswordRejected(Vec<PasswordViolationDto>),
+    
     #[error("Not found: {0}")]
     NotFound(String),
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+    
+    /// The credentials or token are valid but the account is not active
+    #[error("Account is {0}")]
+    AccountUnavailable(UserStatus),
+    
+    /// The credentials or token are valid but the user's email address is not verified yet
+    #[error("Email address is not verified")]
+    EmailNotVerified,
+    
+    /// Rejected until `retry_after` seconds have passed
+    #[error("Too many requests: {message}")]
+    TooManyRequests { message: String, retry_after: u64 },
+    
+    /// The server is overloaded; the request may succeed after `retry_after` seconds
+    #[error("Service unavailable: {message}")]
+    ServiceUnavailable { message: String, retry_after: u64 },
+    
     #[error("Unexpected error: {0}")]
     UnexpectedError(String),
 }
@@ -1,24 +1,78 @@
-use crate::dtos::{LoginRequestDto, LoginResponseDto, RegisterRequestDto, RegisterResponseDto};
+use crate::dtos::{
+    ChangePasswordRequestDto, LockedAccountDto, LoginOutcome, LoginRequestDto, LoginResponseDto, MfaChallengeDto, MfaLoginDto,
+    RegisterOutcome, RegisterRequestDto, RegisterResponseDto,
+};
 use crate::errors::ApplicationError;
+use crate::services::{
+    EmailMessage, EmailVerificationRule, EmailVerificationService, LoginThrottle, MfaService, Notifier, PasswordValidator,
+};
 use async_trait::async_trait;
-use domain::entities::role::{Role, RoleName};
+use domain::entities::{Role, RoleName};
 use domain::entities::User;
+use domain::errors::DomainError;
 use domain::repositories::UserRepository;
-use domain::value_objects::JwtClaims;
+use domain::value_objects::{JwtClaims, TokenScope};
+use std::net::IpAddr;
 use std::sync::Arc;
-use tracing::{info, instrument};
+use tokio::sync::OnceCell;
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
 
 #[async_trait]
 pub trait AuthService: Send + Sync {
-    async fn login(&self, request: LoginRequestDto) -> Result<LoginResponseDto, ApplicationError>;
-    async fn register(&self, request: RegisterRequestDto) -> Result<RegisterResponseDto, ApplicationError>;
+    /// `client_ip`, when known, is also subject to lockout after repeated failures.
+    /// Users with a second factor get a challenge to complete with
+    /// `complete_mfa_login` instead of a token.
+    async fn login(&self, request: LoginRequestDto, client_ip: Option<IpAddr>) -> Result<LoginOutcome, ApplicationError>;
+    /// Exchanges a challenge from `login` and a code of the user's second
+    /// factor for a token. Wrong codes count as failed logins.
+    async fn complete_mfa_login(
+        &self,
+        request: MfaLoginDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginResponseDto, ApplicationError>;
+    /// With uniform registration, every request is `Accepted` and the outcome
+    /// is emailed, so the response does not reveal registered users.
+    async fn register(&self, request: RegisterRequestDto) -> Result<RegisterOutcome, ApplicationError>;
+    /// Replaces the password of the user with `user_id` after checking the
+    /// current one, revokes all tokens issued to them and returns a new one.
+    async fn change_password(
+        &self,
+        user_id: &str,
+        request: ChangePasswordRequestDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginResponseDto, ApplicationError>;
+    /// Checks the token, that it has not been revoked and that its user still
+    /// exists and is active. Tokens of users who still have to verify their
+    /// email address are narrowed to `TokenScope::ReadOnly`.
     async fn validate_token(&self, token: &str) -> Result<JwtClaims, ApplicationError>;
+    async fn locked_accounts(&self) -> Result<Vec<LockedAccountDto>, ApplicationError>;
 }
 
-pub struct AuthServiceImpl<T: UserRepository> {
-    user_repository: Arc<T>,
+/// Lifetime of the token issued for an expired password, which only permits changing it
+const PASSWORD_CHANGE_TOKEN_SECONDS: i64 = 600;
+/// Lifetime of the challenge that stands between the password and the second factor
+const MFA_CHALLENGE_TOKEN_SECONDS: i64 = 300;
+/// Lifetime of the token issued to users who must enroll a second factor first
+const MFA_ENROLLMENT_TOKEN_SECONDS: i64 = 600;
+
+pub struct AuthServiceImpl {
+    user_repository: Arc<dyn UserRepository>,
     jwt_service: Arc<dyn JwtService>,
+    /// Lifetime of the token issued after a successful login, in seconds
+    token_seconds: i64,
     password_service: Arc<dyn PasswordService>,
+    login_throttle: Arc<LoginThrottle>,
+    password_validator: Arc<PasswordValidator>,
+    /// Set when registration must not reveal whether a username or email is taken
+    uniform_registration: Option<Arc<dyn Notifier>>,
+    /// Set when new addresses are sent a verification token
+    email_verification: Option<Arc<dyn EmailVerificationService>>,
+    email_verification_rule: EmailVerificationRule,
+    /// Set when users can have a second factor
+    mfa: Option<Arc<dyn MfaService>>,
+    /// Verified instead of a stored hash when the username is unknown
+    dummy_hash: OnceCell<String>,
 }
 
 #[async_trait]
@@ -29,81 +83,418 @@ pub trait JwtService: Send + Sync {
 
 #[async_trait]
 pub trait PasswordService: Send + Sync {
-    fn hash_password(&self, password: &str) -> Result<String, ApplicationError>;
-    fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError>;
+    async fn hash_password(&self, password: &str) -> Result<String, ApplicationError>;
+    async fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError>;
+    /// Whether `hash` was made with another algorithm or other parameters than
+    /// `hash_password` uses now
+    fn needs_rehash(&self, hash: &str) -> bool;
+    /// Whether `verify_password` understands `hash`, which may be in a legacy
+    /// format that `hash_password` no longer produces
+    fn is_supported_hash(&self, hash: &str) -> bool;
+}
+
+/// Unguessable tokens that are sent to users and stored only as a hash.
+pub trait OneTimeTokenService: Send + Sync {
+    /// A new random token, safe to put in a URL
+    fn generate(&self) -> String;
+    /// What is stored instead of `token`; equal tokens have equal hashes
+    fn hash(&self, token: &str) -> String;
 }
 
-impl<T: UserRepository> AuthServiceImpl<T> {
+impl AuthServiceImpl {
     pub fn new(
-        user_repository: Arc<T>,
+        user_repository: Arc<dyn UserRepository>,
         jwt_service: Arc<dyn JwtService>,
+        token_seconds: i64,
         password_service: Arc<dyn PasswordService>,
+        login_throttle: Arc<LoginThrottle>,
+        password_validator: Arc<PasswordValidator>,
     ) -> Self {
         Self {
             user_repository,
             jwt_service,
+            token_seconds,
             password_service,
+            login_throttle,
+            password_validator,
+            uniform_registration: None,
+            email_verification: None,
+            email_verification_rule: EmailVerificationRule::Optional,
+            mfa: None,
+            dummy_hash: OnceCell::new(),
+        }
+    }
+
+    /// Makes registration respond the same whether or not the username or
+    /// email is taken, and tells the address owner through `notifier`.
+    pub fn with_uniform_registration(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.uniform_registration = Some(notifier);
+        self
+    }
+
+    /// Sends new users a token to verify their email address with, and
+    /// restricts unverified users as `rule` says.
+    pub fn with_email_verification(
+        mut self,
+        service: Arc<dyn EmailVerificationService>,
+        rule: EmailVerificationRule,
+    ) -> Self {
+        self.email_verification = Some(service);
+        self.email_verification_rule = rule;
+        self
+    }
+
+    /// Asks users who enabled a second factor for it when logging in, and
+    /// restricts users whose role requires one until they have enrolled it.
+    pub fn with_mfa(mut self, service: Arc<dyn MfaService>) -> Self {
+        self.mfa = Some(service);
+        self
+    }
+
+    /// Whether `user` is still restricted by the email verification rule
+    fn email_verification_pending(&self, user: &User) -> bool {
+        self.email_verification_rule != EmailVerificationRule::Optional && !user.is_email_verified()
+    }
+
+    /// Sends `user` a token to verify their email address with, if enabled.
+    /// Failing to do so does not fail the registration.
+    async fn send_verification(&self, user: &User) {
+        if let Some(service) = &self.email_verification {
+            if let Err(e) = service.send_verification(user, &user.email).await {
+                warn!("Failed to send email verification to user {}: {}", user.username, e);
+            }
+        }
+    }
+
+    /// A hash made like the stored ones, so verifying against it takes as
+    /// long as verifying a real user's password.
+    async fn dummy_hash(&self) -> Result<&str, ApplicationError> {
+        self.dummy_hash
+            .get_or_try_init(|| self.password_service.hash_password("dummy password for unknown users"))
+            .await
+            .map(String::as_str)
+    }
+
+    /// Registration that answers `Accepted` in every case. The password is
+    /// hashed and both lookups run regardless of the outcome, so the response
+    /// time does not tell either.
+    async fn register_uniformly(
+        &self,
+        request: RegisterRequestDto,
+        notifier: &Arc<dyn Notifier>,
+    ) -> Result<RegisterOutcome, ApplicationError> {
+        let password_hash = self.password_service.hash_password(&request.password).await?;
+        let username_taken = self.user_repository.find_by_username(&request.username).await?;
+        let email_taken = self.user_repository.find_by_email(&request.email).await?;
+
+        let message = match (username_taken, email_taken) {
+            (_, Some(owner)) => email_taken_message(&request.email, &owner.username),
+            (Some(_), None) => username_taken_message(&request.email, &request.username),
+            (None, None) => {
+                let role = Role::new(request.role.unwrap_or(RoleName::User));
+                let user = User::new(request.username.clone(), request.email.clone(), password_hash, role);
+                match self.user_repository.create(&user).await {
+                    Ok(()) => {
+                        info!("Registration successful for user: {}", request.username);
+                        self.send_verification(&user).await;
+                        welcome_message(&request.email, &request.username)
+                    }
+                    // Taken by a soft-deleted user or a concurrent registration
+                    Err(DomainError::ValidationError(_)) | Err(DomainError::Conflict(_)) => {
+                        unavailable_message(&request.email, &request.username)
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+
+        // Sent in the background so delivery time does not show in the response
+        let notifier = Arc::clone(notifier);
+        tokio::spawn(async move {
+            if let Err(e) = notifier.send(message).await {
+                error!("Failed to send registration email: {}", e);
+            }
+        });
+
+        Ok(RegisterOutcome::Accepted)
+    }
+
+    /// Whether the role of `user` requires a second factor they have not enrolled yet
+    async fn mfa_enrollment_pending(&self, user: &User) -> Result<bool, ApplicationError> {
+        match &self.mfa {
+            Some(mfa) if mfa.is_required(user) => Ok(!mfa.is_enabled(user).await?),
+            _ => Ok(false),
+        }
+    }
+
+    /// A token for `user`, or only for changing the password if it has expired,
+    /// or only for enrolling a second factor that their role requires, or only
+    /// for reading until the email address is verified.
+    async fn issue_token(&self, user: User) -> Result<LoginResponseDto, ApplicationError> {
+        let password_change_required = self.password_validator.rotation().is_expired(&user);
+        let mfa_enrollment_required = self.mfa_enrollment_pending(&user).await?;
+        let (claims, expires_in) = if password_change_required {
+            info!("Password of user {} has expired", user.username);
+            let claims = JwtClaims::password_change(user.id, user.role.name.clone(), PASSWORD_CHANGE_TOKEN_SECONDS);
+            (claims, PASSWORD_CHANGE_TOKEN_SECONDS)
+        } else if mfa_enrollment_required {
+            info!("User {} has to enroll a second factor", user.username);
+            let claims = JwtClaims::mfa_enrollment(user.id, user.role.name.clone(), MFA_ENROLLMENT_TOKEN_SECONDS);
+            (claims, MFA_ENROLLMENT_TOKEN_SECONDS)
+        } else {
+            (JwtClaims::new(user.id, user.role.name.clone(), self.token_seconds), self.token_seconds)
+        };
+        let email_verification_required = self.email_verification_pending(&user);
+        let claims = JwtClaims {
+            token_version: user.token_version,
+            ..claims
+        };
+        let token = self.jwt_service.generate_token(claims)?;
+
+        let expires_at = chrono::Utc::now() + chrono::Duration::seconds(expires_in);
+
+        Ok(LoginResponseDto {
+            token,
+            expires_at,
+            user_id: user.id.to_string(),
+            username: user.username,
+            role: user.role.name,
+            password_change_required,
+            email_verification_required,
+            mfa_enrollment_required,
+        })
+    }
+
+    /// A challenge for `user` to complete with their second factor
+    fn issue_mfa_challenge(&self, user: &User) -> Result<MfaChallengeDto, ApplicationError> {
+        let claims = JwtClaims {
+            token_version: user.token_version,
+            ..JwtClaims::mfa_challenge(user.id, user.role.name.clone(), MFA_CHALLENGE_TOKEN_SECONDS)
+        };
+        Ok(MfaChallengeDto {
+            challenge_token: self.jwt_service.generate_token(claims)?,
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(MFA_CHALLENGE_TOKEN_SECONDS),
+        })
+    }
+
+    /// Replaces the stored hash of `user` with one made by the current
+    /// algorithm. Failing to do so does not fail the login.
+    async fn rehash_password(&self, user: User, password: &str) -> User {
+        let mut updated = user.clone();
+        let result = match self.password_service.hash_password(password).await {
+            Ok(hash) => {
+                updated.password_hash = hash;
+                updated.touch();
+                self.user_repository.update(&updated).await.map_err(ApplicationError::from)
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => {
+                info!("Upgraded password hash of user: {}", user.username);
+                updated
+            }
+            Err(e) => {
+                warn!("Failed to upgrade password hash of user {}: {}", user.username, e);
+                user
+            }
         }
     }
 }
 
 #[async_trait]
-impl<T: UserRepository> AuthService for AuthServiceImpl<T> {
+impl AuthService for AuthServiceImpl {
     #[instrument(skip(self, request), fields(username = %request.username))]
-    async fn login(&self, request: LoginRequestDto) -> Result<LoginResponseDto, ApplicationError> {
+    async fn login(&self, request: LoginRequestDto, client_ip: Option<IpAddr>) -> Result<LoginOutcome, ApplicationError> {
         info!("Attempting login for user: {}", request.username);
         
-        let user = self
-            .user_repository
-            .find_by_username(&request.username)
-            .await?
-            .ok_or_else(|| ApplicationError::AuthenticationError("Invalid username or password".to_string()))?;
+        self.login_throttle.check(&request.username, client_ip).await?;
+
+        let user = match self.user_repository.find_by_username(&request.username).await? {
+            Some(user) => user,
+            None => {
+                // Costs as much as a wrong password, so timing does not reveal unknown usernames
+                let dummy_hash = self.dummy_hash().await?;
+                self.password_service.verify_password(&request.password, dummy_hash).await?;
+
+                // Unknown usernames are counted too, so guessing them is throttled the same way
+                self.login_throttle.record_failure(&request.username, client_ip).await?;
+                return Err(ApplicationError::AuthenticationError(
+                    "Invalid username or password".to_string(),
+                ));
+            }
+        };
 
         let password_valid = self
             .password_service
-            .verify_password(&request.password, &user.password_hash)?;
+            .verify_password(&request.password, &user.password_hash)
+            .await?;
 
         if !password_valid {
+            self.login_throttle.record_failure(&request.username, client_ip).await?;
             return Err(ApplicationError::AuthenticationError(
                 "Invalid username or password".to_string(),
             ));
         }
 
-        let claims = JwtClaims::new(user.id, user.role.name.clone(), 3600); // 1 hour token
-        let token = self.jwt_service.generate_token(claims)?;
+        // With a second factor, failures are only cleared once it is given too,
+        // so knowing the password does not reset the lockout for guessing codes
+        let mfa_enabled = match &self.mfa {
+            Some(mfa) => mfa.is_enabled(&user).await?,
+            None => false,
+        };
+        if !mfa_enabled {
+            self.login_throttle.record_success(&request.username).await?;
+        }
+
+        // Checked after the password so the status is only revealed to the account owner
+        if !user.is_active() {
+            return Err(ApplicationError::AccountUnavailable(user.status));
+        }
+        if self.email_verification_rule == EmailVerificationRule::RequiredForLogin && !user.is_email_verified() {
+            return Err(ApplicationError::EmailNotVerified);
+        }
+
+        // The plaintext is only available now, so outdated hashes are upgraded here
+        let user = if self.password_service.needs_rehash(&user.password_hash) {
+            self.rehash_password(user, &request.password).await
+        } else {
+            user
+        };
 
-        let expires_at = chrono::Utc::now() + chrono::Duration::seconds(3600);
+        if mfa_enabled {
+            info!("Password accepted, second factor required for user: {}", request.username);
+            return Ok(LoginOutcome::MfaRequired(self.issue_mfa_challenge(&user)?));
+        }
 
         info!("Login successful for user: {}", request.username);
-        
-        Ok(LoginResponseDto {
-            token,
-            expires_at,
-            user_id: user.id.to_string(),
-            username: user.username,
-            role: user.role.name,
-        })
+
+        Ok(LoginOutcome::Authenticated(self.issue_token(user).await?))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn complete_mfa_login(
+        &self,
+        request: MfaLoginDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginResponseDto, ApplicationError> {
+        let invalid_challenge = || ApplicationError::AuthenticationError("Invalid or expired MFA challenge".to_string());
+
+        let mfa = self.mfa.as_ref().ok_or_else(invalid_challenge)?;
+        let claims = self.jwt_service.validate_token(&request.challenge_token)?;
+        if claims.scope != TokenScope::MfaChallenge {
+            return Err(invalid_challenge());
+        }
+        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| invalid_challenge())?;
+        let user = self
+            .user_repository
+            .find_by_id(&user_id)
+            .await?
+            .filter(|user| user.token_version == claims.token_version)
+            .ok_or_else(invalid_challenge)?;
+        if !user.is_active() {
+            return Err(ApplicationError::AccountUnavailable(user.status));
+        }
+
+        // Counted against the same lockout as passwords, so codes cannot be guessed either
+        self.login_throttle.check(&user.username, client_ip).await?;
+        if !mfa.verify(&user, &request.code).await? {
+            self.login_throttle.record_failure(&user.username, client_ip).await?;
+            return Err(ApplicationError::AuthenticationError(
+                "Invalid authentication code".to_string(),
+            ));
+        }
+        self.login_throttle.record_success(&user.username).await?;
+
+        info!("Login successful for user: {}", user.username);
+
+        self.issue_token(user).await
+    }
+
+    #[instrument(skip(self, request), fields(user_id = %user_id))]
+    async fn change_password(
+        &self,
+        user_id: &str,
+        request: ChangePasswordRequestDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginResponseDto, ApplicationError> {
+        info!("Attempting password change for user: {}", user_id);
+
+        let uuid = Uuid::parse_str(user_id)
+            .map_err(|_| ApplicationError::ValidationError("Invalid user ID format".to_string()))?;
+        let mut user = self
+            .user_repository
+            .find_by_id(&uuid)
+            .await?
+            .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", user_id)))?;
+
+        // Wrong current passwords count as failed logins, so a stolen token
+        // cannot be used to guess the password either
+        self.login_throttle.check(&user.username, client_ip).await?;
+        if !self
+            .password_service
+            .verify_password(&request.current_password, &user.password_hash)
+            .await?
+        {
+            self.login_throttle.record_failure(&user.username, client_ip).await?;
+            return Err(ApplicationError::AuthenticationError(
+                "Current password is incorrect".to_string(),
+            ));
+        }
+        self.login_throttle.record_success(&user.username).await?;
+
+        self.password_validator
+            .validate_change(
+                &request.new_password,
+                &user,
+                &user.username,
+                &user.email,
+                self.password_service.as_ref(),
+            )
+            .await?;
+        let password_hash = self.password_service.hash_password(&request.new_password).await?;
+
+        user.set_password(password_hash, self.password_validator.rotation().history_size());
+        // Ends every other session; the caller continues with the token returned here
+        user.revoke_tokens();
+        user.touch();
+        self.user_repository.update(&user).await?;
+
+        info!("Password changed for user: {}", user.username);
+
+        self.issue_token(user).await
     }
 
     #[instrument(skip(self, request), fields(username = %request.username, email = %request.email))]
-    async fn register(&self, request: RegisterRequestDto) -> Result<RegisterResponseDto, ApplicationError> {
+    async fn register(&self, request: RegisterRequestDto) -> Result<RegisterOutcome, ApplicationError> {
         info!("Attempting registration for user: {}", request.username);
+
+        // Rejecting a password reveals nothing about other accounts, so this
+        // also happens before uniform registration
+        self.password_validator
+            .validate(&request.password, &request.username, &request.email)
+            .await?;
+
+        if let Some(notifier) = &self.uniform_registration {
+            return self.register_uniformly(request, notifier).await;
+        }
         
         // Check if username already exists
-        if let Some(_) = self.user_repository.find_by_username(&request.username).await? {
+        if self.user_repository.find_by_username(&request.username).await?.is_some() {
             return Err(ApplicationError::ValidationError(
                 "Username already exists".to_string(),
             ));
         }
 
         // Check if email already exists
-        if let Some(_) = self.user_repository.find_by_email(&request.email).await? {
+        if self.user_repository.find_by_email(&request.email).await?.is_some() {
             return Err(ApplicationError::ValidationError(
                 "Email already exists".to_string(),
             ));
         }
 
-        let password_hash = self.password_service.hash_password(&request.password)?;
+        let password_hash = self.password_service.hash_password(&request.password).await?;
         
         // Default to User role if not specified
         let role = match request.role {
@@ -116,18 +507,102 @@ impl<T: UserRepository> AuthService for AuthServiceImpl<T> {
         self.user_repository.create(&user).await?;
 
         info!("Registration successful for user: {}", request.username);
+        self.send_verification(&user).await;
         
-        Ok(RegisterResponseDto {
+        Ok(RegisterOutcome::Registered(RegisterResponseDto {
             user_id: user.id.to_string(),
             username: user.username,
             email: user.email,
             role: role.name,
-        })
+        }))
     }
 
     #[instrument(skip(self, token))]
     async fn validate_token(&self, token: &str) -> Result<JwtClaims, ApplicationError> {
         info!("Validating JWT token");
-        self.jwt_service.validate_token(token)
+        let mut claims = self.jwt_service.validate_token(token)?;
+
+        let user_id = Uuid::parse_str(&claims.sub)
+            .map_err(|_| ApplicationError::AuthenticationError("Invalid token subject".to_string()))?;
+        let user = self
+            .user_repository
+            .find_by_id(&user_id)
+            .await?
+            .ok_or_else(|| ApplicationError::AuthenticationError("User no longer exists".to_string()))?;
+
+        // Only worth a code of the second factor, see `complete_mfa_login`
+        if claims.scope == TokenScope::MfaChallenge {
+            return Err(ApplicationError::AuthenticationError("Invalid token scope".to_string()));
+        }
+
+        if claims.token_version != user.token_version {
+            return Err(ApplicationError::AuthenticationError("Token has been revoked".to_string()));
+        }
+
+        if !user.is_active() {
+            return Err(ApplicationError::AccountUnavailable(user.status));
+        }
+
+        // Checked on every request, so verifying takes effect without logging in again
+        if self.email_verification_pending(&user) {
+            if self.email_verification_rule == EmailVerificationRule::RequiredForLogin {
+                return Err(ApplicationError::EmailNotVerified);
+            }
+            if claims.scope == TokenScope::Full {
+                claims.scope = TokenScope::ReadOnly;
+            }
+        }
+
+        Ok(claims)
+    }
+
+    #[instrument(skip(self))]
+    async fn locked_accounts(&self) -> Result<Vec<LockedAccountDto>, ApplicationError> {
+        info!("Listing locked accounts");
+        self.login_throttle.locked_accounts().await
+    }
+}
+
+fn welcome_message(email: &str, username: &str) -> EmailMessage {
+    EmailMessage {
+        to: email.to_string(),
+        subject: "Your account has been created".to_string(),
+        body: format!("Welcome! Your account {} has been created. You can log in now.", username),
+    }
+}
+
+fn email_taken_message(email: &str, existing_username: &str) -> EmailMessage {
+    EmailMessage {
+        to: email.to_string(),
+        subject: "Registration attempt with your email address".to_string(),
+        body: format!(
+            "Someone tried to register a new account with this email address, which already \
+             belongs to your account {}. If this was you, log in with that account instead. \
+             Otherwise you can ignore this message.",
+            existing_username
+        ),
+    }
+}
+
+fn username_taken_message(email: &str, username: &str) -> EmailMessage {
+    EmailMessage {
+        to: email.to_string(),
+        subject: "Your registration could not be completed".to_string(),
+        body: format!(
+            "The username {} is not available. Please register again with a different username.",
+            username
+        ),
+    }
+}
+
+fn unavailable_message(email: &str, username: &str) -> EmailMessage {
+    EmailMessage {
+        to: email.to_string(),
+        subject: "Your registration could not be completed".to_string(),
+        body: format!(
+            "The username {} or this email address is not available. Please register again \
+             with a different username, or log in if you already have an account.",
+            username
+        ),
     }
 }
@@ -0,0 +1,211 @@
+use crate::dtos::{EmailVerificationConfirmDto, EmailVerificationRequestDto};
+use crate::errors::ApplicationError;
+use crate::services::{EmailMessage, Notifier, OneTimeTokenService};
+use async_trait::async_trait;
+use chrono::Utc;
+use domain::entities::{EmailVerificationToken, User};
+use domain::repositories::{EmailVerificationTokenRepository, UserRepository};
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+/// How long an emailed token can be redeemed, unless configured otherwise
+const DEFAULT_TOKEN_TTL_HOURS: i64 = 24;
+
+/// What users can do before they have verified their email address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmailVerificationRule {
+    /// Everything; verification is only offered
+    #[default]
+    Optional,
+    /// Logging in and reading, but nothing that changes data
+    RequiredForWrites,
+    /// Nothing; logging in is refused
+    RequiredForLogin,
+}
+
+#[async_trait]
+pub trait EmailVerificationService: Send + Sync {
+    /// Emails a token to `email` that confirms it as the address of `user`.
+    /// Sent in the background; failures are only logged.
+    async fn send_verification(&self, user: &User, email: &str) -> Result<(), ApplicationError>;
+    /// Emails a new token to `request.email` if it belongs to an active user
+    /// who has not verified it yet. Succeeds in every case, so the caller
+    /// learns nothing about the address.
+    async fn request_verification(&self, request: EmailVerificationRequestDto) -> Result<(), ApplicationError>;
+    /// Marks the token's address as verified and makes it the email of the
+    /// token's user, if it is not already. Any other outstanding token of the
+    /// user cannot be used afterwards.
+    async fn confirm_verification(&self, request: EmailVerificationConfirmDto) -> Result<(), ApplicationError>;
+}
+
+#[derive(Clone)]
+pub struct EmailVerificationServiceImpl {
+    user_repository: Arc<dyn UserRepository>,
+    token_repository: Arc<dyn EmailVerificationTokenRepository>,
+    token_service: Arc<dyn OneTimeTokenService>,
+    notifier: Arc<dyn Notifier>,
+    token_ttl: chrono::Duration,
+    /// Link to the page that redeems tokens; the token is appended
+    verification_url: Option<String>,
+}
+
+impl EmailVerificationServiceImpl {
+    pub fn new(
+        user_repository: Arc<dyn UserRepository>,
+        token_repository: Arc<dyn EmailVerificationTokenRepository>,
+        token_service: Arc<dyn OneTimeTokenService>,
+        notifier: Arc<dyn Notifier>,
+    ) -> Self {
+        Self {
+            user_repository,
+            token_repository,
+            token_service,
+            notifier,
+            token_ttl: chrono::Duration::hours(DEFAULT_TOKEN_TTL_HOURS),
+            verification_url: None,
+        }
+    }
+
+    pub fn with_token_ttl(mut self, token_ttl: chrono::Duration) -> Self {
+        self.token_ttl = token_ttl;
+        self
+    }
+
+    /// Emails a link made of `verification_url` and the token instead of the bare token.
+    pub fn with_verification_url(mut self, verification_url: String) -> Self {
+        self.verification_url = Some(verification_url);
+        self
+    }
+
+    /// Stores a token for `email` and sends it there, in the background.
+    fn spawn_send(&self, user_id: Uuid, username: String, email: String) {
+        let token_repository = Arc::clone(&self.token_repository);
+        let token_service = Arc::clone(&self.token_service);
+        let notifier = Arc::clone(&self.notifier);
+        let token_ttl = self.token_ttl;
+        let verification_url = self.verification_url.clone();
+
+        tokio::spawn(async move {
+            let token = token_service.generate();
+            let record = EmailVerificationToken::new(token_service.hash(&token), user_id, email.clone(), token_ttl);
+            if let Err(e) = token_repository.create(&record).await {
+                error!("Failed to store email verification token: {}", e);
+                return;
+            }
+
+            let message = verification_message(&username, &email, &token, verification_url.as_deref(), token_ttl);
+            match notifier.send(message).await {
+                Ok(()) => info!(user_id = %user_id, "Email verification token sent"),
+                Err(e) => error!("Failed to send email verification email: {}", e),
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl EmailVerificationService for EmailVerificationServiceImpl {
+    #[instrument(skip(self, user, email), fields(user_id = %user.id))]
+    async fn send_verification(&self, user: &User, email: &str) -> Result<(), ApplicationError> {
+        info!("Sending email verification");
+        self.spawn_send(user.id, user.username.clone(), email.to_string());
+        Ok(())
+    }
+
+    #[instrument(skip(self, request))]
+    async fn request_verification(&self, request: EmailVerificationRequestDto) -> Result<(), ApplicationError> {
+        info!("Email verification requested");
+
+        // Looked up in the background, so neither the response time nor a
+        // failure tells whether the address belongs to a user
+        let service = self.clone();
+        tokio::spawn(async move {
+            match service.user_repository.find_by_email(&request.email).await {
+                Ok(Some(user)) if user.is_active() && !user.is_email_verified() => {
+                    service.spawn_send(user.id, user.username, user.email)
+                }
+                Ok(_) => info!("No active user with an unverified address to verify"),
+                Err(e) => error!("Failed to look up user for email verification: {}", e),
+            }
+        });
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, request))]
+    async fn confirm_verification(&self, request: EmailVerificationConfirmDto) -> Result<(), ApplicationError> {
+        let invalid_token =
+            || ApplicationError::ValidationError("Invalid or expired email verification token".to_string());
+
+        let token_hash = self.token_service.hash(&request.token);
+        let token = self
+            .token_repository
+            .find(&token_hash)
+            .await?
+            .filter(|token| token.is_usable(Utc::now()))
+            .ok_or_else(invalid_token)?;
+        let mut user = self
+            .user_repository
+            .find_by_id(&token.user_id)
+            .await?
+            .filter(User::is_active)
+            .ok_or_else(invalid_token)?;
+
+        let previous_email = user.email.clone();
+        if token.email != user.email {
+            // The address may have been taken since the change was requested
+            if self.user_repository.find_by_email(&token.email).await?.is_some() {
+                return Err(ApplicationError::ValidationError("Email already exists".to_string()));
+            }
+        }
+
+        // Redeemed only now, so a rejected change does not use up the token
+        if self.token_repository.consume(&token_hash, Utc::now()).await?.is_none() {
+            return Err(invalid_token());
+        }
+
+        user.verify_email(token.email);
+        user.touch();
+        self.user_repository.update(&user).await?;
+
+        let discarded = self.token_repository.delete_for_user(&user.id).await?;
+        if user.email == previous_email {
+            info!(target: "audit", user_id = %user.id, discarded_tokens = discarded, "Email address verified");
+        } else {
+            info!(
+                target: "audit",
+                user_id = %user.id,
+                previous_email = %previous_email,
+                email = %user.email,
+                discarded_tokens = discarded,
+                "Email address changed"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn verification_message(
+    username: &str,
+    email: &str,
+    token: &str,
+    verification_url: Option<&str>,
+    token_ttl: chrono::Duration,
+) -> EmailMessage {
+    let hours = token_ttl.num_hours().max(1);
+    let instructions = match verification_url {
+        Some(url) => format!("To confirm it, open {}{} within {} hours.", url, token, hours),
+        None => format!("To confirm it, use this verification token within {} hours:\n\n{}", hours, token),
+    };
+
+    EmailMessage {
+        to: email.to_string(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "This address was given as the email address of the account {}. {}\n\n\
+             If this was not you, you can ignore this message.",
+            username, instructions
+        ),
+    }
+}
@@ -0,0 +1,153 @@
+use crate::dtos::LockedAccountDto;
+use crate::errors::ApplicationError;
+use chrono::{DateTime, Duration, Utc};
+use domain::entities::{FailedLogins, LoginAttemptKey, LoginAttemptScope};
+use domain::repositories::LoginAttemptRepository;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::{instrument, warn};
+
+/// When repeated failed logins lock an account or address, and for how long.
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    /// Failures of one account before it is locked; 0 disables account lockout
+    pub account_threshold: u32,
+    /// Failures from one address before it is locked; 0 disables address lockout
+    pub ip_threshold: u32,
+    /// Lockout after reaching a threshold, doubled with every further failure
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Failures older than this are forgotten
+    pub reset_after: Duration,
+}
+
+impl LockoutPolicy {
+    fn threshold(&self, scope: LoginAttemptScope) -> u32 {
+        match scope {
+            LoginAttemptScope::Account => self.account_threshold,
+            LoginAttemptScope::Ip => self.ip_threshold,
+        }
+    }
+
+    /// End of the lockout earned by `failed`, if any.
+    fn locked_until(&self, failed: &FailedLogins) -> Option<DateTime<Utc>> {
+        let threshold = self.threshold(failed.key.scope);
+        if threshold == 0 || failed.failures < threshold {
+            return None;
+        }
+
+        // Saturates long before the shift could overflow
+        let doublings = (failed.failures - threshold).min(20);
+        let delay = (self.base_delay * 2i32.pow(doublings)).min(self.max_delay);
+
+        Some(failed.last_failed_at + delay)
+    }
+}
+
+/// Counts failed logins and rejects attempts while an account or the client
+/// address is locked out.
+pub struct LoginThrottle {
+    repository: Arc<dyn LoginAttemptRepository>,
+    policy: LockoutPolicy,
+}
+
+impl LoginThrottle {
+    pub fn new(repository: Arc<dyn LoginAttemptRepository>, policy: LockoutPolicy) -> Self {
+        Self { repository, policy }
+    }
+
+    /// Fails with `TooManyRequests` while `username` or `ip` is locked out.
+    #[instrument(skip(self))]
+    pub async fn check(&self, username: &str, ip: Option<IpAddr>) -> Result<(), ApplicationError> {
+        let now = Utc::now();
+
+        for key in self.keys(username, ip) {
+            let locked_until = match self.repository.find(&key).await? {
+                Some(failed) if failed.last_failed_at >= now - self.policy.reset_after => {
+                    self.policy.locked_until(&failed)
+                }
+                _ => None,
+            };
+
+            if let Some(locked_until) = locked_until.filter(|until| *until > now) {
+                let retry_after = (locked_until - now).num_seconds().max(1) as u64;
+                return Err(ApplicationError::TooManyRequests {
+                    message: "Too many failed login attempts".to_string(),
+                    retry_after,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn record_failure(&self, username: &str, ip: Option<IpAddr>) -> Result<(), ApplicationError> {
+        let now = Utc::now();
+
+        for key in self.keys(username, ip) {
+            let failed = self
+                .repository
+                .record_failure(&key, now, now - self.policy.reset_after)
+                .await?;
+
+            if failed.failures == self.policy.threshold(key.scope) {
+                warn!("Locking out {} {} after {} failed logins", key.scope, key.subject, failed.failures);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears the account's failures. The address keeps its count so that one
+    /// valid account cannot be used to keep guessing others.
+    #[instrument(skip(self))]
+    pub async fn record_success(&self, username: &str) -> Result<(), ApplicationError> {
+        if self.policy.account_threshold > 0 {
+            self.repository.reset(&LoginAttemptKey::account(username)).await?;
+        }
+        Ok(())
+    }
+
+    /// Accounts that are locked out right now.
+    #[instrument(skip(self))]
+    pub async fn locked_accounts(&self) -> Result<Vec<LockedAccountDto>, ApplicationError> {
+        if self.policy.account_threshold == 0 {
+            return Ok(Vec::new());
+        }
+
+        let now = Utc::now();
+        let candidates = self
+            .repository
+            .find_with_failures(
+                LoginAttemptScope::Account,
+                self.policy.account_threshold,
+                now - self.policy.reset_after,
+            )
+            .await?;
+
+        Ok(candidates
+            .into_iter()
+            .filter_map(|failed| {
+                let locked_until = self.policy.locked_until(&failed).filter(|until| *until > now)?;
+                Some(LockedAccountDto {
+                    username: failed.key.subject,
+                    failures: failed.failures,
+                    last_failed_at: failed.last_failed_at,
+                    locked_until,
+                })
+            })
+            .collect())
+    }
+
+    fn keys(&self, username: &str, ip: Option<IpAddr>) -> Vec<LoginAttemptKey> {
+        let mut keys = Vec::with_capacity(2);
+        if self.policy.account_threshold > 0 {
+            keys.push(LoginAttemptKey::account(username));
+        }
+        if let Some(ip) = ip.filter(|_| self.policy.ip_threshold > 0) {
+            keys.push(LoginAttemptKey::ip(ip));
+        }
+        keys
+    }
+}
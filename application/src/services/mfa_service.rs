@@ -0,0 +1,319 @@
+use crate::dtos::{MfaCodeDto, MfaDisableDto, MfaEnrollmentDto, MfaRecoveryCodesDto, MfaStatusDto};
+use crate::errors::ApplicationError;
+use crate::services::{LoginThrottle, OneTimeTokenService, PasswordService};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::RoleName;
+use domain::entities::{TotpEnrollment, User};
+use domain::repositories::{MfaRepository, UserRepository};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+/// Recovery codes issued when a second factor is enabled, unless configured otherwise
+const DEFAULT_RECOVERY_CODE_COUNT: usize = 10;
+
+/// Time-based one-time passwords (RFC 6238).
+pub trait TotpService: Send + Sync {
+    /// A new random shared secret, base32 encoded
+    fn generate_secret(&self) -> String;
+    /// The time step whose code `code` is, if it is the code of the step of
+    /// `now` or of one within the allowed clock skew
+    fn verify(&self, secret: &str, code: &str, now: DateTime<Utc>) -> Option<i64>;
+    /// `otpauth://` URI that sets up an authenticator app for `account`
+    fn provisioning_uri(&self, secret: &str, account: &str) -> String;
+    /// `data` as a QR code in SVG
+    fn qr_code_svg(&self, data: &str) -> Result<String, ApplicationError>;
+    /// A new random recovery code, readable enough to be typed in
+    fn generate_recovery_code(&self) -> String;
+}
+
+/// Encrypts TOTP secrets for storage. Codes are computed from the secret, so
+/// unlike passwords and recovery codes it cannot be stored as a hash.
+pub trait SecretCipher: Send + Sync {
+    fn encrypt(&self, plaintext: &str) -> Result<String, ApplicationError>;
+    fn decrypt(&self, ciphertext: &str) -> Result<String, ApplicationError>;
+}
+
+#[async_trait]
+pub trait MfaService: Send + Sync {
+    async fn status(&self, user_id: &str) -> Result<MfaStatusDto, ApplicationError>;
+    /// Creates a new secret for the user, replacing one that was not
+    /// confirmed yet. Fails if a second factor is already enabled.
+    async fn begin_enrollment(&self, user_id: &str) -> Result<MfaEnrollmentDto, ApplicationError>;
+    /// Enables the second factor once `request.code` shows that the
+    /// authenticator works, and returns new recovery codes.
+    async fn confirm_enrollment(&self, user_id: &str, request: MfaCodeDto) -> Result<MfaRecoveryCodesDto, ApplicationError>;
+    /// Removes the second factor, given the password and a current code or
+    /// a recovery code. Wrong ones count as failed logins.
+    async fn disable(
+        &self,
+        user_id: &str,
+        request: MfaDisableDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<(), ApplicationError>;
+    /// Removes the second factor of a user who lost it, on behalf of the
+    /// administrator `actor_id`, and revokes the user's tokens. Every reset
+    /// is logged under the `audit` tracing target.
+    async fn reset(&self, user_id: &str, actor_id: &str) -> Result<(), ApplicationError>;
+    /// Whether `user` has a confirmed second factor
+    async fn is_enabled(&self, user: &User) -> Result<bool, ApplicationError>;
+    /// Whether the role of `user` requires a second factor
+    fn is_required(&self, user: &User) -> bool;
+    /// Checks `code`, a current code of the authenticator or an unused
+    /// recovery code. Either is accepted only once.
+    async fn verify(&self, user: &User, code: &str) -> Result<bool, ApplicationError>;
+}
+
+pub struct MfaServiceImpl {
+    user_repository: Arc<dyn UserRepository>,
+    mfa_repository: Arc<dyn MfaRepository>,
+    totp_service: Arc<dyn TotpService>,
+    secret_cipher: Arc<dyn SecretCipher>,
+    /// Hashes recovery codes for storage
+    token_service: Arc<dyn OneTimeTokenService>,
+    password_service: Arc<dyn PasswordService>,
+    login_throttle: Arc<LoginThrottle>,
+    required_roles: HashSet<RoleName>,
+    recovery_code_count: usize,
+}
+
+impl MfaServiceImpl {
+    pub fn new(
+        user_repository: Arc<dyn UserRepository>,
+        mfa_repository: Arc<dyn MfaRepository>,
+        totp_service: Arc<dyn TotpService>,
+        secret_cipher: Arc<dyn SecretCipher>,
+        token_service: Arc<dyn OneTimeTokenService>,
+        password_service: Arc<dyn PasswordService>,
+        login_throttle: Arc<LoginThrottle>,
+    ) -> Self {
+        Self {
+            user_repository,
+            mfa_repository,
+            totp_service,
+            secret_cipher,
+            token_service,
+            password_service,
+            login_throttle,
+            required_roles: HashSet::new(),
+            recovery_code_count: DEFAULT_RECOVERY_CODE_COUNT,
+        }
+    }
+
+    /// Makes users of `roles` enroll a second factor before they can do anything else.
+    pub fn with_required_roles(mut self, roles: HashSet<RoleName>) -> Self {
+        self.required_roles = roles;
+        self
+    }
+
+    pub fn with_recovery_code_count(mut self, count: usize) -> Self {
+        self.recovery_code_count = count;
+        self
+    }
+
+    async fn find_user(&self, user_id: &str) -> Result<User, ApplicationError> {
+        let uuid = Uuid::parse_str(user_id)
+            .map_err(|_| ApplicationError::ValidationError("Invalid user ID format".to_string()))?;
+        self.user_repository
+            .find_by_id(&uuid)
+            .await?
+            .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", user_id)))
+    }
+
+    /// The enabled second factor of `user`
+    async fn find_confirmed(&self, user: &User) -> Result<Option<TotpEnrollment>, ApplicationError> {
+        Ok(self
+            .mfa_repository
+            .find(&user.id)
+            .await?
+            .filter(TotpEnrollment::is_confirmed))
+    }
+
+    /// Whether `code` is a current code of the authenticator of `enrollment`,
+    /// and if so the time step it belongs to
+    fn verify_totp(&self, enrollment: &TotpEnrollment, code: &str, now: DateTime<Utc>) -> Result<Option<i64>, ApplicationError> {
+        let secret = self.secret_cipher.decrypt(&enrollment.secret)?;
+        Ok(self.totp_service.verify(&secret, code, now))
+    }
+
+    /// What is stored instead of a recovery code; dashes, spaces and case do not matter
+    fn hash_recovery_code(&self, code: &str) -> String {
+        let normalized: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        self.token_service.hash(&normalized)
+    }
+}
+
+/// Authenticator codes are six digits; anything else is taken for a recovery code
+fn is_totp_code(code: &str) -> bool {
+    code.len() == 6 && code.chars().all(|c| c.is_ascii_digit())
+}
+
+#[async_trait]
+impl MfaService for MfaServiceImpl {
+    #[instrument(skip(self), fields(user_id = %user_id))]
+    async fn status(&self, user_id: &str) -> Result<MfaStatusDto, ApplicationError> {
+        let user = self.find_user(user_id).await?;
+        let enrollment = self.find_confirmed(&user).await?;
+        let recovery_codes_remaining = match enrollment {
+            Some(_) => self.mfa_repository.count_recovery_codes(&user.id).await?,
+            None => 0,
+        };
+
+        Ok(MfaStatusDto {
+            enabled: enrollment.is_some(),
+            enabled_at: enrollment.and_then(|enrollment| enrollment.confirmed_at),
+            recovery_codes_remaining,
+            required: self.is_required(&user),
+        })
+    }
+
+    #[instrument(skip(self), fields(user_id = %user_id))]
+    async fn begin_enrollment(&self, user_id: &str) -> Result<MfaEnrollmentDto, ApplicationError> {
+        info!("Beginning MFA enrollment for user: {}", user_id);
+
+        let user = self.find_user(user_id).await?;
+        if self.find_confirmed(&user).await?.is_some() {
+            return Err(ApplicationError::Conflict("A second factor is already enabled".to_string()));
+        }
+
+        let secret = self.totp_service.generate_secret();
+        let enrollment = TotpEnrollment::new(user.id, self.secret_cipher.encrypt(&secret)?);
+        self.mfa_repository.save(&enrollment).await?;
+
+        let otpauth_uri = self.totp_service.provisioning_uri(&secret, &user.username);
+        let qr_code_svg = self.totp_service.qr_code_svg(&otpauth_uri)?;
+        Ok(MfaEnrollmentDto {
+            secret,
+            otpauth_uri,
+            qr_code_svg,
+        })
+    }
+
+    #[instrument(skip(self, request), fields(user_id = %user_id))]
+    async fn confirm_enrollment(&self, user_id: &str, request: MfaCodeDto) -> Result<MfaRecoveryCodesDto, ApplicationError> {
+        let user = self.find_user(user_id).await?;
+        let mut enrollment = match self.mfa_repository.find(&user.id).await? {
+            Some(enrollment) if enrollment.is_confirmed() => {
+                return Err(ApplicationError::Conflict("A second factor is already enabled".to_string()));
+            }
+            Some(enrollment) => enrollment,
+            None => {
+                return Err(ApplicationError::Conflict("No second factor is being enrolled".to_string()));
+            }
+        };
+
+        let now = Utc::now();
+        let step = self
+            .verify_totp(&enrollment, request.code.trim(), now)?
+            .ok_or_else(|| ApplicationError::ValidationError("Invalid authentication code".to_string()))?;
+        enrollment.confirmed_at = Some(now);
+        enrollment.last_used_step = Some(step);
+
+        let recovery_codes: Vec<String> = (0..self.recovery_code_count)
+            .map(|_| self.totp_service.generate_recovery_code())
+            .collect();
+        let code_hashes: Vec<String> = recovery_codes.iter().map(|code| self.hash_recovery_code(code)).collect();
+        self.mfa_repository.replace_recovery_codes(&user.id, &code_hashes).await?;
+        self.mfa_repository.save(&enrollment).await?;
+
+        info!(target: "audit", user_id = %user.id, "Second factor enabled");
+
+        Ok(MfaRecoveryCodesDto { recovery_codes })
+    }
+
+    #[instrument(skip(self, request), fields(user_id = %user_id))]
+    async fn disable(
+        &self,
+        user_id: &str,
+        request: MfaDisableDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<(), ApplicationError> {
+        let user = self.find_user(user_id).await?;
+        if self.find_confirmed(&user).await?.is_none() {
+            return Err(ApplicationError::Conflict("No second factor is enabled".to_string()));
+        }
+
+        // Like a login, so that a stolen token is not enough to guess either
+        self.login_throttle.check(&user.username, client_ip).await?;
+        if !self
+            .password_service
+            .verify_password(&request.password, &user.password_hash)
+            .await?
+        {
+            self.login_throttle.record_failure(&user.username, client_ip).await?;
+            return Err(ApplicationError::AuthenticationError("Password is incorrect".to_string()));
+        }
+        if !self.verify(&user, &request.code).await? {
+            self.login_throttle.record_failure(&user.username, client_ip).await?;
+            return Err(ApplicationError::AuthenticationError("Invalid authentication code".to_string()));
+        }
+        self.login_throttle.record_success(&user.username).await?;
+
+        self.mfa_repository.delete(&user.id).await?;
+        info!(target: "audit", user_id = %user.id, "Second factor disabled");
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(user_id = %user_id, actor_id = %actor_id))]
+    async fn reset(&self, user_id: &str, actor_id: &str) -> Result<(), ApplicationError> {
+        let mut user = self.find_user(user_id).await?;
+        if self.mfa_repository.find(&user.id).await?.is_none() {
+            return Err(ApplicationError::Conflict(format!(
+                "User with ID {} has no second factor",
+                user_id
+            )));
+        }
+
+        // Whoever took over the lost factor may hold a session that passed it
+        user.revoke_tokens();
+        user.touch();
+        self.user_repository.update(&user).await?;
+        self.mfa_repository.delete(&user.id).await?;
+
+        info!(target: "audit", actor_id = %actor_id, user_id = %user.id, "Second factor reset by administrator");
+
+        Ok(())
+    }
+
+    async fn is_enabled(&self, user: &User) -> Result<bool, ApplicationError> {
+        Ok(self.find_confirmed(user).await?.is_some())
+    }
+
+    fn is_required(&self, user: &User) -> bool {
+        self.required_roles.contains(&user.role.name)
+    }
+
+    #[instrument(skip(self, user, code), fields(user_id = %user.id))]
+    async fn verify(&self, user: &User, code: &str) -> Result<bool, ApplicationError> {
+        let Some(enrollment) = self.find_confirmed(user).await? else {
+            return Ok(false);
+        };
+
+        let code = code.trim();
+        if is_totp_code(code) {
+            // Recording the step fails for a code that was used before
+            return match self.verify_totp(&enrollment, code, Utc::now())? {
+                Some(step) => Ok(self.mfa_repository.record_step(&user.id, step).await?),
+                None => Ok(false),
+            };
+        }
+
+        let used = self
+            .mfa_repository
+            .consume_recovery_code(&user.id, &self.hash_recovery_code(code))
+            .await?;
+        if used {
+            let remaining = self.mfa_repository.count_recovery_codes(&user.id).await?;
+            info!(target: "audit", user_id = %user.id, remaining, "Recovery code used");
+        }
+        Ok(used)
+    }
+}
@@ -1,5 +1,19 @@
 mod auth_service;
+mod email_verification_service;
+mod login_throttle;
+mod mfa_service;
+mod notifier;
+mod password_policy;
+mod password_reset_service;
+mod password_strength;
 mod user_service;
 
 pub use auth_service::*;
+pub use email_verification_service::*;
+pub use login_throttle::*;
+pub use mfa_service::*;
+pub use notifier::*;
+pub use password_policy::*;
+pub use password_reset_service::*;
+pub use password_strength::*;
 pub use user_service::*;
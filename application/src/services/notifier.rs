@@ -0,0 +1,18 @@
+use crate::errors::ApplicationError;
+use async_trait::async_trait;
+
+/// A plain text email
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers messages to users, e.g. to tell them about activity on their
+/// account without revealing it to whoever triggered it, or to send them
+/// tokens that prove they own their email address.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, message: EmailMessage) -> Result<(), ApplicationError>;
+}
@@ -0,0 +1,232 @@
+use crate::dtos::PasswordViolationDto;
+use crate::errors::ApplicationError;
+use crate::services::{estimate_strength, PasswordService};
+use async_trait::async_trait;
+use domain::entities::RoleName;
+use domain::entities::User;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+/// Requirements a new password must meet.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    /// In characters
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    /// Anything that is neither a letter nor a digit
+    pub require_symbol: bool,
+    /// Rejects passwords that contain the username or the email address, or
+    /// are contained in them
+    pub reject_account_names: bool,
+    /// Lowest acceptable `PasswordStrength::score` (0 to 4); 0 disables the check
+    pub min_strength: u8,
+}
+
+impl PasswordPolicy {
+    /// Every requirement `password` fails for the account with `username`
+    /// and `email`.
+    pub fn violations(&self, password: &str, username: &str, email: &str) -> Vec<PasswordViolationDto> {
+        let mut violations = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(violation(
+                "too_short",
+                format!("Password must be at least {} characters long", self.min_length),
+            ));
+        }
+        if length > self.max_length {
+            violations.push(violation(
+                "too_long",
+                format!("Password must be at most {} characters long", self.max_length),
+            ));
+        }
+
+        let classes = [
+            (self.require_lowercase, password.chars().any(char::is_lowercase), "missing_lowercase", "a lowercase letter"),
+            (self.require_uppercase, password.chars().any(char::is_uppercase), "missing_uppercase", "an uppercase letter"),
+            (self.require_digit, password.chars().any(|c| c.is_numeric()), "missing_digit", "a digit"),
+            (self.require_symbol, password.chars().any(|c| !c.is_alphanumeric()), "missing_symbol", "a symbol"),
+        ];
+        for (required, present, code, description) in classes {
+            if required && !present {
+                violations.push(violation(code, format!("Password must contain {}", description)));
+            }
+        }
+
+        let email_name = email.split('@').next().unwrap_or(email);
+        if self.reject_account_names {
+            let lower = password.to_lowercase();
+            if resembles(&lower, &username.to_lowercase()) {
+                violations.push(violation("similar_to_username", "Password must not resemble the username".to_string()));
+            }
+            if resembles(&lower, &email.to_lowercase()) || resembles(&lower, &email_name.to_lowercase()) {
+                violations.push(violation("similar_to_email", "Password must not resemble the email address".to_string()));
+            }
+        }
+
+        if self.min_strength > 0 {
+            let strength = estimate_strength(password, &[username, email, email_name]);
+            if strength.score < self.min_strength {
+                let mut message = format!(
+                    "Password is too easy to guess (strength {} of 4, at least {} required)",
+                    strength.score, self.min_strength
+                );
+                if !strength.patterns.is_empty() {
+                    let reasons: Vec<String> = strength.patterns.iter().map(|p| p.describe()).collect();
+                    message.push_str(&format!(": it {}", reasons.join(", ")));
+                }
+                violations.push(violation("too_weak", message));
+            }
+        }
+
+        violations
+    }
+}
+
+/// Whether one of `password` and `name` contains the other; names shorter
+/// than three characters are ignored.
+fn resembles(password: &str, name: &str) -> bool {
+    name.chars().count() >= 3 && (password.contains(name) || (password.chars().count() >= 3 && name.contains(password)))
+}
+
+fn violation(code: &str, message: String) -> PasswordViolationDto {
+    PasswordViolationDto {
+        code: code.to_string(),
+        message,
+    }
+}
+
+/// Limits on how long and how often a password may be used.
+#[derive(Debug, Clone, Default)]
+pub struct PasswordRotationPolicy {
+    /// How many of the most recent passwords, the current one included, a new
+    /// password must differ from; 0 disables the check
+    pub reuse_limit: usize,
+    /// How long a password may be used, per role; roles without an entry never expire
+    pub max_age: HashMap<RoleName, chrono::Duration>,
+}
+
+impl PasswordRotationPolicy {
+    /// How many earlier password hashes to keep in `User::password_history`
+    pub fn history_size(&self) -> usize {
+        self.reuse_limit.saturating_sub(1)
+    }
+
+    /// Whether `user` must change their password before doing anything else.
+    pub fn is_expired(&self, user: &User) -> bool {
+        self.max_age
+            .get(&user.role.name)
+            .is_some_and(|max_age| chrono::Utc::now() - user.password_set_at() >= *max_age)
+    }
+}
+
+/// A corpus of passwords known from data breaches.
+#[async_trait]
+pub trait BreachedPasswordChecker: Send + Sync {
+    /// How often `password` appears in the corpus; 0 if it does not.
+    async fn occurrences(&self, password: &str) -> Result<u64, ApplicationError>;
+}
+
+/// Checks new passwords against the `PasswordPolicy` and, optionally, a
+/// breached password corpus.
+pub struct PasswordValidator {
+    policy: PasswordPolicy,
+    breached: Option<(Arc<dyn BreachedPasswordChecker>, u64)>,
+    rotation: PasswordRotationPolicy,
+}
+
+impl PasswordValidator {
+    pub fn new(policy: PasswordPolicy) -> Self {
+        Self {
+            policy,
+            breached: None,
+            rotation: PasswordRotationPolicy::default(),
+        }
+    }
+
+    /// Also rejects passwords found at least `min_occurrences` times in `checker`.
+    pub fn with_breached_check(mut self, checker: Arc<dyn BreachedPasswordChecker>, min_occurrences: u64) -> Self {
+        self.breached = Some((checker, min_occurrences.max(1)));
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: PasswordRotationPolicy) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn rotation(&self) -> &PasswordRotationPolicy {
+        &self.rotation
+    }
+
+    /// Fails with `PasswordRejected`, listing every reason, unless `password`
+    /// is acceptable for the account with `username` and `email`.
+    #[instrument(skip(self, password))]
+    pub async fn validate(&self, password: &str, username: &str, email: &str) -> Result<(), ApplicationError> {
+        into_result(self.violations(password, username, email).await)
+    }
+
+    /// Like `validate`, for replacing the password of `user`, who will have
+    /// `username` and `email` afterwards. Also rejects the user's recent
+    /// passwords, which are checked with `password_service`.
+    #[instrument(skip(self, password, user, password_service), fields(user_id = %user.id))]
+    pub async fn validate_change(
+        &self,
+        password: &str,
+        user: &User,
+        username: &str,
+        email: &str,
+        password_service: &dyn PasswordService,
+    ) -> Result<(), ApplicationError> {
+        let mut violations = self.violations(password, username, email).await;
+
+        let recent = std::iter::once(&user.password_hash)
+            .chain(user.password_history.iter())
+            .take(self.rotation.reuse_limit);
+        for hash in recent {
+            if password_service.verify_password(password, hash).await? {
+                violations.push(violation(
+                    "reused",
+                    format!(
+                        "Password must differ from the last {} passwords",
+                        self.rotation.reuse_limit
+                    ),
+                ));
+                break;
+            }
+        }
+
+        into_result(violations)
+    }
+
+    async fn violations(&self, password: &str, username: &str, email: &str) -> Vec<PasswordViolationDto> {
+        let mut violations = self.policy.violations(password, username, email);
+
+        if let Some((checker, min_occurrences)) = &self.breached {
+            match checker.occurrences(password).await {
+                Ok(count) if count >= *min_occurrences => violations.push(violation(
+                    "breached",
+                    format!("Password has appeared in {} known data breaches and must not be used", count),
+                )),
+                Ok(_) => {}
+                // An unavailable corpus should not stop users from setting passwords
+                Err(e) => error!("Breached password check failed: {}", e),
+            }
+        }
+
+        violations
+    }
+}
+
+fn into_result(violations: Vec<PasswordViolationDto>) -> Result<(), ApplicationError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ApplicationError::PasswordRejected(violations))
+    }
+}
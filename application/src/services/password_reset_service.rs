@@ -0,0 +1,182 @@
+use crate::dtos::{PasswordResetConfirmDto, PasswordResetRequestDto};
+use crate::errors::ApplicationError;
+use crate::services::{EmailMessage, Notifier, OneTimeTokenService, PasswordService, PasswordValidator};
+use async_trait::async_trait;
+use chrono::Utc;
+use domain::entities::{PasswordResetToken, User};
+use domain::repositories::{PasswordResetTokenRepository, UserRepository};
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+
+/// How long an emailed token can be redeemed, unless configured otherwise
+const DEFAULT_TOKEN_TTL_MINUTES: i64 = 15;
+
+#[async_trait]
+pub trait PasswordResetService: Send + Sync {
+    /// Emails a reset token to `request.email` if it belongs to an active
+    /// user. Succeeds in every case, so the caller learns nothing about the
+    /// address.
+    async fn request_reset(&self, request: PasswordResetRequestDto) -> Result<(), ApplicationError>;
+    /// Sets the new password of the token's user and revokes all tokens
+    /// issued to them. The reset token, and any other outstanding one of the
+    /// user, cannot be used again.
+    async fn confirm_reset(&self, request: PasswordResetConfirmDto) -> Result<(), ApplicationError>;
+}
+
+pub struct PasswordResetServiceImpl {
+    user_repository: Arc<dyn UserRepository>,
+    token_repository: Arc<dyn PasswordResetTokenRepository>,
+    token_service: Arc<dyn OneTimeTokenService>,
+    password_service: Arc<dyn PasswordService>,
+    password_validator: Arc<PasswordValidator>,
+    notifier: Arc<dyn Notifier>,
+    token_ttl: chrono::Duration,
+    /// Link to the page that redeems tokens; the token is appended
+    reset_url: Option<String>,
+}
+
+impl PasswordResetServiceImpl {
+    pub fn new(
+        user_repository: Arc<dyn UserRepository>,
+        token_repository: Arc<dyn PasswordResetTokenRepository>,
+        token_service: Arc<dyn OneTimeTokenService>,
+        password_service: Arc<dyn PasswordService>,
+        password_validator: Arc<PasswordValidator>,
+        notifier: Arc<dyn Notifier>,
+    ) -> Self {
+        Self {
+            user_repository,
+            token_repository,
+            token_service,
+            password_service,
+            password_validator,
+            notifier,
+            token_ttl: chrono::Duration::minutes(DEFAULT_TOKEN_TTL_MINUTES),
+            reset_url: None,
+        }
+    }
+
+    pub fn with_token_ttl(mut self, token_ttl: chrono::Duration) -> Self {
+        self.token_ttl = token_ttl;
+        self
+    }
+
+    /// Emails a link made of `reset_url` and the token instead of the bare token.
+    pub fn with_reset_url(mut self, reset_url: String) -> Self {
+        self.reset_url = Some(reset_url);
+        self
+    }
+}
+
+#[async_trait]
+impl PasswordResetService for PasswordResetServiceImpl {
+    #[instrument(skip(self, request))]
+    async fn request_reset(&self, request: PasswordResetRequestDto) -> Result<(), ApplicationError> {
+        info!("Password reset requested");
+
+        let user_repository = Arc::clone(&self.user_repository);
+        let token_repository = Arc::clone(&self.token_repository);
+        let token_service = Arc::clone(&self.token_service);
+        let notifier = Arc::clone(&self.notifier);
+        let token_ttl = self.token_ttl;
+        let reset_url = self.reset_url.clone();
+
+        // Handled in the background, so neither the response time nor a
+        // failure tells whether the address belongs to a user
+        tokio::spawn(async move {
+            let user = match user_repository.find_by_email(&request.email).await {
+                Ok(Some(user)) if user.is_active() => user,
+                Ok(_) => {
+                    info!("No active user to reset the password of");
+                    return;
+                }
+                Err(e) => {
+                    error!("Failed to look up user for password reset: {}", e);
+                    return;
+                }
+            };
+
+            let token = token_service.generate();
+            let record = PasswordResetToken::new(token_service.hash(&token), user.id, token_ttl);
+            if let Err(e) = token_repository.create(&record).await {
+                error!("Failed to store password reset token: {}", e);
+                return;
+            }
+
+            let message = reset_message(&user, &token, reset_url.as_deref(), token_ttl);
+            match notifier.send(message).await {
+                Ok(()) => info!(user_id = %user.id, "Password reset token sent"),
+                Err(e) => error!("Failed to send password reset email: {}", e),
+            }
+        });
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, request))]
+    async fn confirm_reset(&self, request: PasswordResetConfirmDto) -> Result<(), ApplicationError> {
+        let invalid_token =
+            || ApplicationError::ValidationError("Invalid or expired password reset token".to_string());
+
+        let token_hash = self.token_service.hash(&request.token);
+        let token = self
+            .token_repository
+            .find(&token_hash)
+            .await?
+            .filter(|token| token.is_usable(Utc::now()))
+            .ok_or_else(invalid_token)?;
+        let mut user = self
+            .user_repository
+            .find_by_id(&token.user_id)
+            .await?
+            .filter(User::is_active)
+            .ok_or_else(invalid_token)?;
+
+        self.password_validator
+            .validate_change(
+                &request.new_password,
+                &user,
+                &user.username,
+                &user.email,
+                self.password_service.as_ref(),
+            )
+            .await?;
+        let password_hash = self.password_service.hash_password(&request.new_password).await?;
+
+        // Redeemed only now, so a rejected password does not use up the token
+        if self.token_repository.consume(&token_hash, Utc::now()).await?.is_none() {
+            return Err(invalid_token());
+        }
+
+        user.set_password(password_hash, self.password_validator.rotation().history_size());
+        user.revoke_tokens();
+        user.touch();
+        self.user_repository.update(&user).await?;
+
+        let discarded = self.token_repository.delete_for_user(&user.id).await?;
+        info!(target: "audit", user_id = %user.id, discarded_tokens = discarded, "Password reset by email");
+
+        Ok(())
+    }
+}
+
+fn reset_message(user: &User, token: &str, reset_url: Option<&str>, token_ttl: chrono::Duration) -> EmailMessage {
+    let minutes = token_ttl.num_minutes().max(1);
+    let instructions = match reset_url {
+        Some(url) => format!("To choose a new password, open {}{} within {} minutes.", url, token, minutes),
+        None => format!(
+            "To choose a new password, use this reset token within {} minutes:\n\n{}",
+            minutes, token
+        ),
+    };
+
+    EmailMessage {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Someone asked to reset the password of your account {}. {}\n\n\
+             If this was not you, you can ignore this message; your password stays unchanged.",
+            user.username, instructions
+        ),
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::Datelike;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The most common passwords, most common first. A password made of one of
+/// them needs about its rank in guesses.
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "password", "123456789", "12345678", "12345", "qwerty", "1234567", "111111",
+    "1234567890", "123123", "abc123", "1234", "password1", "iloveyou", "1q2w3e4r", "000000",
+    "qwerty123", "zaq12wsx", "dragon", "sunshine", "princess", "letmein", "654321", "monkey",
+    "1qaz2wsx", "123321", "qwertyuiop", "superman", "asdfghjkl", "trustno1", "football",
+    "baseball", "welcome", "admin", "login", "master", "hello", "freedom", "whatever", "qazwsx",
+    "shadow", "michael", "jennifer", "charlie", "jordan", "hunter", "ranger", "buster", "soccer",
+    "harley", "batman", "andrew", "tigger", "daniel", "starwars", "computer", "michelle",
+    "jessica", "pepper", "ginger", "summer", "ashley", "nicole", "chelsea", "biteme", "matthew",
+    "access", "yankees", "dallas", "austin", "thunder", "taylor", "matrix", "mustang", "killer",
+    "secret", "cheese", "changeme", "default", "guest", "root", "test", "pass", "love", "money",
+    "flower", "cookie", "orange", "banana", "apple", "silver", "golden", "lovely", "angel",
+    "admin123", "password123", "qwe123", "asd123", "zxcvbnm", "mypassword", "spring", "autumn",
+    "winter", "server", "secure", "welcome123", "hello123", "letmein123",
+];
+
+/// Adjacent keys; runs along them are keyboard patterns
+const KEYBOARD_ROWS: &[&str] = &["qwertyuiop", "asdfghjkl", "zxcvbnm", "1234567890"];
+
+/// Guesses per character not covered by any pattern
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+
+/// A weakness found in a password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordPattern {
+    CommonPassword(String),
+    /// Part of the username or email address
+    AccountName,
+    Sequence,
+    KeyboardPattern,
+    Repeat,
+    Year,
+}
+
+impl PasswordPattern {
+    pub fn describe(&self) -> String {
+        match self {
+            PasswordPattern::CommonPassword(word) => format!("contains the common password \"{}\"", word),
+            PasswordPattern::AccountName => "contains your username or email address".to_string(),
+            PasswordPattern::Sequence => "contains a sequence like \"abc\" or \"987\"".to_string(),
+            PasswordPattern::KeyboardPattern => "contains a keyboard pattern like \"qwerty\"".to_string(),
+            PasswordPattern::Repeat => "contains repeated characters".to_string(),
+            PasswordPattern::Year => "contains a year".to_string(),
+        }
+    }
+}
+
+/// How hard a password is to guess.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordStrength {
+    /// From 0 (guessed almost at once) to 4 (very hard to guess), on the same
+    /// scale as zxcvbn
+    pub score: u8,
+    /// Estimated number of guesses, as a power of ten
+    pub guesses_log10: f64,
+    /// The weaknesses the estimate is based on
+    pub patterns: Vec<PasswordPattern>,
+}
+
+#[derive(Debug, Clone)]
+struct Match {
+    start: usize,
+    /// Exclusive
+    end: usize,
+    guesses: f64,
+    pattern: PasswordPattern,
+}
+
+/// Estimates how many guesses an attacker needs for `password`, in the style
+/// of zxcvbn: the password is split into the known patterns that make it
+/// cheapest to guess, and the guesses for each part are multiplied.
+///
+/// `account_inputs` are the user's own details, such as the username, which
+/// an attacker would try first.
+pub fn estimate_strength(password: &str, account_inputs: &[&str]) -> PasswordStrength {
+    let chars: Vec<char> = password.chars().collect();
+    let lower: Vec<char> = password.to_lowercase().chars().collect();
+    // Lowercasing can change the length of some scripts; fall back to the original
+    let lower = if lower.len() == chars.len() { lower } else { chars.clone() };
+
+    let mut matches = dictionary_matches(&chars, &lower, account_inputs);
+    matches.extend(sequence_matches(&lower));
+    matches.extend(keyboard_matches(&lower));
+    matches.extend(repeat_matches(&lower));
+    matches.extend(year_matches(&chars));
+
+    // best[i] is the cheapest way to guess the first i characters, as the sum of
+    // log10 guesses, with the match that ends there (None for a bruteforced char)
+    let n = chars.len();
+    let mut best: Vec<(f64, Option<usize>)> = vec![(f64::INFINITY, None); n + 1];
+    best[0] = (0.0, None);
+    for end in 1..=n {
+        best[end] = (best[end - 1].0 + BRUTEFORCE_CARDINALITY.log10(), None);
+        for (index, m) in matches.iter().enumerate().filter(|(_, m)| m.end == end) {
+            let cost = best[m.start].0 + m.guesses.max(1.0).log10();
+            if cost < best[end].0 {
+                best[end] = (cost, Some(index));
+            }
+        }
+    }
+
+    // Walk back along the cheapest split to count its parts and collect the patterns
+    let mut patterns = Vec::new();
+    let mut parts = 0u32;
+    let mut position = n;
+    let mut in_bruteforce = false;
+    while position > 0 {
+        match best[position].1 {
+            Some(index) => {
+                let m = &matches[index];
+                if !patterns.contains(&m.pattern) {
+                    patterns.push(m.pattern.clone());
+                }
+                parts += 1;
+                in_bruteforce = false;
+                position = m.start;
+            }
+            None => {
+                if !in_bruteforce {
+                    parts += 1;
+                }
+                in_bruteforce = true;
+                position -= 1;
+            }
+        }
+    }
+    patterns.reverse();
+
+    // The attacker also has to try the parts in every order
+    let ordering = (1..=parts).map(|k| (k as f64).log10()).sum::<f64>();
+    let guesses_log10 = best[n].0 + ordering;
+
+    PasswordStrength {
+        score: score(guesses_log10),
+        guesses_log10,
+        patterns,
+    }
+}
+
+fn score(guesses_log10: f64) -> u8 {
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+fn common_password_ranks() -> &'static HashMap<&'static str, usize> {
+    static RANKS: OnceLock<HashMap<&'static str, usize>> = OnceLock::new();
+    RANKS.get_or_init(|| {
+        COMMON_PASSWORDS
+            .iter()
+            .enumerate()
+            .map(|(index, word)| (*word, index + 1))
+            .collect()
+    })
+}
+
+/// Undoes common character substitutions such as "p@ssw0rd"
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+fn dictionary_matches(chars: &[char], lower: &[char], account_inputs: &[&str]) -> Vec<Match> {
+    let unleeted: Vec<char> = lower.iter().map(|c| unleet(*c)).collect();
+    let account_inputs: Vec<String> = account_inputs
+        .iter()
+        .map(|input| input.to_lowercase())
+        .filter(|input| input.chars().count() >= 3)
+        .collect();
+
+    let mut matches = Vec::new();
+    for start in 0..lower.len() {
+        for end in start + 3..=lower.len() {
+            let plain: String = lower[start..end].iter().collect();
+            let substituted: String = unleeted[start..end].iter().collect();
+
+            let found = [(&plain, false), (&substituted, true)].into_iter().find_map(|(word, leet)| {
+                if account_inputs.iter().any(|input| input == word) {
+                    Some((1, PasswordPattern::AccountName, leet))
+                } else {
+                    common_password_ranks()
+                        .get(word.as_str())
+                        .map(|rank| (*rank, PasswordPattern::CommonPassword(word.clone()), leet))
+                }
+            });
+
+            if let Some((rank, pattern, leet)) = found {
+                let leet = leet && substituted != plain;
+                let guesses = rank as f64 * uppercase_variations(&chars[start..end]) * if leet { 2.0 } else { 1.0 };
+                matches.push(Match { start, end, guesses, pattern });
+            }
+        }
+    }
+    matches
+}
+
+/// Extra guesses for capitalization: a capital first letter or all capitals
+/// are tried early, anything else costs more.
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    if upper == 0 {
+        1.0
+    } else if upper == word.len() || (upper == 1 && word[0].is_uppercase()) {
+        2.0
+    } else {
+        2f64.powi(upper.min(10) as i32)
+    }
+}
+
+fn sequence_matches(lower: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + 2 < lower.len() {
+        let delta = lower[start + 1] as i64 - lower[start] as i64;
+        let mut end = start + 1;
+        if delta.abs() == 1 {
+            while end + 1 < lower.len() && lower[end + 1] as i64 - lower[end] as i64 == delta {
+                end += 1;
+            }
+        }
+
+        let len = end + 1 - start;
+        if len >= 3 {
+            let first = lower[start];
+            let base = if matches!(first, 'a' | 'z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match {
+                start,
+                end: end + 1,
+                guesses: base * len as f64 * direction,
+                pattern: PasswordPattern::Sequence,
+            });
+            start = end + 1;
+        } else {
+            start += 1;
+        }
+    }
+    matches
+}
+
+fn keyboard_matches(lower: &[char]) -> Vec<Match> {
+    let text: String = lower.iter().collect();
+    let mut matches = Vec::new();
+    for row in KEYBOARD_ROWS {
+        let reversed: String = row.chars().rev().collect();
+        for candidate in [row.to_string(), reversed] {
+            let candidate: Vec<char> = candidate.chars().collect();
+            for len in 4..=candidate.len() {
+                for window in candidate.windows(len) {
+                    let pattern: String = window.iter().collect();
+                    for (byte_start, _) in text.match_indices(&pattern) {
+                        let start = text[..byte_start].chars().count();
+                        matches.push(Match {
+                            start,
+                            end: start + len,
+                            guesses: 20.0 * len as f64,
+                            pattern: PasswordPattern::KeyboardPattern,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn repeat_matches(lower: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for start in 0..lower.len() {
+        for block in 1..=(lower.len() - start) / 2 {
+            let mut repeats = 1;
+            while start + (repeats + 1) * block <= lower.len()
+                && lower[start..start + block] == lower[start + repeats * block..start + (repeats + 1) * block]
+            {
+                repeats += 1;
+            }
+
+            // Single characters only count from three in a row
+            if repeats >= 2 && block * repeats >= 3 {
+                let block_guesses = BRUTEFORCE_CARDINALITY.powi(block.min(6) as i32);
+                matches.push(Match {
+                    start,
+                    end: start + block * repeats,
+                    guesses: block_guesses * repeats as f64,
+                    pattern: PasswordPattern::Repeat,
+                });
+            }
+        }
+    }
+    matches
+}
+
+fn year_matches(chars: &[char]) -> Vec<Match> {
+    chars
+        .windows(4)
+        .enumerate()
+        .filter_map(|(start, window)| {
+            let year: String = window.iter().collect();
+            let year = year.parse::<u32>().ok().filter(|year| (1900..=2099).contains(year))?;
+            // Recent years are guessed first
+            let distance = (year as f64 - chrono::Utc::now().year() as f64).abs().max(20.0);
+            Some(Match {
+                start,
+                end: start + 4,
+                guesses: distance,
+                pattern: PasswordPattern::Year,
+            })
+        })
+        .collect()
+}
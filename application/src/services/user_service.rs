@@ -1,35 +1,106 @@
-use crate::dtos::{CreateUserDto, UpdateUserDto, UserDto};
+use crate::dtos::{CreateUserDto, ImportUserDto, PatchableUserDto, UpdateUserDto, UserDto, UserPatch};
 use crate::errors::ApplicationError;
+use crate::services::{EmailVerificationService, PasswordValidator};
 use async_trait::async_trait;
-use domain::entities::role::Role;
-use domain::entities::User;
-use domain::repositories::UserRepository;
+use domain::entities::Role;
+use domain::entities::{User, UserStatus};
+use domain::errors::DomainError;
+use domain::repositories::{UnitOfWork, UserRepository};
+use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 use uuid::Uuid;
 
+/// Most users accepted by one `import_users` call, which runs in a single transaction
+const MAX_IMPORT_BATCH: usize = 1000;
+
 #[async_trait]
 pub trait UserService: Send + Sync {
     async fn get_user_by_id(&self, id: &str) -> Result<UserDto, ApplicationError>;
-    async fn get_all_users(&self) -> Result<Vec<UserDto>, ApplicationError>;
+    /// Soft-deleted users are only listed with `include_deleted`.
+    async fn get_all_users(&self, include_deleted: bool) -> Result<Vec<UserDto>, ApplicationError>;
     async fn create_user(&self, user: CreateUserDto) -> Result<UserDto, ApplicationError>;
-    async fn update_user(&self, id: &str, user: UpdateUserDto) -> Result<UserDto, ApplicationError>;
+    /// Creates all `users` with their existing password hashes, or none of them
+    /// if any is invalid or already taken.
+    async fn import_users(&self, users: Vec<ImportUserDto>) -> Result<Vec<UserDto>, ApplicationError>;
+    /// With `if_match`, the update only applies if the user's current version is listed.
+    /// With email verification, a new email only takes effect once it is confirmed.
+    async fn update_user(
+        &self,
+        id: &str,
+        user: UpdateUserDto,
+        if_match: Option<&[i64]>,
+    ) -> Result<UserDto, ApplicationError>;
+    /// Applies `patch` to the user's current state and stores the result with
+    /// `update_user`. `if_match` works as for `update_user`.
+    async fn patch_user(
+        &self,
+        id: &str,
+        patch: UserPatch,
+        if_match: Option<&[i64]>,
+    ) -> Result<UserDto, ApplicationError>;
+    /// Soft-deletes the user; it can be restored until the retention purge removes it.
     async fn delete_user(&self, id: &str) -> Result<(), ApplicationError>;
+    async fn restore_user(&self, id: &str) -> Result<UserDto, ApplicationError>;
+    /// Moves the user to `status`; fails with a conflict if the lifecycle
+    /// does not allow it (see `UserStatus::can_transition_to`).
+    async fn change_status(
+        &self,
+        id: &str,
+        status: UserStatus,
+        reason: Option<String>,
+    ) -> Result<UserDto, ApplicationError>;
+    /// Sets a new password chosen by the administrator `actor_id`, without
+    /// knowing the current one, and revokes all tokens issued to the user.
+    /// Every reset is logged under the `audit` tracing target.
+    async fn reset_password(
+        &self,
+        id: &str,
+        password: String,
+        reason: Option<String>,
+        actor_id: &str,
+    ) -> Result<UserDto, ApplicationError>;
 }
 
-pub struct UserServiceImpl<T: UserRepository> {
-    user_repository: Arc<T>,
+pub struct UserServiceImpl {
+    user_repository: Arc<dyn UserRepository>,
+    unit_of_work: Arc<dyn UnitOfWork>,
     password_service: Arc<dyn super::auth_service::PasswordService>,
+    password_validator: Arc<PasswordValidator>,
+    /// Set when new addresses must be confirmed before they are used
+    email_verification: Option<Arc<dyn EmailVerificationService>>,
 }
 
-impl<T: UserRepository> UserServiceImpl<T> {
+impl UserServiceImpl {
     pub fn new(
-        user_repository: Arc<T>,
+        user_repository: Arc<dyn UserRepository>,
+        unit_of_work: Arc<dyn UnitOfWork>,
         password_service: Arc<dyn super::auth_service::PasswordService>,
+        password_validator: Arc<PasswordValidator>,
     ) -> Self {
         Self {
             user_repository,
+            unit_of_work,
             password_service,
+            password_validator,
+            email_verification: None,
+        }
+    }
+
+    /// Sends new addresses a verification token, and keeps the current
+    /// address of a user until the new one is confirmed.
+    pub fn with_email_verification(mut self, service: Arc<dyn EmailVerificationService>) -> Self {
+        self.email_verification = Some(service);
+        self
+    }
+
+    /// Sends `user` a token to verify `email` with, if enabled. Failing to do
+    /// so does not fail the request.
+    async fn send_verification(&self, user: &User, email: &str) {
+        if let Some(service) = &self.email_verification {
+            if let Err(e) = service.send_verification(user, email).await {
+                warn!("Failed to send email verification to user {}: {}", user.username, e);
+            }
         }
     }
 
@@ -41,12 +112,19 @@ impl<T: UserRepository> UserServiceImpl<T> {
             role: user.role.name,
             created_at: user.created_at,
             updated_at: user.updated_at,
+            version: user.version,
+            deleted_at: user.deleted_at,
+            status: user.status,
+            status_reason: user.status_reason,
+            status_changed_at: user.status_changed_at,
+            password_changed_at: user.password_changed_at,
+            email_verified_at: user.email_verified_at,
         }
     }
 }
 
 #[async_trait]
-impl<T: UserRepository> UserService for UserServiceImpl<T> {
+impl UserService for UserServiceImpl {
     #[instrument(skip(self), fields(user_id = %id))]
     async fn get_user_by_id(&self, id: &str) -> Result<UserDto, ApplicationError> {
         info!("Getting user by ID: {}", id);
@@ -65,10 +143,14 @@ impl<T: UserRepository> UserService for UserServiceImpl<T> {
     }
 
     #[instrument(skip(self))]
-    async fn get_all_users(&self) -> Result<Vec<UserDto>, ApplicationError> {
+    async fn get_all_users(&self, include_deleted: bool) -> Result<Vec<UserDto>, ApplicationError> {
         info!("Getting all users");
         
-        let users = self.user_repository.find_all().await?;
+        let users = if include_deleted {
+            self.user_repository.find_all_including_deleted().await?
+        } else {
+            self.user_repository.find_all().await?
+        };
         let user_dtos = users.into_iter().map(|user| self.map_to_dto(user)).collect();
 
         Ok(user_dtos)
@@ -77,48 +159,136 @@ impl<T: UserRepository> UserService for UserServiceImpl<T> {
     #[instrument(skip(self, user), fields(username = %user.username, email = %user.email))]
     async fn create_user(&self, user: CreateUserDto) -> Result<UserDto, ApplicationError> {
         info!("Creating new user: {}", user.username);
-        
+
+        self.password_validator
+            .validate(&user.password, &user.username, &user.email)
+            .await?;
+
+        // Hash before starting the transaction so it is not held open meanwhile
+        let password_hash = self.password_service.hash_password(&user.password).await?;
+
+        let transaction = self.unit_of_work.begin().await?;
+
         // Check if username already exists
-        if let Some(_) = self.user_repository.find_by_username(&user.username).await? {
+        if transaction.users().find_by_username(&user.username).await?.is_some() {
             return Err(ApplicationError::ValidationError(
                 "Username already exists".to_string(),
             ));
         }
 
         // Check if email already exists
-        if let Some(_) = self.user_repository.find_by_email(&user.email).await? {
+        if transaction.users().find_by_email(&user.email).await?.is_some() {
             return Err(ApplicationError::ValidationError(
                 "Email already exists".to_string(),
             ));
         }
 
-        let password_hash = self.password_service.hash_password(&user.password)?;
         let role = Role::new(user.role);
         let new_user = User::new(user.username, user.email, password_hash, role);
 
-        self.user_repository.create(&new_user).await?;
+        transaction.users().create(&new_user).await?;
+        transaction.commit().await?;
+
+        self.send_verification(&new_user, &new_user.email).await;
 
         Ok(self.map_to_dto(new_user))
     }
 
-    #[instrument(skip(self, user), fields(user_id = %id))]
-    async fn update_user(&self, id: &str, user: UpdateUserDto) -> Result<UserDto, ApplicationError> {
+    #[instrument(skip(self, users), fields(count = users.len()))]
+    async fn import_users(&self, users: Vec<ImportUserDto>) -> Result<Vec<UserDto>, ApplicationError> {
+        info!("Importing {} users", users.len());
+
+        if users.len() > MAX_IMPORT_BATCH {
+            return Err(ApplicationError::ValidationError(format!(
+                "At most {} users can be imported at once",
+                MAX_IMPORT_BATCH
+            )));
+        }
+
+        let mut usernames = HashSet::new();
+        let mut emails = HashSet::new();
+        for user in &users {
+            if !usernames.insert(user.username.as_str()) {
+                return Err(ApplicationError::ValidationError(format!(
+                    "Username {} is listed more than once",
+                    user.username
+                )));
+            }
+            if !emails.insert(user.email.as_str()) {
+                return Err(ApplicationError::ValidationError(format!(
+                    "Email {} is listed more than once",
+                    user.email
+                )));
+            }
+            if !self.password_service.is_supported_hash(&user.password_hash) {
+                return Err(ApplicationError::ValidationError(format!(
+                    "Password hash of user {} is not in a supported format",
+                    user.username
+                )));
+            }
+        }
+
+        let transaction = self.unit_of_work.begin().await?;
+
+        let mut imported = Vec::with_capacity(users.len());
+        for user in users {
+            if transaction.users().find_by_username(&user.username).await?.is_some() {
+                return Err(ApplicationError::ValidationError(format!(
+                    "Username {} already exists",
+                    user.username
+                )));
+            }
+            if transaction.users().find_by_email(&user.email).await?.is_some() {
+                return Err(ApplicationError::ValidationError(format!(
+                    "Email {} already exists",
+                    user.email
+                )));
+            }
+
+            let new_user = User::new(user.username, user.email, user.password_hash, Role::new(user.role));
+            transaction.users().create(&new_user).await?;
+            imported.push(new_user);
+        }
+        transaction.commit().await?;
+
+        Ok(imported.into_iter().map(|user| self.map_to_dto(user)).collect())
+    }
+
+    #[instrument(skip(self, user, if_match), fields(user_id = %id))]
+    async fn update_user(
+        &self,
+        id: &str,
+        user: UpdateUserDto,
+        if_match: Option<&[i64]>,
+    ) -> Result<UserDto, ApplicationError> {
         info!("Updating user with ID: {}", id);
         
         let uuid = Uuid::parse_str(id).map_err(|_| {
             ApplicationError::ValidationError("Invalid user ID format".to_string())
         })?;
+        user.validate()?;
 
-        let mut existing_user = self
-            .user_repository
+        let transaction = self.unit_of_work.begin().await?;
+
+        let mut existing_user = transaction
+            .users()
             .find_by_id(&uuid)
             .await?
             .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", id)))?;
 
+        if let Some(versions) = if_match {
+            if !versions.contains(&existing_user.version) {
+                return Err(ApplicationError::PreconditionFailed(format!(
+                    "User with ID {} is at version {}",
+                    id, existing_user.version
+                )));
+            }
+        }
+
         // Update fields if provided
         if let Some(username) = user.username {
             // Check if the new username is already taken by another user
-            if let Some(found_user) = self.user_repository.find_by_username(&username).await? {
+            if let Some(found_user) = transaction.users().find_by_username(&username).await? {
                 if found_user.id != uuid {
                     return Err(ApplicationError::ValidationError(
                         "Username already exists".to_string(),
@@ -128,33 +298,81 @@ impl<T: UserRepository> UserService for UserServiceImpl<T> {
             existing_user.username = username;
         }
 
-        if let Some(email) = user.email {
+        // Set once the update is stored, if the new address must be confirmed first
+        let mut unconfirmed_email = None;
+        if let Some(email) = user.email.filter(|email| *email != existing_user.email) {
             // Check if the new email is already taken by another user
-            if let Some(found_user) = self.user_repository.find_by_email(&email).await? {
-                if found_user.id != uuid {
-                    return Err(ApplicationError::ValidationError(
-                        "Email already exists".to_string(),
-                    ));
-                }
+            if transaction.users().find_by_email(&email).await?.is_some() {
+                return Err(ApplicationError::ValidationError(
+                    "Email already exists".to_string(),
+                ));
+            }
+            if self.email_verification.is_some() {
+                unconfirmed_email = Some(email);
+            } else {
+                existing_user.email = email;
             }
-            existing_user.email = email;
-        }
-
-        if let Some(password) = user.password {
-            existing_user.password_hash = self.password_service.hash_password(&password)?;
         }
 
         if let Some(role_name) = user.role {
             existing_user.role = Role::new(role_name);
         }
 
-        existing_user.updated_at = chrono::Utc::now();
+        existing_user.touch();
 
-        self.user_repository.update(&existing_user).await?;
+        match transaction.users().update(&existing_user).await {
+            // Someone else updated the user after we read it
+            Err(DomainError::Conflict(msg)) if if_match.is_some() => {
+                return Err(ApplicationError::PreconditionFailed(msg));
+            }
+            result => result?,
+        }
+        transaction.commit().await?;
+
+        if let Some(email) = unconfirmed_email {
+            info!("Email change of user {} awaits confirmation", existing_user.id);
+            self.send_verification(&existing_user, &email).await;
+        }
 
         Ok(self.map_to_dto(existing_user))
     }
 
+    #[instrument(skip(self, patch, if_match), fields(user_id = %id))]
+    async fn patch_user(
+        &self,
+        id: &str,
+        patch: UserPatch,
+        if_match: Option<&[i64]>,
+    ) -> Result<UserDto, ApplicationError> {
+        info!("Patching user with ID: {}", id);
+
+        let current = self.get_user_by_id(id).await?;
+
+        if let Some(versions) = if_match {
+            if !versions.contains(&current.version) {
+                return Err(ApplicationError::PreconditionFailed(format!(
+                    "User with ID {} is at version {}",
+                    id, current.version
+                )));
+            }
+        }
+
+        let patched = patch.apply(&PatchableUserDto::from(&current))?;
+        let update = UpdateUserDto {
+            username: Some(patched.username),
+            email: Some(patched.email),
+            role: Some(patched.role),
+        };
+
+        // The patch was computed from `current`, so it must not be applied to anything newer
+        match self.update_user(id, update, Some(&[current.version])).await {
+            Err(ApplicationError::PreconditionFailed(msg)) if if_match.is_none() => {
+                Err(ApplicationError::Conflict(msg))
+            }
+            result => result,
+        }
+    }
+
     #[instrument(skip(self), fields(user_id = %id))]
     async fn delete_user(&self, id: &str) -> Result<(), ApplicationError> {
         info!("Deleting user with ID: {}", id);
@@ -163,15 +381,124 @@ impl<T: UserRepository> UserService for UserServiceImpl<T> {
             ApplicationError::ValidationError("Invalid user ID format".to_string())
         })?;
 
-        // Check if user exists
-        let _ = self
-            .user_repository
+        let transaction = self.unit_of_work.begin().await?;
+
+        let mut user = transaction
+            .users()
             .find_by_id(&uuid)
             .await?
             .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", id)))?;
 
-        self.user_repository.delete(&uuid).await?;
+        user.soft_delete();
+        transaction.users().update(&user).await?;
+        transaction.commit().await?;
 
         Ok(())
     }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn restore_user(&self, id: &str) -> Result<UserDto, ApplicationError> {
+        info!("Restoring user with ID: {}", id);
+
+        let uuid = Uuid::parse_str(id).map_err(|_| {
+            ApplicationError::ValidationError("Invalid user ID format".to_string())
+        })?;
+
+        let transaction = self.unit_of_work.begin().await?;
+
+        let mut user = transaction
+            .users()
+            .find_by_id_including_deleted(&uuid)
+            .await?
+            .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", id)))?;
+
+        if !user.is_deleted() {
+            return Err(ApplicationError::Conflict(format!("User with ID {} is not deleted", id)));
+        }
+
+        user.restore();
+        transaction.users().update(&user).await?;
+        transaction.commit().await?;
+
+        Ok(self.map_to_dto(user))
+    }
+
+    #[instrument(skip(self, reason), fields(user_id = %id, status = %status))]
+    async fn change_status(
+        &self,
+        id: &str,
+        status: UserStatus,
+        reason: Option<String>,
+    ) -> Result<UserDto, ApplicationError> {
+        info!("Changing status of user with ID {} to {}", id, status);
+
+        let uuid = Uuid::parse_str(id).map_err(|_| {
+            ApplicationError::ValidationError("Invalid user ID format".to_string())
+        })?;
+
+        let transaction = self.unit_of_work.begin().await?;
+
+        let mut user = transaction
+            .users()
+            .find_by_id(&uuid)
+            .await?
+            .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", id)))?;
+
+        user.change_status(status, reason)?;
+        transaction.users().update(&user).await?;
+        transaction.commit().await?;
+
+        Ok(self.map_to_dto(user))
+    }
+
+    #[instrument(skip(self, password, reason), fields(user_id = %id, actor_id = %actor_id))]
+    async fn reset_password(
+        &self,
+        id: &str,
+        password: String,
+        reason: Option<String>,
+        actor_id: &str,
+    ) -> Result<UserDto, ApplicationError> {
+        info!("Resetting password of user with ID: {}", id);
+
+        let uuid = Uuid::parse_str(id).map_err(|_| {
+            ApplicationError::ValidationError("Invalid user ID format".to_string())
+        })?;
+
+        let current = self
+            .user_repository
+            .find_by_id(&uuid)
+            .await?
+            .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", id)))?;
+        self.password_validator
+            .validate_change(&password, &current, &current.username, &current.email, self.password_service.as_ref())
+            .await?;
+
+        // Hash before starting the transaction so it is not held open meanwhile
+        let password_hash = self.password_service.hash_password(&password).await?;
+
+        let transaction = self.unit_of_work.begin().await?;
+
+        let mut user = transaction
+            .users()
+            .find_by_id(&uuid)
+            .await?
+            .ok_or_else(|| ApplicationError::NotFound(format!("User with ID {} not found", id)))?;
+
+        user.set_password(password_hash, self.password_validator.rotation().history_size());
+        user.revoke_tokens();
+        user.touch();
+        transaction.users().update(&user).await?;
+        transaction.commit().await?;
+
+        info!(
+            target: "audit",
+            actor_id = %actor_id,
+            user_id = %user.id,
+            reason = reason.as_deref().unwrap_or(""),
+            "Password reset by administrator"
+        );
+
+        Ok(self.map_to_dto(user))
+    }
 }
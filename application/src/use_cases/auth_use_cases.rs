@@ -1,34 +1,146 @@
-use crate::dtos::{LoginRequestDto, LoginResponseDto, RegisterRequestDto, RegisterResponseDto};
+use crate::dtos::{
+    ChangePasswordRequestDto, EmailVerificationConfirmDto, EmailVerificationRequestDto, LockedAccountDto, LoginOutcome,
+    LoginRequestDto, LoginResponseDto, MfaCodeDto, MfaDisableDto, MfaEnrollmentDto, MfaLoginDto, MfaRecoveryCodesDto, MfaStatusDto,
+    PasswordResetConfirmDto, PasswordResetRequestDto, RegisterOutcome, RegisterRequestDto,
+};
 use crate::errors::ApplicationError;
-use crate::services::AuthService;
+use crate::services::{AuthService, EmailVerificationService, MfaService, PasswordResetService};
+use domain::value_objects::JwtClaims;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tracing::{info, instrument};
 
 pub struct AuthUseCases {
     auth_service: Arc<dyn AuthService>,
+    password_reset_service: Arc<dyn PasswordResetService>,
+    email_verification_service: Arc<dyn EmailVerificationService>,
+    mfa_service: Arc<dyn MfaService>,
 }
 
 impl AuthUseCases {
-    pub fn new(auth_service: Arc<dyn AuthService>) -> Self {
-        Self { auth_service }
+    pub fn new(
+        auth_service: Arc<dyn AuthService>,
+        password_reset_service: Arc<dyn PasswordResetService>,
+        email_verification_service: Arc<dyn EmailVerificationService>,
+        mfa_service: Arc<dyn MfaService>,
+    ) -> Self {
+        Self {
+            auth_service,
+            password_reset_service,
+            email_verification_service,
+            mfa_service,
+        }
     }
 
     #[instrument(skip(self, request), fields(username = %request.username))]
-    pub async fn login(&self, request: LoginRequestDto) -> Result<LoginResponseDto, ApplicationError> {
+    pub async fn login(
+        &self,
+        request: LoginRequestDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginOutcome, ApplicationError> {
         info!("Login use case for user: {}", request.username);
-        self.auth_service.login(request).await
+        self.auth_service.login(request, client_ip).await
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn complete_mfa_login(
+        &self,
+        request: MfaLoginDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginResponseDto, ApplicationError> {
+        info!("Complete MFA login use case");
+        self.auth_service.complete_mfa_login(request, client_ip).await
     }
 
     #[instrument(skip(self, request), fields(username = %request.username, email = %request.email))]
-    pub async fn register(&self, request: RegisterRequestDto) -> Result<RegisterResponseDto, ApplicationError> {
+    pub async fn register(&self, request: RegisterRequestDto) -> Result<RegisterOutcome, ApplicationError> {
         info!("Register use case for user: {}", request.username);
         self.auth_service.register(request).await
     }
 
+    #[instrument(skip(self, request), fields(user_id = %user_id))]
+    pub async fn change_password(
+        &self,
+        user_id: &str,
+        request: ChangePasswordRequestDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginResponseDto, ApplicationError> {
+        info!("Change password use case for user: {}", user_id);
+        self.auth_service.change_password(user_id, request, client_ip).await
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn request_password_reset(&self, request: PasswordResetRequestDto) -> Result<(), ApplicationError> {
+        info!("Request password reset use case");
+        self.password_reset_service.request_reset(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn confirm_password_reset(&self, request: PasswordResetConfirmDto) -> Result<(), ApplicationError> {
+        info!("Confirm password reset use case");
+        self.password_reset_service.confirm_reset(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn request_email_verification(&self, request: EmailVerificationRequestDto) -> Result<(), ApplicationError> {
+        info!("Request email verification use case");
+        self.email_verification_service.request_verification(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn confirm_email_verification(&self, request: EmailVerificationConfirmDto) -> Result<(), ApplicationError> {
+        info!("Confirm email verification use case");
+        self.email_verification_service.confirm_verification(request).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %user_id))]
+    pub async fn mfa_status(&self, user_id: &str) -> Result<MfaStatusDto, ApplicationError> {
+        info!("MFA status use case for user: {}", user_id);
+        self.mfa_service.status(user_id).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %user_id))]
+    pub async fn begin_mfa_enrollment(&self, user_id: &str) -> Result<MfaEnrollmentDto, ApplicationError> {
+        info!("Begin MFA enrollment use case for user: {}", user_id);
+        self.mfa_service.begin_enrollment(user_id).await
+    }
+
+    #[instrument(skip(self, request), fields(user_id = %user_id))]
+    pub async fn confirm_mfa_enrollment(
+        &self,
+        user_id: &str,
+        request: MfaCodeDto,
+    ) -> Result<MfaRecoveryCodesDto, ApplicationError> {
+        info!("Confirm MFA enrollment use case for user: {}", user_id);
+        self.mfa_service.confirm_enrollment(user_id, request).await
+    }
+
+    #[instrument(skip(self, request), fields(user_id = %user_id))]
+    pub async fn disable_mfa(
+        &self,
+        user_id: &str,
+        request: MfaDisableDto,
+        client_ip: Option<IpAddr>,
+    ) -> Result<(), ApplicationError> {
+        info!("Disable MFA use case for user: {}", user_id);
+        self.mfa_service.disable(user_id, request, client_ip).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %user_id, actor_id = %actor_id))]
+    pub async fn reset_mfa(&self, user_id: &str, actor_id: &str) -> Result<(), ApplicationError> {
+        info!("Reset MFA use case for user: {}", user_id);
+        self.mfa_service.reset(user_id, actor_id).await
+    }
+
     #[instrument(skip(self, token))]
-    pub async fn validate_token(&self, token: &str) -> Result<(), ApplicationError> {
+    pub async fn validate_token(&self, token: &str) -> Result<JwtClaims, ApplicationError> {
         info!("Validate token use case");
-        let _ = self.auth_service.validate_token(token).await?;
-        Ok(())
+        self.auth_service.validate_token(token).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn locked_accounts(&self) -> Result<Vec<LockedAccountDto>, ApplicationError> {
+        info!("Locked accounts use case");
+        self.auth_service.locked_accounts().await
     }
 }
@@ -1,6 +1,7 @@
-use crate::dtos::{CreateUserDto, UpdateUserDto, UserDto};
+use crate::dtos::{CreateUserDto, ImportUserDto, UpdateUserDto, UserDto, UserPatch};
 use crate::errors::ApplicationError;
 use crate::services::UserService;
+use domain::entities::UserStatus;
 use std::sync::Arc;
 use tracing::{info, instrument};
 
@@ -20,9 +21,9 @@ impl UserUseCases {
     }
 
     #[instrument(skip(self))]
-    pub async fn get_all_users(&self) -> Result<Vec<UserDto>, ApplicationError> {
+    pub async fn get_all_users(&self, include_deleted: bool) -> Result<Vec<UserDto>, ApplicationError> {
         info!("Get all users use case");
-        self.user_service.get_all_users().await
+        self.user_service.get_all_users(include_deleted).await
     }
 
     #[instrument(skip(self, user), fields(username = %user.username, email = %user.email))]
@@ -31,10 +32,32 @@ impl UserUseCases {
         self.user_service.create_user(user).await
     }
 
-    #[instrument(skip(self, user), fields(user_id = %id))]
-    pub async fn update_user(&self, id: &str, user: UpdateUserDto) -> Result<UserDto, ApplicationError> {
+    #[instrument(skip(self, users), fields(count = users.len()))]
+    pub async fn import_users(&self, users: Vec<ImportUserDto>) -> Result<Vec<UserDto>, ApplicationError> {
+        info!("Import users use case for {} users", users.len());
+        self.user_service.import_users(users).await
+    }
+
+    #[instrument(skip(self, user, if_match), fields(user_id = %id))]
+    pub async fn update_user(
+        &self,
+        id: &str,
+        user: UpdateUserDto,
+        if_match: Option<&[i64]>,
+    ) -> Result<UserDto, ApplicationError> {
         info!("Update user use case for ID: {}", id);
-        self.user_service.update_user(id, user).await
+        self.user_service.update_user(id, user, if_match).await
+    }
+
+    #[instrument(skip(self, patch, if_match), fields(user_id = %id))]
+    pub async fn patch_user(
+        &self,
+        id: &str,
+        patch: UserPatch,
+        if_match: Option<&[i64]>,
+    ) -> Result<UserDto, ApplicationError> {
+        info!("Patch user use case for ID: {}", id);
+        self.user_service.patch_user(id, patch, if_match).await
     }
 
     #[instrument(skip(self), fields(user_id = %id))]
@@ -42,4 +65,34 @@ impl UserUseCases {
         info!("Delete user use case for ID: {}", id);
         self.user_service.delete_user(id).await
     }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    pub async fn restore_user(&self, id: &str) -> Result<UserDto, ApplicationError> {
+        info!("Restore user use case for ID: {}", id);
+        self.user_service.restore_user(id).await
+    }
+
+    #[instrument(skip(self, reason), fields(user_id = %id))]
+    pub async fn suspend_user(&self, id: &str, reason: Option<String>) -> Result<UserDto, ApplicationError> {
+        info!("Suspend user use case for ID: {}", id);
+        self.user_service.change_status(id, UserStatus::Suspended, reason).await
+    }
+
+    #[instrument(skip(self, reason), fields(user_id = %id))]
+    pub async fn reactivate_user(&self, id: &str, reason: Option<String>) -> Result<UserDto, ApplicationError> {
+        info!("Reactivate user use case for ID: {}", id);
+        self.user_service.change_status(id, UserStatus::Active, reason).await
+    }
+
+    #[instrument(skip(self, password, reason), fields(user_id = %id, actor_id = %actor_id))]
+    pub async fn reset_password(
+        &self,
+        id: &str,
+        password: String,
+        reason: Option<String>,
+        actor_id: &str,
+    ) -> Result<UserDto, ApplicationError> {
+        info!("Reset password use case for ID: {}", id);
+        self.user_service.reset_password(id, password, reason, actor_id).await
+    }
 }
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A single-use token that confirms an email address belongs to a user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmailVerificationToken {
+    /// Hash of the token sent to the user; the token itself is never stored
+    pub token_hash: String,
+    pub user_id: Uuid,
+    /// The address the token was sent to. If it differs from the user's
+    /// current one, redeeming the token changes the user's address to it.
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the token was redeemed; it cannot be redeemed again
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+impl EmailVerificationToken {
+    pub fn new(token_hash: String, user_id: Uuid, email: String, ttl: chrono::Duration) -> Self {
+        let now = Utc::now();
+        Self {
+            token_hash,
+            user_id,
+            email,
+            created_at: now,
+            expires_at: now + ttl,
+            used_at: None,
+        }
+    }
+
+    /// Whether the token can still be redeemed at `now`
+    pub fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.used_at.is_none() && self.expires_at > now
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What failed login attempts are counted against
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum LoginAttemptScope {
+    /// The username that was tried, whether or not it exists
+    Account,
+    /// The address the attempt came from
+    Ip,
+}
+
+impl LoginAttemptScope {
+    /// Name used in storage
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoginAttemptScope::Account => "account",
+            LoginAttemptScope::Ip => "ip",
+        }
+    }
+}
+
+impl fmt::Display for LoginAttemptScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct LoginAttemptKey {
+    pub scope: LoginAttemptScope,
+    /// Username or IP address
+    pub subject: String,
+}
+
+impl LoginAttemptKey {
+    pub fn account(username: &str) -> Self {
+        Self {
+            scope: LoginAttemptScope::Account,
+            subject: username.to_string(),
+        }
+    }
+
+    pub fn ip(ip: std::net::IpAddr) -> Self {
+        Self {
+            scope: LoginAttemptScope::Ip,
+            subject: ip.to_string(),
+        }
+    }
+}
+
+/// Consecutive failed logins for one key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailedLogins {
+    pub key: LoginAttemptKey,
+    pub failures: u32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+}
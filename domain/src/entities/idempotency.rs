@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A request made with an idempotency key, and its response once it finished
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IdempotencyRecord {
+    /// The client's key, scoped to the caller and endpoint
+    pub key: String,
+    /// Hash of the request, to detect a key reused for a different request
+    pub fingerprint: String,
+    /// `None` while the request is still being processed
+    pub response: Option<StoredResponse>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl IdempotencyRecord {
+    pub fn new(key: String, fingerprint: String, ttl: chrono::Duration) -> Self {
+        let now = Utc::now();
+        Self {
+            key,
+            fingerprint,
+            response: None,
+            created_at: now,
+            expires_at: now + ttl,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
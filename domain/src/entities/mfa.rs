@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A user's TOTP (RFC 6238) authenticator. It only counts as a second factor
+/// once a code generated from the secret has confirmed it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TotpEnrollment {
+    pub user_id: Uuid,
+    /// Shared secret, encrypted for storage; base32 encoded as in
+    /// `otpauth://` URIs once decrypted
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    /// When a code first confirmed the authenticator; `None` while enrolling
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// Time step of the last accepted code; codes of this or an earlier step
+    /// are not accepted again
+    pub last_used_step: Option<i64>,
+}
+
+impl TotpEnrollment {
+    pub fn new(user_id: Uuid, secret: String) -> Self {
+        Self {
+            user_id,
+            secret,
+            created_at: Utc::now(),
+            confirmed_at: None,
+            last_used_step: None,
+        }
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
@@ -1,5 +1,19 @@
+mod email_verification;
+mod failed_logins;
+mod idempotency;
+mod mfa;
+mod password_reset;
+mod rate_limit;
 mod user;
+mod user_status;
 mod role;
 
+pub use email_verification::*;
+pub use failed_logins::*;
+pub use idempotency::*;
+pub use mfa::*;
+pub use password_reset::*;
+pub use rate_limit::*;
 pub use user::*;
+pub use user_status::*;
 pub use role::*;
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A single-use token that lets the owner of an email address set a new
+/// password without knowing the current one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PasswordResetToken {
+    /// Hash of the token sent to the user; the token itself is never stored
+    pub token_hash: String,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the token was redeemed; it cannot be redeemed again
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+impl PasswordResetToken {
+    pub fn new(token_hash: String, user_id: Uuid, ttl: chrono::Duration) -> Self {
+        let now = Utc::now();
+        Self {
+            token_hash,
+            user_id,
+            created_at: now,
+            expires_at: now + ttl,
+            used_at: None,
+        }
+    }
+
+    /// Whether the token can still be redeemed at `now`
+    pub fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.used_at.is_none() && self.expires_at > now
+    }
+}
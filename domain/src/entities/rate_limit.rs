@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// Allows `limit` requests per `period`, of which up to `burst` may arrive
+/// back to back.
+///
+/// Enforced with the generic cell rate algorithm (GCRA): a key's only state
+/// is its theoretical arrival time (TAT), the time at which its bucket would
+/// be full again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitQuota {
+    pub limit: u32,
+    pub period: Duration,
+    pub burst: u32,
+}
+
+/// Outcome of one request against a quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    /// Requests that could still be made right now
+    pub remaining: u32,
+    /// Until the full burst is available again
+    pub reset_after: Duration,
+    /// Until the next request is allowed, for refused requests
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitQuota {
+    /// Time one request "costs"
+    pub fn emission_interval(&self) -> Duration {
+        // Configuration rejects limits beyond `i32`; saturate rather than wrap
+        self.period / i32::try_from(self.limit.max(1)).unwrap_or(i32::MAX)
+    }
+
+    /// How far the TAT may run ahead of the clock
+    pub fn tolerance(&self) -> Duration {
+        self.emission_interval() * i32::try_from(self.burst.max(1)).unwrap_or(i32::MAX)
+    }
+
+    /// Applies a request at `now` to a key whose TAT is `tat` (`None` for a
+    /// key without history). Returns the decision and, if the request is
+    /// allowed, the TAT to store.
+    pub fn check(
+        &self,
+        tat: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> (RateLimitDecision, Option<DateTime<Utc>>) {
+        let tat = tat.map_or(now, |tat| tat.max(now));
+        let new_tat = tat + self.emission_interval();
+
+        if new_tat - now <= self.tolerance() {
+            (self.decision(new_tat, now, true), Some(new_tat))
+        } else {
+            (self.decision(tat, now, false), None)
+        }
+    }
+
+    /// Decision for a key whose TAT is `tat` after the request was handled.
+    /// Stores that apply the algorithm themselves use this to describe the result.
+    pub fn decision(&self, tat: DateTime<Utc>, now: DateTime<Utc>, allowed: bool) -> RateLimitDecision {
+        let ahead = (tat - now).max(Duration::zero());
+        let interval = self.emission_interval().num_microseconds().unwrap_or(i64::MAX).max(1);
+        let free = (self.tolerance() - ahead).num_microseconds().unwrap_or(0).max(0);
+
+        let retry_after = (!allowed)
+            .then(|| (tat + self.emission_interval() - self.tolerance() - now).max(Duration::zero()));
+
+        RateLimitDecision {
+            allowed,
+            limit: self.limit,
+            remaining: (free / interval) as u32,
+            reset_after: ahead,
+            retry_after,
+        }
+    }
+}
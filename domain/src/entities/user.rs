@@ -1,4 +1,6 @@
-use crate::entities::role::Role;
+use crate::entities::Role;
+use crate::entities::UserStatus;
+use crate::errors::DomainError;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -11,6 +13,38 @@ pub struct User {
     pub role: Role,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// Incremented by every update; used to detect concurrent modifications
+    #[serde(default = "initial_version")]
+    pub version: i64,
+    /// Set while the user is soft-deleted; repositories hide such users from
+    /// their regular queries until they are restored or purged
+    #[serde(default)]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub status: UserStatus,
+    /// Why the status was last changed, if a reason was given
+    #[serde(default)]
+    pub status_reason: Option<String>,
+    /// `None` until the status changes for the first time
+    #[serde(default)]
+    pub status_changed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// `None` for users stored before password changes were tracked
+    #[serde(default)]
+    pub password_changed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Hashes of earlier passwords, most recent first
+    #[serde(default)]
+    pub password_history: Vec<String>,
+    /// Carried by every token issued to the user; tokens with an older
+    /// version are no longer accepted
+    #[serde(default)]
+    pub token_version: i64,
+    /// When `email` was confirmed by its owner; `None` until then
+    #[serde(default)]
+    pub email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn initial_version() -> i64 {
+    1
 }
 
 impl User {
@@ -24,7 +58,94 @@ impl User {
             role,
             created_at: now,
             updated_at: now,
+            version: initial_version(),
+            deleted_at: None,
+            status: UserStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+            password_changed_at: Some(now),
+            password_history: Vec::new(),
+            token_version: 0,
+            email_verified_at: None,
+        }
+    }
+
+    /// Records a modification: bumps `version` and `updated_at`.
+    ///
+    /// Call this once before passing a changed user to `UserRepository::update`.
+    /// `soft_delete`, `restore` and `change_status` call it themselves; the
+    /// other mutators leave it to the caller.
+    pub fn touch(&mut self) {
+        self.version += 1;
+        self.updated_at = chrono::Utc::now();
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Marks the user as deleted.
+    pub fn soft_delete(&mut self) {
+        self.touch();
+        self.deleted_at = Some(self.updated_at);
+    }
+
+    /// Undoes `soft_delete`; the user keeps its username and email.
+    pub fn restore(&mut self) {
+        self.touch();
+        self.deleted_at = None;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
+
+    /// Moves the user to `status` if `UserStatus::can_transition_to` allows
+    /// it, and fails with `DomainError::Conflict` otherwise.
+    pub fn change_status(&mut self, status: UserStatus, reason: Option<String>) -> Result<(), DomainError> {
+        if !self.status.can_transition_to(status) {
+            return Err(DomainError::Conflict(format!(
+                "Cannot change status of user {} from {} to {}",
+                self.id, self.status, status
+            )));
         }
+
+        self.touch();
+        self.status = status;
+        self.status_reason = reason;
+        self.status_changed_at = Some(self.updated_at);
+        Ok(())
+    }
+
+    /// Replaces the password with `password_hash` and keeps the previous hash
+    /// in `password_history`, which is cut down to `history_size` hashes.
+    pub fn set_password(&mut self, password_hash: String, history_size: usize) {
+        let previous = std::mem::replace(&mut self.password_hash, password_hash);
+        self.password_history.insert(0, previous);
+        self.password_history.truncate(history_size);
+        self.password_changed_at = Some(chrono::Utc::now());
+    }
+
+    /// Invalidates every token issued to the user so far.
+    pub fn revoke_tokens(&mut self) {
+        self.token_version += 1;
+    }
+
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
+    /// Makes `email`, which its owner has just confirmed, the user's verified
+    /// address.
+    pub fn verify_email(&mut self, email: String) {
+        self.email = email;
+        self.email_verified_at = Some(chrono::Utc::now());
+    }
+
+    /// When the current password was set; the creation time for users stored
+    /// before password changes were tracked.
+    pub fn password_set_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.password_changed_at.unwrap_or(self.created_at)
     }
 
     pub fn has_permission(&self, permission: &str) -> bool {
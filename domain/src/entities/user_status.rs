@@ -0,0 +1,65 @@
+use crate::errors::DomainError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Lifecycle state of an account. Only active users can log in or use a token.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum UserStatus {
+    #[default]
+    Active,
+    /// Blocked by an administrator
+    Suspended,
+    /// Blocked automatically, e.g. after too many failed logins
+    Locked,
+    /// Closed by the user or an administrator
+    Deactivated,
+}
+
+impl UserStatus {
+    /// Whether an account in this status may be moved to `next`.
+    ///
+    /// Every blocked status can return to `Active`; a deactivated account can
+    /// only be reactivated.
+    pub fn can_transition_to(self, next: UserStatus) -> bool {
+        use UserStatus::*;
+
+        matches!(
+            (self, next),
+            (Active, Suspended | Locked | Deactivated)
+                | (Suspended, Active | Deactivated)
+                | (Locked, Active | Suspended | Deactivated)
+                | (Deactivated, Active)
+        )
+    }
+
+    /// Name used in storage
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Locked => "locked",
+            UserStatus::Deactivated => "deactivated",
+        }
+    }
+}
+
+impl fmt::Display for UserStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for UserStatus {
+    type Err = DomainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(UserStatus::Active),
+            "suspended" => Ok(UserStatus::Suspended),
+            "locked" => Ok(UserStatus::Locked),
+            "deactivated" => Ok(UserStatus::Deactivated),
+            other => Err(DomainError::ValidationError(format!("Unknown user status: {}", other))),
+        }
+    }
+}
@@ -8,6 +8,9 @@ pub enum DomainError {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
     #[error("Authentication error: {0}")]
     AuthenticationError(String),
     
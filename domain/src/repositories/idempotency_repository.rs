@@ -0,0 +1,20 @@
+use crate::entities::{IdempotencyRecord, StoredResponse};
+use crate::errors::DomainError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Stores `record` unless a live record with the same key exists, which
+    /// is returned instead. Expired records, and unfinished ones created
+    /// before `abandoned_before`, no longer count and are replaced.
+    async fn try_begin(
+        &self,
+        record: &IdempotencyRecord,
+        abandoned_before: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyRecord>, DomainError>;
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), DomainError>;
+    async fn remove(&self, key: &str) -> Result<(), DomainError>;
+    /// Deletes expired records and returns how many there were.
+    async fn purge_expired(&self) -> Result<u64, DomainError>;
+}
@@ -0,0 +1,30 @@
+use crate::entities::{FailedLogins, LoginAttemptKey, LoginAttemptScope};
+use crate::errors::DomainError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Counts failed logins per account and per source address.
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    async fn find(&self, key: &LoginAttemptKey) -> Result<Option<FailedLogins>, DomainError>;
+    /// Atomically adds a failure at `failed_at` and returns the new count.
+    /// A count whose last failure was before `reset_before` starts over.
+    async fn record_failure(
+        &self,
+        key: &LoginAttemptKey,
+        failed_at: DateTime<Utc>,
+        reset_before: DateTime<Utc>,
+    ) -> Result<FailedLogins, DomainError>;
+    async fn reset(&self, key: &LoginAttemptKey) -> Result<(), DomainError>;
+    /// Counts in `scope` with at least `min_failures` failures since
+    /// `reset_before`, most recent failure first.
+    async fn find_with_failures(
+        &self,
+        scope: LoginAttemptScope,
+        min_failures: u32,
+        reset_before: DateTime<Utc>,
+    ) -> Result<Vec<FailedLogins>, DomainError>;
+    /// Deletes counts whose last failure was before `reset_before` and
+    /// returns how many there were.
+    async fn purge_stale(&self, reset_before: DateTime<Utc>) -> Result<u64, DomainError>;
+}
@@ -0,0 +1,24 @@
+use crate::entities::TotpEnrollment;
+use crate::errors::DomainError;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Stores TOTP enrollments, at most one per user, and the hashes of their
+/// recovery codes.
+#[async_trait]
+pub trait MfaRepository: Send + Sync {
+    /// Stores `enrollment`, replacing any other enrollment of the same user.
+    async fn save(&self, enrollment: &TotpEnrollment) -> Result<(), DomainError>;
+    async fn find(&self, user_id: &Uuid) -> Result<Option<TotpEnrollment>, DomainError>;
+    /// Deletes the user's enrollment and recovery codes; whether there was an enrollment.
+    async fn delete(&self, user_id: &Uuid) -> Result<bool, DomainError>;
+    /// Atomically records `step` as the last used time step of the user's
+    /// enrollment if it is later than the recorded one; `false` if it is not,
+    /// i.e. the code was used before.
+    async fn record_step(&self, user_id: &Uuid, step: i64) -> Result<bool, DomainError>;
+    /// Replaces all recovery codes of the user.
+    async fn replace_recovery_codes(&self, user_id: &Uuid, code_hashes: &[String]) -> Result<(), DomainError>;
+    /// Atomically removes the recovery code; `false` if the user has no such code.
+    async fn consume_recovery_code(&self, user_id: &Uuid, code_hash: &str) -> Result<bool, DomainError>;
+    async fn count_recovery_codes(&self, user_id: &Uuid) -> Result<u64, DomainError>;
+}
@@ -1,3 +1,17 @@
+mod email_verification_repository;
+mod idempotency_repository;
+mod login_attempt_repository;
+mod mfa_repository;
+mod password_reset_repository;
+mod rate_limit_repository;
+mod unit_of_work;
 mod user_repository;
 
+pub use email_verification_repository::*;
+pub use idempotency_repository::*;
+pub use login_attempt_repository::*;
+pub use mfa_repository::*;
+pub use password_reset_repository::*;
+pub use rate_limit_repository::*;
+pub use unit_of_work::*;
 pub use user_repository::*;
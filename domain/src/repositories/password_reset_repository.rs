@@ -0,0 +1,19 @@
+use crate::entities::PasswordResetToken;
+use crate::errors::DomainError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Stores password reset tokens by the hash of the token.
+#[async_trait]
+pub trait PasswordResetTokenRepository: Send + Sync {
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), DomainError>;
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, DomainError>;
+    /// Atomically marks the token used if it is still usable at `now` and
+    /// returns it; `None` if it is unknown, expired or already used.
+    async fn consume(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<PasswordResetToken>, DomainError>;
+    /// Deletes every token of the user and returns how many there were.
+    async fn delete_for_user(&self, user_id: &Uuid) -> Result<u64, DomainError>;
+    /// Deletes tokens that expired before `now` and returns how many there were.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError>;
+}
@@ -0,0 +1,19 @@
+use crate::entities::{RateLimitDecision, RateLimitQuota};
+use crate::errors::DomainError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Keeps the GCRA state of rate limited keys.
+#[async_trait]
+pub trait RateLimitRepository: Send + Sync {
+    /// Atomically applies one request at `now` to `key` under `quota`.
+    async fn acquire(
+        &self,
+        key: &str,
+        quota: &RateLimitQuota,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitDecision, DomainError>;
+    /// Deletes keys whose bucket is full again by `now`; they behave exactly
+    /// like keys without history. Returns how many there were.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError>;
+}
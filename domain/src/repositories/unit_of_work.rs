@@ -0,0 +1,21 @@
+use crate::errors::DomainError;
+use crate::repositories::UserRepository;
+use async_trait::async_trait;
+
+/// Starts transactions spanning several repository operations.
+#[async_trait]
+pub trait UnitOfWork: Send + Sync {
+    async fn begin(&self) -> Result<Box<dyn RepositoryTransaction>, DomainError>;
+}
+
+/// Transaction-scoped repositories.
+///
+/// Writes made through `users()` are visible to reads in the same transaction
+/// and to everyone else only after `commit`. Dropping the transaction without
+/// committing rolls it back.
+#[async_trait]
+pub trait RepositoryTransaction: Send + Sync {
+    fn users(&self) -> &dyn UserRepository;
+    async fn commit(self: Box<Self>) -> Result<(), DomainError>;
+    async fn rollback(self: Box<Self>) -> Result<(), DomainError>;
+}
@@ -1,15 +1,77 @@
 use crate::entities::User;
 use crate::errors::DomainError;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Storage of users.
+///
+/// Users are soft-deleted by storing them with `deleted_at` set (see
+/// `User::soft_delete`). The `find_*` queries skip soft-deleted users unless
+/// their name says otherwise, but a soft-deleted user keeps its username and
+/// email reserved until it is purged.
 #[async_trait]
 pub trait UserRepository: Send + Sync {
     async fn create(&self, user: &User) -> Result<(), DomainError>;
+    /// Stores `user` only if the stored version is the one before `user.version`
+    /// (see `User::touch`), and fails with `DomainError::Conflict` otherwise.
+    /// Soft-deleted users can be updated too, which is how they are restored.
     async fn update(&self, user: &User) -> Result<(), DomainError>;
+    /// Permanently removes the user, whether soft-deleted or not.
     async fn delete(&self, id: &Uuid) -> Result<(), DomainError>;
     async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError>;
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError>;
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError>;
     async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError>;
     async fn find_all(&self) -> Result<Vec<User>, DomainError>;
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError>;
+    /// Permanently removes users soft-deleted before `deleted_before` and
+    /// returns their ids, so that what they owned can be removed too.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError>;
+}
+
+/// Lets shared repositories be used wherever a `UserRepository` is expected,
+/// e.g. wrapped by a decorator while another owner keeps a handle to it.
+#[async_trait]
+impl<T: UserRepository + ?Sized> UserRepository for Arc<T> {
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        (**self).create(user).await
+    }
+
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        (**self).update(user).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        (**self).delete(id).await
+    }
+
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        (**self).find_by_id(id).await
+    }
+
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        (**self).find_by_id_including_deleted(id).await
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        (**self).find_by_username(username).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        (**self).find_by_email(email).await
+    }
+
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        (**self).find_all().await
+    }
+
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        (**self).find_all_including_deleted().await
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        (**self).purge_deleted(deleted_before).await
+    }
 }
@@ -1,4 +1,4 @@
-use crate::entities::role::RoleName;
+use crate::entities::RoleName;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -14,6 +14,28 @@ pub struct JwtClaims {
     pub exp: i64,     // Expiration time (as UTC timestamp)
     pub iat: i64,     // Issued at (as UTC timestamp)
     pub role: RoleName,
+    #[serde(default)]
+    pub scope: TokenScope,
+    /// The user's `token_version` when the token was issued
+    #[serde(default)]
+    pub token_version: i64,
+}
+
+/// What a token may be used for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Everything the user's role allows
+    #[default]
+    Full,
+    /// Only changing the user's own password, which has expired
+    PasswordChange,
+    /// Only reading, until the user has verified their email address
+    ReadOnly,
+    /// Only completing a login with a second factor
+    MfaChallenge,
+    /// Only enrolling a second factor, which the user's role requires
+    MfaEnrollment,
 }
 
 impl JwtToken {
@@ -37,6 +59,32 @@ impl JwtClaims {
             exp,
             iat,
             role,
+            scope: TokenScope::Full,
+            token_version: 0,
+        }
+    }
+
+    /// Claims for a token that only permits changing the user's own password.
+    pub fn password_change(user_id: Uuid, role: RoleName, expires_in_seconds: i64) -> Self {
+        Self {
+            scope: TokenScope::PasswordChange,
+            ..Self::new(user_id, role, expires_in_seconds)
+        }
+    }
+
+    /// Claims for a token that only permits completing a login with a second factor.
+    pub fn mfa_challenge(user_id: Uuid, role: RoleName, expires_in_seconds: i64) -> Self {
+        Self {
+            scope: TokenScope::MfaChallenge,
+            ..Self::new(user_id, role, expires_in_seconds)
+        }
+    }
+
+    /// Claims for a token that only permits enrolling a second factor.
+    pub fn mfa_enrollment(user_id: Uuid, role: RoleName, expires_in_seconds: i64) -> Self {
+        Self {
+            scope: TokenScope::MfaEnrollment,
+            ..Self::new(user_id, role, expires_in_seconds)
         }
     }
 }
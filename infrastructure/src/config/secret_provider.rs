@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, instrument};
+
+use super::secret::Secret;
+use crate::errors::InfrastructureError;
+
+/// Source of secret configuration values.
+///
+/// Keys are the environment variable names used by `EnvConfigProvider`
+/// (for example `JWT_SECRET`); each provider maps them onto its own storage.
+pub trait SecretProvider: Send + Sync {
+    fn get_secret(&self, key: &str) -> Result<Option<Secret>, InfrastructureError>;
+}
+
+/// Reads secrets straight from environment variables.
+pub struct EnvSecretProvider;
+
+impl SecretProvider for EnvSecretProvider {
+    fn get_secret(&self, key: &str) -> Result<Option<Secret>, InfrastructureError> {
+        Ok(env::var(key).ok().map(Secret::from))
+    }
+}
+
+/// Reads secrets from one file per key inside a directory, e.g. Docker or
+/// Kubernetes secrets mounted at `/run/secrets`. File names are the
+/// lower-cased keys (`JWT_SECRET` -> `/run/secrets/jwt_secret`).
+pub struct FileSecretProvider {
+    directory: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl SecretProvider for FileSecretProvider {
+    fn get_secret(&self, key: &str) -> Result<Option<Secret>, InfrastructureError> {
+        let path = self.directory.join(key.to_lowercase());
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        read_secret_file(&path).map(Some)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VaultConfig {
+    pub address: String,
+    pub token: Secret,
+    /// Full API path of the secret, e.g. `secret/data/rust-server` for a KV v2 mount.
+    pub secret_path: String,
+    pub timeout: Duration,
+}
+
+/// Reads secrets from a single secret in a Vault-compatible KV engine over HTTP.
+///
+/// Field names inside the secret are the lower-cased keys. The secret is
+/// fetched once on first use and kept for the lifetime of the provider.
+pub struct VaultSecretProvider {
+    config: VaultConfig,
+    values: Mutex<Option<HashMap<String, String>>>,
+}
+
+impl VaultSecretProvider {
+    pub fn new(config: VaultConfig) -> Self {
+        Self {
+            config,
+            values: Mutex::new(None),
+        }
+    }
+
+    #[instrument(skip(self), fields(path = %self.config.secret_path))]
+    fn fetch(&self) -> Result<HashMap<String, String>, InfrastructureError> {
+        info!("Fetching secrets from Vault");
+
+        let url = format!(
+            "{}/v1/{}",
+            self.config.address.trim_end_matches('/'),
+            self.config.secret_path.trim_start_matches('/')
+        );
+
+        let body: serde_json::Value = ureq::get(&url)
+            .set("X-Vault-Token", self.config.token.expose())
+            .timeout(self.config.timeout)
+            .call()
+            .map_err(|e| InfrastructureError::ConfigurationError(format!("Vault request failed: {}", e)))?
+            .into_json()?;
+
+        // KV v2 nests the fields under `data.data`, KV v1 returns them under `data`
+        let data = body.get("data").ok_or_else(|| {
+            InfrastructureError::ConfigurationError("Vault response has no data".to_string())
+        })?;
+        let fields = data.get("data").filter(|d| d.is_object()).unwrap_or(data);
+
+        let values = fields
+            .as_object()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(values)
+    }
+}
+
+impl SecretProvider for VaultSecretProvider {
+    fn get_secret(&self, key: &str) -> Result<Option<Secret>, InfrastructureError> {
+        let mut values = self.values.lock().map_err(|e| {
+            InfrastructureError::ConfigurationError(format!("Failed to acquire lock: {}", e))
+        })?;
+
+        if values.is_none() {
+            *values = Some(self.fetch()?);
+        }
+
+        Ok(values
+            .as_ref()
+            .and_then(|values| values.get(&key.to_lowercase()))
+            .cloned()
+            .map(Secret::from))
+    }
+}
+
+/// Resolves a secret, honouring the `<KEY>_FILE` convention before asking the provider.
+///
+/// `JWT_SECRET_FILE=/run/secrets/jwt` takes precedence over whatever the
+/// provider returns for `JWT_SECRET`.
+pub fn resolve_secret(
+    provider: &dyn SecretProvider,
+    key: &str,
+) -> Result<Option<Secret>, InfrastructureError> {
+    if let Ok(path) = env::var(format!("{}_FILE", key)) {
+        return read_secret_file(Path::new(&path)).map(Some);
+    }
+
+    provider.get_secret(key)
+}
+
+/// Builds the provider selected by `SECRET_PROVIDER` (`env`, `file` or `vault`).
+pub fn secret_provider_from_env() -> Result<Box<dyn SecretProvider>, InfrastructureError> {
+    let kind = env::var("SECRET_PROVIDER").unwrap_or_else(|_| "env".to_string());
+
+    match kind.to_lowercase().as_str() {
+        "env" => Ok(Box::new(EnvSecretProvider)),
+        "file" => {
+            let directory = env::var("SECRETS_DIR").unwrap_or_else(|_| "/run/secrets".to_string());
+            Ok(Box::new(FileSecretProvider::new(directory)))
+        }
+        "vault" => {
+            let address = env::var("VAULT_ADDR").unwrap_or_else(|_| "http://127.0.0.1:8200".to_string());
+            let token = resolve_secret(&EnvSecretProvider, "VAULT_TOKEN")?.ok_or_else(|| {
+                InfrastructureError::ConfigurationError(
+                    "VAULT_TOKEN or VAULT_TOKEN_FILE must be set".to_string(),
+                )
+            })?;
+            let secret_path = env::var("VAULT_SECRET_PATH")
+                .unwrap_or_else(|_| "secret/data/rust-server".to_string());
+            let timeout = env::var("VAULT_TIMEOUT")
+                .unwrap_or_else(|_| "5".to_string()) // seconds
+                .parse::<u64>()
+                .map_err(|e| {
+                    InfrastructureError::ConfigurationError(format!("Invalid Vault timeout: {}", e))
+                })?;
+
+            Ok(Box::new(VaultSecretProvider::new(VaultConfig {
+                address,
+                token,
+                secret_path,
+                timeout: Duration::from_secs(timeout),
+            })))
+        }
+        other => Err(InfrastructureError::ConfigurationError(format!(
+            "Unknown secret provider: {}",
+            other
+        ))),
+    }
+}
+
+fn read_secret_file(path: &Path) -> Result<Secret, InfrastructureError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        InfrastructureError::ConfigurationError(format!(
+            "Failed to read secret file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    // Files written with `echo` or editors usually end in a newline that is not part of the secret
+    Ok(Secret::new(contents.trim_end_matches(['\r', '\n'])))
+}
@@ -0,0 +1,59 @@
+use application::errors::ApplicationError;
+use application::services::{EmailMessage, Notifier};
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use std::path::PathBuf;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use super::{build_email, email_error, sender_mailbox};
+use crate::config::EmailConfig;
+use crate::errors::InfrastructureError;
+
+/// Writes emails to a local maildir instead of sending them, so that
+/// development setups and tests can read them without a mail server.
+///
+/// Each email is written to `tmp/` and then moved to `new/`, so readers
+/// never see a partial file.
+pub struct FileNotifier {
+    directory: PathBuf,
+    from: Mailbox,
+}
+
+impl FileNotifier {
+    pub fn new(config: &EmailConfig) -> Result<Self, InfrastructureError> {
+        let directory = PathBuf::from(&config.file_dir);
+        for subdirectory in ["tmp", "new", "cur"] {
+            std::fs::create_dir_all(directory.join(subdirectory)).map_err(|e| {
+                InfrastructureError::ConfigurationError(format!(
+                    "Failed to create maildir {}: {}",
+                    directory.display(),
+                    e
+                ))
+            })?;
+        }
+
+        Ok(Self {
+            directory,
+            from: sender_mailbox(config)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    #[instrument(skip(self, message), fields(subject = %message.subject))]
+    async fn send(&self, message: EmailMessage) -> Result<(), ApplicationError> {
+        let email = build_email(&self.from, message)?;
+
+        let name = format!("{}.{}.localhost", chrono::Utc::now().timestamp(), Uuid::new_v4().simple());
+        let staged = self.directory.join("tmp").join(&name);
+        let delivered = self.directory.join("new").join(&name);
+
+        tokio::fs::write(&staged, email.formatted()).await.map_err(email_error)?;
+        tokio::fs::rename(&staged, &delivered).await.map_err(email_error)?;
+        info!(path = %delivered.display(), "Email written to maildir");
+
+        Ok(())
+    }
+}
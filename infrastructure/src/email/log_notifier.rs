@@ -0,0 +1,15 @@
+use application::errors::ApplicationError;
+use application::services::{EmailMessage, Notifier};
+use async_trait::async_trait;
+use tracing::info;
+
+/// Writes emails to the log instead of sending them, for development.
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn send(&self, message: EmailMessage) -> Result<(), ApplicationError> {
+        info!(to = %message.to, subject = %message.subject, "Email not sent: {}", message.body);
+        Ok(())
+    }
+}
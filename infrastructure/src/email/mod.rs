@@ -0,0 +1,63 @@
+mod file_notifier;
+mod log_notifier;
+mod smtp_notifier;
+
+pub use file_notifier::*;
+pub use log_notifier::*;
+pub use smtp_notifier::*;
+
+use application::errors::ApplicationError;
+use application::services::{EmailMessage, Notifier};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::Message;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::{EmailConfig, EmailDelivery};
+use crate::errors::InfrastructureError;
+
+/// Builds the notifier for the configured email delivery.
+pub fn create_notifier(config: &EmailConfig) -> Result<Arc<dyn Notifier>, InfrastructureError> {
+    match config.delivery {
+        EmailDelivery::Log => {
+            warn!("Emails are written to the log instead of being sent; set EMAIL_DELIVERY=smtp to send them");
+            Ok(Arc::new(LogNotifier))
+        }
+        EmailDelivery::Smtp => {
+            info!("Sending emails through {}:{}", config.smtp.host, config.smtp.port);
+            Ok(Arc::new(SmtpNotifier::new(config)?))
+        }
+        EmailDelivery::File => {
+            warn!("Emails are written to the maildir {} instead of being sent", config.file_dir);
+            Ok(Arc::new(FileNotifier::new(config)?))
+        }
+    }
+}
+
+fn email_error(e: impl std::fmt::Display) -> ApplicationError {
+    ApplicationError::UnexpectedError(format!("Failed to send email: {}", e))
+}
+
+fn sender_mailbox(config: &EmailConfig) -> Result<Mailbox, InfrastructureError> {
+    config
+        .from
+        .parse::<Mailbox>()
+        .map_err(|e| InfrastructureError::ConfigurationError(format!("Invalid EMAIL_FROM: {}", e)))
+}
+
+/// `message` as a complete email from `from`.
+fn build_email(from: &Mailbox, message: EmailMessage) -> Result<Message, ApplicationError> {
+    let to = message
+        .to
+        .parse::<Mailbox>()
+        .map_err(|e| email_error(format!("invalid recipient: {}", e)))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(message.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.body)
+        .map_err(email_error)
+}
@@ -0,0 +1,57 @@
+use application::errors::ApplicationError;
+use application::services::{EmailMessage, Notifier};
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use tracing::{info, instrument};
+
+use super::{build_email, email_error, sender_mailbox};
+use crate::config::{EmailConfig, SmtpTls};
+use crate::errors::InfrastructureError;
+
+/// Sends emails through an SMTP relay.
+pub struct SmtpNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpNotifier {
+    pub fn new(config: &EmailConfig) -> Result<Self, InfrastructureError> {
+        let smtp = &config.smtp;
+        let invalid = |e: &dyn std::fmt::Display| {
+            InfrastructureError::ConfigurationError(format!("Invalid SMTP configuration: {}", e))
+        };
+
+        let mut builder = match smtp.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)
+                .map_err(|e| invalid(&e))?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host).map_err(|e| invalid(&e))?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host),
+        }
+        .port(smtp.port);
+
+        if let Some(username) = &smtp.username {
+            let password = smtp.password.as_ref().map(|p| p.expose().to_string()).unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: sender_mailbox(config)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    #[instrument(skip(self, message), fields(subject = %message.subject))]
+    async fn send(&self, message: EmailMessage) -> Result<(), ApplicationError> {
+        let email = build_email(&self.from, message)?;
+
+        self.transport.send(email).await.map_err(email_error)?;
+        info!("Email sent");
+
+        Ok(())
+    }
+}
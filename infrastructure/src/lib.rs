@@ -1,5 +1,9 @@
 pub mod config;
+pub mod email;
 pub mod errors;
 pub mod persistence;
 pub mod security;
 pub mod tracing;
+
+#[cfg(test)]
+mod tests;
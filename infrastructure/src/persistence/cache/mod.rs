@@ -0,0 +1,3 @@
+mod user_repository;
+
+pub use user_repository::*;
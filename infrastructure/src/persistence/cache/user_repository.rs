@@ -0,0 +1,469 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::User;
+use domain::errors::DomainError;
+use domain::repositories::{RepositoryTransaction, UnitOfWork, UserRepository};
+use lru::LruCache;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument};
+use uuid::Uuid;
+
+use crate::config::CacheConfig;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered with a cached user
+    pub hits: u64,
+    /// Lookups answered with a cached "not found"
+    pub negative_hits: u64,
+    /// Lookups passed through to the inner repository
+    pub misses: u64,
+}
+
+/// Counters shared between a `CachedUserRepository` and whoever reports them
+#[derive(Debug, Default)]
+pub struct CacheMetrics {
+    hits: AtomicU64,
+    negative_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheMetrics {
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            negative_hits: self.negative_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+/// LRU cache whose entries also expire after a per-entry TTL
+struct TtlCache<K: Hash + Eq, V> {
+    entries: LruCache<K, Entry<V>>,
+}
+
+impl<K: Hash + Eq, V: Clone> TtlCache<K, V> {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        match self.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                self.entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&mut self, key: K, value: V, ttl: Duration) {
+        if ttl.is_zero() {
+            self.entries.pop(&key);
+            return;
+        }
+
+        self.entries.put(
+            key,
+            Entry {
+                value,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.pop(key);
+    }
+}
+
+/// Users by id, plus username and email aliases pointing at ids.
+///
+/// `None` values are negative entries. An alias whose user no longer carries
+/// that username or email (it was renamed) is treated as a miss.
+struct Indexes {
+    by_id: TtlCache<Uuid, Option<User>>,
+    by_username: TtlCache<String, Option<Uuid>>,
+    by_email: TtlCache<String, Option<Uuid>>,
+}
+
+enum Key<'a> {
+    Id(&'a Uuid),
+    Username(&'a str),
+    Email(&'a str),
+}
+
+/// Cache state shared by a `CachedUserRepository` and its `CachedUnitOfWork`
+struct UserCache {
+    indexes: Mutex<Indexes>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    // Bumped around every write so a lookup that raced with it does not cache stale data
+    generation: AtomicU64,
+    metrics: Arc<CacheMetrics>,
+}
+
+impl UserCache {
+    fn lock(&self) -> Result<MutexGuard<'_, Indexes>, DomainError> {
+        self.indexes.lock().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire cache lock: {}", e))
+        })
+    }
+
+    /// `Some(result)` when the cache can answer the lookup
+    fn lookup(&self, key: &Key<'_>) -> Result<Option<Option<User>>, DomainError> {
+        let mut indexes = self.lock()?;
+
+        let cached = match key {
+            Key::Id(id) => indexes.by_id.get(id),
+            Key::Username(username) => match indexes.by_username.get(&username.to_string()) {
+                Some(Some(id)) => indexes
+                    .by_id
+                    .get(&id)
+                    .filter(|user| matches!(user, Some(user) if user.username == *username)),
+                other => other.map(|_| None),
+            },
+            Key::Email(email) => match indexes.by_email.get(&email.to_string()) {
+                Some(Some(id)) => indexes
+                    .by_id
+                    .get(&id)
+                    .filter(|user| matches!(user, Some(user) if user.email == *email)),
+                other => other.map(|_| None),
+            },
+        };
+
+        match &cached {
+            Some(Some(_)) => self.metrics.hits.fetch_add(1, Ordering::Relaxed),
+            Some(None) => self.metrics.negative_hits.fetch_add(1, Ordering::Relaxed),
+            None => self.metrics.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        Ok(cached)
+    }
+
+    fn store(&self, generation: u64, key: &Key<'_>, result: &Option<User>) -> Result<(), DomainError> {
+        let mut indexes = self.lock()?;
+
+        // A write happened while we were reading; its result may already be stale
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return Ok(());
+        }
+
+        match result {
+            Some(user) => Self::populate(&mut indexes, user, self.ttl),
+            None => match key {
+                Key::Id(id) => indexes.by_id.put(**id, None, self.negative_ttl),
+                Key::Username(username) => {
+                    indexes.by_username.put(username.to_string(), None, self.negative_ttl)
+                }
+                Key::Email(email) => indexes.by_email.put(email.to_string(), None, self.negative_ttl),
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Runs a write against the inner repository and then refreshes the cache
+    /// with `refresh`, which also runs when the write failed.
+    async fn write<F>(
+        &self,
+        result: impl std::future::Future<Output = Result<(), DomainError>>,
+        refresh: F,
+    ) -> Result<(), DomainError>
+    where
+        F: FnOnce(&mut Indexes, bool),
+    {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        let result = result.await;
+
+        let mut indexes = self.lock()?;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        refresh(&mut indexes, result.is_ok());
+
+        result
+    }
+
+    fn populate(indexes: &mut Indexes, user: &User, ttl: Duration) {
+        // Lookups do not return soft-deleted users, so neither may the cache
+        if user.is_deleted() {
+            return;
+        }
+        indexes.by_id.put(user.id, Some(user.clone()), ttl);
+        indexes.by_username.put(user.username.clone(), Some(user.id), ttl);
+        indexes.by_email.put(user.email.clone(), Some(user.id), ttl);
+    }
+
+    fn forget(indexes: &mut Indexes, user: &User) {
+        indexes.by_id.remove(&user.id);
+        indexes.by_username.remove(&user.username);
+        indexes.by_email.remove(&user.email);
+    }
+}
+
+/// Read-through cache in front of another `UserRepository`.
+///
+/// Writes go to the inner repository first and then refresh the cache. The
+/// cache is local to the process, so with several instances another
+/// instance's writes become visible only after the TTL.
+pub struct CachedUserRepository<R: UserRepository> {
+    inner: R,
+    cache: Arc<UserCache>,
+}
+
+impl<R: UserRepository> CachedUserRepository<R> {
+    pub fn new(inner: R, config: &CacheConfig) -> Self {
+        let capacity = NonZeroUsize::new(config.capacity).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            inner,
+            cache: Arc::new(UserCache {
+                indexes: Mutex::new(Indexes {
+                    by_id: TtlCache::new(capacity),
+                    by_username: TtlCache::new(capacity),
+                    by_email: TtlCache::new(capacity),
+                }),
+                ttl: Duration::from_secs(config.ttl),
+                negative_ttl: Duration::from_secs(config.negative_ttl),
+                generation: AtomicU64::new(0),
+                metrics: Arc::new(CacheMetrics::default()),
+            }),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<CacheMetrics> {
+        Arc::clone(&self.cache.metrics)
+    }
+
+    /// Wraps the unit of work of the inner repository's storage so that
+    /// committed transactions invalidate this cache.
+    pub fn unit_of_work(&self, inner: Arc<dyn UnitOfWork>) -> CachedUnitOfWork {
+        CachedUnitOfWork {
+            inner,
+            cache: Arc::clone(&self.cache),
+        }
+    }
+
+    async fn find(&self, key: Key<'_>) -> Result<Option<User>, DomainError> {
+        if let Some(cached) = self.cache.lookup(&key)? {
+            debug!("User cache hit");
+            return Ok(cached);
+        }
+
+        debug!("User cache miss");
+        let generation = self.cache.generation.load(Ordering::SeqCst);
+
+        let result = match &key {
+            Key::Id(id) => self.inner.find_by_id(id).await?,
+            Key::Username(username) => self.inner.find_by_username(username).await?,
+            Key::Email(email) => self.inner.find_by_email(email).await?,
+        };
+
+        self.cache.store(generation, &key, &result)?;
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl<R: UserRepository> UserRepository for CachedUserRepository<R> {
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        let ttl = self.cache.ttl;
+        self.cache
+            .write(self.inner.create(user), |indexes, written| {
+                // Drops negative entries for the new keys even if the write failed
+                UserCache::forget(indexes, user);
+                if written {
+                    UserCache::populate(indexes, user, ttl);
+                }
+            })
+            .await
+    }
+
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        let ttl = self.cache.ttl;
+        self.cache
+            .write(self.inner.update(user), |indexes, written| {
+                // Aliases of the old username and email are invalidated by the id entry changing
+                UserCache::forget(indexes, user);
+                if written {
+                    UserCache::populate(indexes, user, ttl);
+                }
+            })
+            .await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        self.cache
+            .write(self.inner.delete(id), |indexes, _| {
+                indexes.by_id.remove(id);
+            })
+            .await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        self.find(Key::Id(id)).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        self.inner.find_by_id_including_deleted(id).await
+    }
+
+    #[instrument(skip(self), fields(username = %username))]
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        self.find(Key::Username(username)).await
+    }
+
+    #[instrument(skip(self), fields(email = %email))]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        self.find(Key::Email(email)).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        self.inner.find_all().await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        self.inner.find_all_including_deleted().await
+    }
+
+    /// Purged users were soft-deleted and therefore not cached, and their
+    /// usernames and emails were taken until now, so no entry goes stale.
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        self.inner.purge_deleted(deleted_before).await
+    }
+}
+
+/// Unit of work whose commits invalidate the users they wrote from a
+/// `CachedUserRepository`. Reads inside a transaction bypass the cache.
+pub struct CachedUnitOfWork {
+    inner: Arc<dyn UnitOfWork>,
+    cache: Arc<UserCache>,
+}
+
+#[async_trait]
+impl UnitOfWork for CachedUnitOfWork {
+    async fn begin(&self) -> Result<Box<dyn RepositoryTransaction>, DomainError> {
+        Ok(Box::new(CachedTransaction {
+            inner: self.inner.begin().await?,
+            cache: Arc::clone(&self.cache),
+            written: Mutex::new(Vec::new()),
+        }))
+    }
+}
+
+/// Keys a transaction wrote, invalidated once it commits
+enum Written {
+    User(Box<User>),
+    Id(Uuid),
+}
+
+struct CachedTransaction {
+    inner: Box<dyn RepositoryTransaction>,
+    cache: Arc<UserCache>,
+    written: Mutex<Vec<Written>>,
+}
+
+impl CachedTransaction {
+    fn record(&self, written: Written) -> Result<(), DomainError> {
+        self.written
+            .lock()
+            .map_err(|e| DomainError::RepositoryError(format!("Failed to acquire cache lock: {}", e)))?
+            .push(written);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RepositoryTransaction for CachedTransaction {
+    fn users(&self) -> &dyn UserRepository {
+        self
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DomainError> {
+        let CachedTransaction { inner, cache, written } = *self;
+        let written = written.into_inner().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire cache lock: {}", e))
+        })?;
+
+        cache
+            .write(inner.commit(), |indexes, _| {
+                for written in &written {
+                    match written {
+                        Written::User(user) => UserCache::forget(indexes, user),
+                        Written::Id(id) => indexes.by_id.remove(id),
+                    }
+                }
+            })
+            .await
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DomainError> {
+        self.inner.rollback().await
+    }
+}
+
+#[async_trait]
+impl UserRepository for CachedTransaction {
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        self.inner.users().create(user).await?;
+        self.record(Written::User(Box::new(user.clone())))
+    }
+
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        self.inner.users().update(user).await?;
+        self.record(Written::User(Box::new(user.clone())))
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        self.inner.users().delete(id).await?;
+        self.record(Written::Id(*id))
+    }
+
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        self.inner.users().find_by_id(id).await
+    }
+
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        self.inner.users().find_by_id_including_deleted(id).await
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        self.inner.users().find_by_username(username).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        self.inner.users().find_by_email(email).await
+    }
+
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        self.inner.users().find_all().await
+    }
+
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        self.inner.users().find_all_including_deleted().await
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        self.inner.users().purge_deleted(deleted_before).await
+    }
+}
@@ -0,0 +1,1153 @@
+//! Behaviour every `UserRepository` implementation must share.
+//!
+//! Run the whole suite against a backend with [`run_user_repository_conformance`],
+//! and its transactions with [`run_unit_of_work_conformance`].
+//! Idempotency key stores have their own suite, [`run_idempotency_repository_conformance`],
+//! and so do failed login counters, [`run_login_attempt_repository_conformance`],
+//! rate limit stores, [`run_rate_limit_repository_conformance`], password
+//! reset tokens, [`run_password_reset_repository_conformance`], email
+//! verification tokens, [`run_email_verification_repository_conformance`], and
+//! TOTP enrollments, [`run_mfa_repository_conformance`].
+//! Every case works on users with unique names, so the suite can run against a
+//! shared database that already contains data.
+
+use chrono::{Duration, SubsecRound, Utc};
+use domain::entities::{Role, RoleName};
+use domain::entities::{
+    EmailVerificationToken, IdempotencyRecord, LoginAttemptKey, LoginAttemptScope, PasswordResetToken, RateLimitQuota,
+    StoredResponse, TotpEnrollment, User, UserStatus,
+};
+use domain::errors::DomainError;
+use domain::repositories::{
+    EmailVerificationTokenRepository, IdempotencyRepository, LoginAttemptRepository, MfaRepository,
+    PasswordResetTokenRepository, RateLimitRepository, UnitOfWork, UserRepository,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Number of tasks racing in the concurrency cases
+const CONCURRENT_WRITERS: usize = 8;
+
+pub async fn run_user_repository_conformance(repository: Arc<dyn UserRepository>) {
+    create_then_find(repository.as_ref()).await;
+    find_missing_returns_none(repository.as_ref()).await;
+    create_rejects_duplicate_username(repository.as_ref()).await;
+    create_rejects_duplicate_email(repository.as_ref()).await;
+    update_persists_changes(repository.as_ref()).await;
+    update_keeps_own_username_and_email(repository.as_ref()).await;
+    update_rejects_taken_username(repository.as_ref()).await;
+    update_rejects_taken_email(repository.as_ref()).await;
+    update_with_stale_version_conflicts(repository.as_ref()).await;
+    status_change_persists(repository.as_ref()).await;
+    password_change_persists(repository.as_ref()).await;
+    email_verification_persists(repository.as_ref()).await;
+    update_missing_is_not_found(repository.as_ref()).await;
+    delete_removes_user(repository.as_ref()).await;
+    delete_missing_is_not_found(repository.as_ref()).await;
+    soft_deleted_user_is_hidden(repository.as_ref()).await;
+    soft_deleted_user_keeps_username_and_email(repository.as_ref()).await;
+    restored_user_is_visible_again(repository.as_ref()).await;
+    purge_removes_users_deleted_before_cutoff(repository.as_ref()).await;
+    find_all_includes_created_users(repository.as_ref()).await;
+    concurrent_creates_of_distinct_users_succeed(Arc::clone(&repository)).await;
+    concurrent_creates_of_same_username_admit_one(Arc::clone(&repository)).await;
+}
+
+/// `unit_of_work` must start transactions on the storage behind `repository`.
+pub async fn run_unit_of_work_conformance(
+    unit_of_work: Arc<dyn UnitOfWork>,
+    repository: Arc<dyn UserRepository>,
+) {
+    committed_writes_become_visible(unit_of_work.as_ref(), repository.as_ref()).await;
+    uncommitted_writes_are_isolated(unit_of_work.as_ref(), repository.as_ref()).await;
+    dropped_transaction_rolls_back(unit_of_work.as_ref(), repository.as_ref()).await;
+    transaction_sees_its_own_delete(unit_of_work.as_ref(), repository.as_ref()).await;
+    transaction_rejects_duplicate_username(unit_of_work.as_ref(), repository.as_ref()).await;
+}
+
+pub async fn run_idempotency_repository_conformance(repository: Arc<dyn IdempotencyRepository>) {
+    first_request_claims_key(repository.as_ref()).await;
+    completed_request_is_returned_with_response(repository.as_ref()).await;
+    removed_key_can_be_claimed_again(repository.as_ref()).await;
+    abandoned_key_can_be_claimed_again(repository.as_ref()).await;
+    expired_key_can_be_claimed_again(repository.as_ref()).await;
+    purge_removes_only_expired_keys(repository.as_ref()).await;
+    concurrent_claims_of_same_key_admit_one(Arc::clone(&repository)).await;
+}
+
+pub async fn run_login_attempt_repository_conformance(repository: Arc<dyn LoginAttemptRepository>) {
+    failures_accumulate(repository.as_ref()).await;
+    stale_failures_start_over(repository.as_ref()).await;
+    reset_clears_failures(repository.as_ref()).await;
+    find_with_failures_filters_and_orders(repository.as_ref()).await;
+    purge_removes_only_stale_failures(repository.as_ref()).await;
+    concurrent_failures_are_all_counted(Arc::clone(&repository)).await;
+}
+
+pub async fn run_rate_limit_repository_conformance(repository: Arc<dyn RateLimitRepository>) {
+    burst_is_allowed_then_refused(repository.as_ref()).await;
+    keys_are_limited_independently(repository.as_ref()).await;
+    capacity_recovers_over_time(repository.as_ref()).await;
+    purge_removes_only_full_buckets(repository.as_ref()).await;
+    concurrent_requests_admit_only_burst(Arc::clone(&repository)).await;
+}
+
+pub async fn run_password_reset_repository_conformance(repository: Arc<dyn PasswordResetTokenRepository>) {
+    reset_token_create_then_find(repository.as_ref()).await;
+    reset_token_create_rejects_duplicate_hash(repository.as_ref()).await;
+    reset_token_is_consumed_once(repository.as_ref()).await;
+    expired_reset_token_is_not_consumed(repository.as_ref()).await;
+    delete_for_user_removes_only_their_tokens(repository.as_ref()).await;
+    purge_removes_only_expired_reset_tokens(repository.as_ref()).await;
+    concurrent_consumes_of_same_token_admit_one(Arc::clone(&repository)).await;
+}
+
+pub async fn run_email_verification_repository_conformance(repository: Arc<dyn EmailVerificationTokenRepository>) {
+    verification_token_create_then_find(repository.as_ref()).await;
+    verification_token_is_consumed_once(repository.as_ref()).await;
+    delete_for_user_removes_only_their_verification_tokens(repository.as_ref()).await;
+    purge_removes_only_expired_verification_tokens(repository.as_ref()).await;
+}
+
+pub async fn run_mfa_repository_conformance(repository: Arc<dyn MfaRepository>) {
+    enrollment_save_then_find(repository.as_ref()).await;
+    record_step_rejects_replayed_steps(repository.as_ref()).await;
+    recovery_codes_are_consumed_once(repository.as_ref()).await;
+    delete_removes_enrollment_and_recovery_codes(repository.as_ref()).await;
+    concurrent_records_of_same_step_admit_one(Arc::clone(&repository)).await;
+}
+
+/// A user with unique username and email.
+///
+/// Timestamps are truncated to microseconds, the precision backends are required to keep.
+pub fn sample_user(role: RoleName) -> User {
+    let suffix = Uuid::new_v4().simple().to_string();
+    let mut user = User::new(
+        format!("user_{}", suffix),
+        format!("{}@example.com", suffix),
+        "hash".to_string(),
+        Role::new(role),
+    );
+    user.created_at = user.created_at.trunc_subsecs(6);
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    user.password_changed_at = Some(user.created_at);
+    user
+}
+
+async fn create_then_find(repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::Manager);
+    repository.create(&user).await.expect("create failed");
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), Some(user.clone()));
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), Some(user.clone()));
+    assert_eq!(repository.find_by_email(&user.email).await.unwrap(), Some(user));
+}
+
+async fn find_missing_returns_none(repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), None);
+    assert_eq!(repository.find_by_email(&user.email).await.unwrap(), None);
+}
+
+async fn create_rejects_duplicate_username(repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    let mut duplicate = sample_user(RoleName::User);
+    duplicate.username = user.username.clone();
+
+    assert_validation_error(repository.create(&duplicate).await, "duplicate username on create");
+    assert_eq!(repository.find_by_id(&duplicate.id).await.unwrap(), None);
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), Some(user));
+}
+
+async fn create_rejects_duplicate_email(repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    let mut duplicate = sample_user(RoleName::User);
+    duplicate.email = user.email.clone();
+
+    assert_validation_error(repository.create(&duplicate).await, "duplicate email on create");
+    assert_eq!(repository.find_by_id(&duplicate.id).await.unwrap(), None);
+    assert_eq!(repository.find_by_email(&user.email).await.unwrap(), Some(user));
+}
+
+async fn update_persists_changes(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    let renamed = sample_user(RoleName::Admin);
+    user.username = renamed.username;
+    user.email = renamed.email;
+    user.password_hash = "new_hash".to_string();
+    user.role = Role::new(RoleName::Admin);
+    user.touch();
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    repository.update(&user).await.expect("update failed");
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), Some(user.clone()));
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), Some(user));
+}
+
+async fn update_keeps_own_username_and_email(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    user.password_hash = "new_hash".to_string();
+    user.touch();
+    repository
+        .update(&user)
+        .await
+        .expect("updating a user without renaming it must succeed");
+}
+
+async fn update_rejects_taken_username(repository: &dyn UserRepository) {
+    let first = sample_user(RoleName::User);
+    let second = sample_user(RoleName::User);
+    repository.create(&first).await.unwrap();
+    repository.create(&second).await.unwrap();
+
+    let mut renamed = second.clone();
+    renamed.username = first.username.clone();
+    renamed.touch();
+
+    assert_validation_error(repository.update(&renamed).await, "taken username on update");
+    assert_eq!(repository.find_by_id(&second.id).await.unwrap(), Some(second));
+}
+
+async fn update_rejects_taken_email(repository: &dyn UserRepository) {
+    let first = sample_user(RoleName::User);
+    let second = sample_user(RoleName::User);
+    repository.create(&first).await.unwrap();
+    repository.create(&second).await.unwrap();
+
+    let mut renamed = second.clone();
+    renamed.email = first.email.clone();
+    renamed.touch();
+
+    assert_validation_error(repository.update(&renamed).await, "taken email on update");
+    assert_eq!(repository.find_by_id(&second.id).await.unwrap(), Some(second));
+}
+
+async fn update_with_stale_version_conflicts(repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    // Two writers both start from the same version
+    let mut first = user.clone();
+    first.password_hash = "first".to_string();
+    first.touch();
+    let mut second = user.clone();
+    second.password_hash = "second".to_string();
+    second.touch();
+
+    repository.update(&first).await.expect("first update failed");
+    assert!(
+        matches!(repository.update(&second).await, Err(DomainError::Conflict(_))),
+        "an update based on a stale version must return Conflict"
+    );
+
+    let stored = repository.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(stored.password_hash, "first");
+    assert_eq!(stored.version, user.version + 1);
+}
+
+async fn update_missing_is_not_found(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    user.touch();
+
+    assert!(
+        matches!(repository.update(&user).await, Err(DomainError::NotFound(_))),
+        "updating a missing user must return NotFound"
+    );
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+}
+
+async fn delete_removes_user(repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    repository.delete(&user.id).await.expect("delete failed");
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), None);
+
+    // The username and email are free again
+    let mut reused = sample_user(RoleName::User);
+    reused.username = user.username;
+    reused.email = user.email;
+    repository
+        .create(&reused)
+        .await
+        .expect("a deleted user's username and email must be reusable");
+}
+
+async fn delete_missing_is_not_found(repository: &dyn UserRepository) {
+    assert!(
+        matches!(repository.delete(&Uuid::new_v4()).await, Err(DomainError::NotFound(_))),
+        "deleting a missing user must return NotFound"
+    );
+}
+
+async fn status_change_persists(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    user.change_status(UserStatus::Suspended, Some("chargeback".to_string()))
+        .unwrap();
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    user.status_changed_at = Some(user.updated_at);
+    repository.update(&user).await.expect("status change failed");
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), Some(user));
+}
+
+async fn password_change_persists(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    user.set_password("second hash".to_string(), 2);
+    user.set_password("third hash".to_string(), 2);
+    user.revoke_tokens();
+    user.touch();
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    user.password_changed_at = Some(user.updated_at);
+    repository.update(&user).await.expect("password change failed");
+
+    let stored = repository.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(stored.password_history, ["second hash", "hash"]);
+    assert_eq!(stored, user);
+}
+
+async fn email_verification_persists(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+    assert!(!repository.find_by_id(&user.id).await.unwrap().unwrap().is_email_verified());
+
+    let email = format!("{}@example.org", Uuid::new_v4().simple());
+    user.verify_email(email.clone());
+    user.email_verified_at = user.email_verified_at.map(|at| at.trunc_subsecs(6));
+    user.touch();
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    repository.update(&user).await.expect("email verification failed");
+
+    let stored = repository.find_by_email(&email).await.unwrap().unwrap();
+    assert!(stored.is_email_verified());
+    assert_eq!(stored, user);
+}
+
+/// Stores `user` soft-deleted
+async fn soft_delete(repository: &dyn UserRepository, user: &mut User) {
+    user.soft_delete();
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    user.deleted_at = Some(user.updated_at);
+    repository.update(user).await.expect("soft delete failed");
+}
+
+async fn soft_deleted_user_is_hidden(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+    soft_delete(repository, &mut user).await;
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), None);
+    assert_eq!(repository.find_by_email(&user.email).await.unwrap(), None);
+    assert!(!repository.find_all().await.unwrap().iter().any(|found| found.id == user.id));
+
+    assert_eq!(
+        repository.find_by_id_including_deleted(&user.id).await.unwrap(),
+        Some(user.clone())
+    );
+    assert!(repository
+        .find_all_including_deleted()
+        .await
+        .unwrap()
+        .contains(&user));
+}
+
+async fn soft_deleted_user_keeps_username_and_email(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+    soft_delete(repository, &mut user).await;
+
+    let mut same_username = sample_user(RoleName::User);
+    same_username.username = user.username.clone();
+    assert_validation_error(
+        repository.create(&same_username).await,
+        "username of a soft-deleted user",
+    );
+
+    let mut same_email = sample_user(RoleName::User);
+    same_email.email = user.email.clone();
+    assert_validation_error(repository.create(&same_email).await, "email of a soft-deleted user");
+}
+
+async fn restored_user_is_visible_again(repository: &dyn UserRepository) {
+    let mut user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+    soft_delete(repository, &mut user).await;
+
+    user.restore();
+    user.updated_at = user.updated_at.trunc_subsecs(6);
+    repository.update(&user).await.expect("restore failed");
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), Some(user.clone()));
+    assert_eq!(repository.find_by_username(&user.username).await.unwrap(), Some(user));
+}
+
+async fn purge_removes_users_deleted_before_cutoff(repository: &dyn UserRepository) {
+    let mut old = sample_user(RoleName::User);
+    let mut recent = sample_user(RoleName::User);
+    let active = sample_user(RoleName::User);
+    for user in [&old, &recent, &active] {
+        repository.create(user).await.unwrap();
+    }
+    soft_delete(repository, &mut old).await;
+
+    let cutoff = Utc::now();
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    soft_delete(repository, &mut recent).await;
+
+    assert!(repository.purge_deleted(cutoff).await.unwrap().contains(&old.id));
+
+    assert_eq!(repository.find_by_id_including_deleted(&old.id).await.unwrap(), None);
+    assert_eq!(
+        repository.find_by_id_including_deleted(&recent.id).await.unwrap(),
+        Some(recent)
+    );
+    assert_eq!(repository.find_by_id(&active.id).await.unwrap(), Some(active));
+
+    // Purging releases the username and email
+    let mut reused = sample_user(RoleName::User);
+    reused.username = old.username;
+    reused.email = old.email;
+    repository
+        .create(&reused)
+        .await
+        .expect("a purged user's username and email must be reusable");
+}
+
+async fn find_all_includes_created_users(repository: &dyn UserRepository) {
+    let first = sample_user(RoleName::User);
+    let second = sample_user(RoleName::Guest);
+    repository.create(&first).await.unwrap();
+    repository.create(&second).await.unwrap();
+
+    let all = repository.find_all().await.unwrap();
+
+    assert!(all.contains(&first));
+    assert!(all.contains(&second));
+}
+
+async fn concurrent_creates_of_distinct_users_succeed(repository: Arc<dyn UserRepository>) {
+    let users: Vec<User> = (0..CONCURRENT_WRITERS)
+        .map(|_| sample_user(RoleName::User))
+        .collect();
+
+    let tasks: Vec<_> = users
+        .iter()
+        .cloned()
+        .map(|user| {
+            let repository = Arc::clone(&repository);
+            tokio::spawn(async move { repository.create(&user).await })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap().expect("concurrent create of a distinct user failed");
+    }
+
+    for user in users {
+        assert_eq!(repository.find_by_id(&user.id).await.unwrap(), Some(user));
+    }
+}
+
+async fn concurrent_creates_of_same_username_admit_one(repository: Arc<dyn UserRepository>) {
+    let username = sample_user(RoleName::User).username;
+
+    let tasks: Vec<_> = (0..CONCURRENT_WRITERS)
+        .map(|_| {
+            let mut user = sample_user(RoleName::User);
+            user.username = username.clone();
+            let repository = Arc::clone(&repository);
+            tokio::spawn(async move { repository.create(&user).await })
+        })
+        .collect();
+
+    let mut created = 0;
+    for task in tasks {
+        match task.await.unwrap() {
+            Ok(()) => created += 1,
+            result => assert_validation_error(result, "concurrent duplicate username"),
+        }
+    }
+
+    assert_eq!(created, 1, "exactly one concurrent create must win");
+    assert!(repository.find_by_username(&username).await.unwrap().is_some());
+}
+
+async fn committed_writes_become_visible(unit_of_work: &dyn UnitOfWork, repository: &dyn UserRepository) {
+    let mut existing = sample_user(RoleName::User);
+    repository.create(&existing).await.unwrap();
+    // Lets caching repositories remember the old state
+    repository.find_by_id(&existing.id).await.unwrap();
+
+    let created = sample_user(RoleName::User);
+    existing.username = sample_user(RoleName::User).username;
+    existing.touch();
+    existing.updated_at = existing.updated_at.trunc_subsecs(6);
+
+    let transaction = unit_of_work.begin().await.unwrap();
+    transaction.users().create(&created).await.unwrap();
+    transaction.users().update(&existing).await.unwrap();
+    transaction.commit().await.expect("commit failed");
+
+    assert_eq!(repository.find_by_id(&created.id).await.unwrap(), Some(created));
+    assert_eq!(repository.find_by_id(&existing.id).await.unwrap(), Some(existing.clone()));
+    assert_eq!(repository.find_by_username(&existing.username).await.unwrap(), Some(existing));
+}
+
+async fn uncommitted_writes_are_isolated(unit_of_work: &dyn UnitOfWork, repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+
+    let transaction = unit_of_work.begin().await.unwrap();
+    transaction.users().create(&user).await.unwrap();
+
+    assert_eq!(transaction.users().find_by_id(&user.id).await.unwrap(), Some(user.clone()));
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+
+    transaction.rollback().await.expect("rollback failed");
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+}
+
+async fn dropped_transaction_rolls_back(unit_of_work: &dyn UnitOfWork, repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+
+    let transaction = unit_of_work.begin().await.unwrap();
+    transaction.users().create(&user).await.unwrap();
+    drop(transaction);
+
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+}
+
+async fn transaction_sees_its_own_delete(unit_of_work: &dyn UnitOfWork, repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    let transaction = unit_of_work.begin().await.unwrap();
+    transaction.users().delete(&user.id).await.unwrap();
+
+    assert_eq!(transaction.users().find_by_id(&user.id).await.unwrap(), None);
+    assert_eq!(transaction.users().find_by_username(&user.username).await.unwrap(), None);
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), Some(user.clone()));
+
+    transaction.commit().await.expect("commit failed");
+    assert_eq!(repository.find_by_id(&user.id).await.unwrap(), None);
+}
+
+async fn transaction_rejects_duplicate_username(unit_of_work: &dyn UnitOfWork, repository: &dyn UserRepository) {
+    let user = sample_user(RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    let mut duplicate = sample_user(RoleName::User);
+    duplicate.username = user.username.clone();
+
+    let transaction = unit_of_work.begin().await.unwrap();
+    assert_validation_error(
+        transaction.users().create(&duplicate).await,
+        "duplicate username in transaction",
+    );
+    transaction.rollback().await.expect("rollback failed");
+
+    assert_eq!(repository.find_by_id(&duplicate.id).await.unwrap(), None);
+}
+
+fn assert_validation_error(result: Result<(), DomainError>, case: &str) {
+    assert!(
+        matches!(result, Err(DomainError::ValidationError(_))),
+        "{}: expected ValidationError, got {:?}",
+        case,
+        result
+    );
+}
+
+fn sample_record(ttl: Duration) -> IdempotencyRecord {
+    IdempotencyRecord::new(Uuid::new_v4().to_string(), "fingerprint".to_string(), ttl)
+}
+
+fn sample_response() -> StoredResponse {
+    StoredResponse {
+        status: 201,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: br#"{"id":1}"#.to_vec(),
+    }
+}
+
+fn long_ago() -> chrono::DateTime<Utc> {
+    Utc::now() - Duration::days(1)
+}
+
+async fn first_request_claims_key(repository: &dyn IdempotencyRepository) {
+    let record = sample_record(Duration::hours(1));
+    assert_eq!(repository.try_begin(&record, long_ago()).await.unwrap(), None);
+
+    let mut retry = record.clone();
+    retry.fingerprint = "other".to_string();
+    let existing = repository
+        .try_begin(&retry, long_ago())
+        .await
+        .unwrap()
+        .expect("key claimed twice");
+    assert_eq!(existing.fingerprint, record.fingerprint);
+    assert_eq!(existing.response, None);
+}
+
+async fn completed_request_is_returned_with_response(repository: &dyn IdempotencyRepository) {
+    let record = sample_record(Duration::hours(1));
+    repository.try_begin(&record, long_ago()).await.unwrap();
+    repository.complete(&record.key, &sample_response()).await.unwrap();
+
+    let existing = repository.try_begin(&record, Utc::now()).await.unwrap();
+    assert_eq!(existing.and_then(|existing| existing.response), Some(sample_response()));
+}
+
+async fn removed_key_can_be_claimed_again(repository: &dyn IdempotencyRepository) {
+    let record = sample_record(Duration::hours(1));
+    repository.try_begin(&record, long_ago()).await.unwrap();
+    repository.remove(&record.key).await.unwrap();
+
+    assert_eq!(repository.try_begin(&record, long_ago()).await.unwrap(), None);
+}
+
+async fn abandoned_key_can_be_claimed_again(repository: &dyn IdempotencyRepository) {
+    let record = sample_record(Duration::hours(1));
+    repository.try_begin(&record, long_ago()).await.unwrap();
+
+    let retry = IdempotencyRecord {
+        key: record.key.clone(),
+        ..sample_record(Duration::hours(1))
+    };
+    let abandoned_before = retry.created_at + Duration::seconds(1);
+    assert_eq!(repository.try_begin(&retry, abandoned_before).await.unwrap(), None);
+}
+
+async fn expired_key_can_be_claimed_again(repository: &dyn IdempotencyRepository) {
+    let record = sample_record(Duration::seconds(-1));
+    repository.try_begin(&record, long_ago()).await.unwrap();
+    repository.complete(&record.key, &sample_response()).await.unwrap();
+
+    let retry = IdempotencyRecord {
+        key: record.key.clone(),
+        ..sample_record(Duration::hours(1))
+    };
+    assert_eq!(repository.try_begin(&retry, long_ago()).await.unwrap(), None);
+}
+
+async fn purge_removes_only_expired_keys(repository: &dyn IdempotencyRepository) {
+    let expired = sample_record(Duration::seconds(-1));
+    let live = sample_record(Duration::hours(1));
+    repository.try_begin(&expired, long_ago()).await.unwrap();
+    repository.try_begin(&live, long_ago()).await.unwrap();
+
+    assert!(repository.purge_expired().await.unwrap() >= 1);
+
+    assert!(repository.try_begin(&live, long_ago()).await.unwrap().is_some());
+    let retry = IdempotencyRecord {
+        key: expired.key.clone(),
+        ..sample_record(Duration::hours(1))
+    };
+    assert_eq!(repository.try_begin(&retry, long_ago()).await.unwrap(), None);
+}
+
+async fn concurrent_claims_of_same_key_admit_one(repository: Arc<dyn IdempotencyRepository>) {
+    let record = sample_record(Duration::hours(1));
+
+    let handles: Vec<_> = (0..CONCURRENT_WRITERS)
+        .map(|_| {
+            let repository = Arc::clone(&repository);
+            let record = record.clone();
+            tokio::spawn(async move { repository.try_begin(&record, long_ago()).await })
+        })
+        .collect();
+
+    let mut claimed = 0;
+    for handle in handles {
+        if handle.await.unwrap().expect("claim failed").is_none() {
+            claimed += 1;
+        }
+    }
+    assert_eq!(claimed, 1, "exactly one request may claim a key");
+}
+
+/// An account key no other case uses
+fn sample_login_key() -> LoginAttemptKey {
+    LoginAttemptKey::account(&format!("user_{}", Uuid::new_v4().simple()))
+}
+
+fn now_micros() -> chrono::DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+async fn failures_accumulate(repository: &dyn LoginAttemptRepository) {
+    let key = sample_login_key();
+    let first = now_micros() - Duration::minutes(2);
+    let last = now_micros();
+
+    repository.record_failure(&key, first, long_ago()).await.unwrap();
+    repository.record_failure(&key, first + Duration::minutes(1), long_ago()).await.unwrap();
+    let failed = repository.record_failure(&key, last, long_ago()).await.unwrap();
+
+    assert_eq!(failed.failures, 3);
+    assert_eq!(failed.first_failed_at, first);
+    assert_eq!(failed.last_failed_at, last);
+    assert_eq!(repository.find(&key).await.unwrap(), Some(failed));
+}
+
+async fn stale_failures_start_over(repository: &dyn LoginAttemptRepository) {
+    let key = sample_login_key();
+    let stale = now_micros() - Duration::hours(2);
+    repository.record_failure(&key, stale, long_ago()).await.unwrap();
+    repository.record_failure(&key, stale, long_ago()).await.unwrap();
+
+    let now = now_micros();
+    let failed = repository
+        .record_failure(&key, now, now - Duration::hours(1))
+        .await
+        .unwrap();
+
+    assert_eq!(failed.failures, 1);
+    assert_eq!(failed.first_failed_at, now);
+}
+
+async fn reset_clears_failures(repository: &dyn LoginAttemptRepository) {
+    let key = sample_login_key();
+    repository.record_failure(&key, now_micros(), long_ago()).await.unwrap();
+
+    repository.reset(&key).await.unwrap();
+    assert_eq!(repository.find(&key).await.unwrap(), None);
+
+    // Resetting a key without failures is not an error
+    repository.reset(&key).await.unwrap();
+}
+
+async fn find_with_failures_filters_and_orders(repository: &dyn LoginAttemptRepository) {
+    let now = now_micros();
+    let older = sample_login_key();
+    let newer = sample_login_key();
+    let below_minimum = sample_login_key();
+    let stale = sample_login_key();
+    let ip = LoginAttemptKey::ip(std::net::Ipv6Addr::from(Uuid::new_v4().as_u128()).into());
+
+    for _ in 0..3 {
+        repository.record_failure(&older, now - Duration::minutes(2), long_ago()).await.unwrap();
+        repository.record_failure(&newer, now - Duration::minutes(1), long_ago()).await.unwrap();
+        repository.record_failure(&stale, now - Duration::hours(2), long_ago()).await.unwrap();
+        repository.record_failure(&ip, now, long_ago()).await.unwrap();
+    }
+    repository.record_failure(&below_minimum, now, long_ago()).await.unwrap();
+
+    let found = repository
+        .find_with_failures(LoginAttemptScope::Account, 3, now - Duration::hours(1))
+        .await
+        .unwrap();
+    let ours: Vec<_> = found
+        .iter()
+        .map(|failed| &failed.key)
+        .filter(|key| [&older, &newer, &below_minimum, &stale, &ip].contains(key))
+        .collect();
+
+    assert_eq!(ours, vec![&newer, &older]);
+}
+
+async fn purge_removes_only_stale_failures(repository: &dyn LoginAttemptRepository) {
+    let now = now_micros();
+    let stale = sample_login_key();
+    let live = sample_login_key();
+    repository.record_failure(&stale, now - Duration::hours(2), long_ago()).await.unwrap();
+    repository.record_failure(&live, now, long_ago()).await.unwrap();
+
+    assert!(repository.purge_stale(now - Duration::hours(1)).await.unwrap() >= 1);
+
+    assert_eq!(repository.find(&stale).await.unwrap(), None);
+    assert!(repository.find(&live).await.unwrap().is_some());
+}
+
+async fn concurrent_failures_are_all_counted(repository: Arc<dyn LoginAttemptRepository>) {
+    let key = sample_login_key();
+
+    let handles: Vec<_> = (0..CONCURRENT_WRITERS)
+        .map(|_| {
+            let repository = Arc::clone(&repository);
+            let key = key.clone();
+            tokio::spawn(async move { repository.record_failure(&key, Utc::now(), long_ago()).await })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.await.unwrap().expect("recording a failure failed");
+    }
+
+    let failed = repository.find(&key).await.unwrap().expect("failures not recorded");
+    assert_eq!(failed.failures, CONCURRENT_WRITERS as u32);
+}
+
+/// Three requests per minute, all of which may be made at once
+fn sample_quota() -> RateLimitQuota {
+    RateLimitQuota {
+        limit: 3,
+        period: Duration::minutes(1),
+        burst: 3,
+    }
+}
+
+fn sample_rate_limit_key() -> String {
+    format!("conformance:{}", Uuid::new_v4())
+}
+
+async fn burst_is_allowed_then_refused(repository: &dyn RateLimitRepository) {
+    let key = sample_rate_limit_key();
+    let now = now_micros();
+
+    for remaining in [2, 1, 0] {
+        let decision = repository.acquire(&key, &sample_quota(), now).await.unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, remaining);
+    }
+
+    let decision = repository.acquire(&key, &sample_quota(), now).await.unwrap();
+    assert!(!decision.allowed);
+    assert_eq!(decision.remaining, 0);
+    assert_eq!(decision.retry_after, Some(Duration::seconds(20)));
+    assert_eq!(decision.reset_after, Duration::minutes(1));
+}
+
+async fn keys_are_limited_independently(repository: &dyn RateLimitRepository) {
+    let exhausted = sample_rate_limit_key();
+    let now = now_micros();
+    for _ in 0..3 {
+        repository.acquire(&exhausted, &sample_quota(), now).await.unwrap();
+    }
+
+    let decision = repository
+        .acquire(&sample_rate_limit_key(), &sample_quota(), now)
+        .await
+        .unwrap();
+    assert!(decision.allowed);
+}
+
+async fn capacity_recovers_over_time(repository: &dyn RateLimitRepository) {
+    let key = sample_rate_limit_key();
+    let now = now_micros();
+    for _ in 0..3 {
+        repository.acquire(&key, &sample_quota(), now).await.unwrap();
+    }
+
+    let decision = repository
+        .acquire(&key, &sample_quota(), now + Duration::seconds(20))
+        .await
+        .unwrap();
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+}
+
+async fn purge_removes_only_full_buckets(repository: &dyn RateLimitRepository) {
+    let now = now_micros();
+    let full = sample_rate_limit_key();
+    let draining = sample_rate_limit_key();
+    repository.acquire(&full, &sample_quota(), now - Duration::hours(1)).await.unwrap();
+    for _ in 0..3 {
+        repository.acquire(&draining, &sample_quota(), now).await.unwrap();
+    }
+
+    assert!(repository.purge_expired(now).await.unwrap() >= 1);
+
+    // The purged key starts over, the other one is still exhausted
+    let decision = repository.acquire(&draining, &sample_quota(), now).await.unwrap();
+    assert!(!decision.allowed);
+    let decision = repository.acquire(&full, &sample_quota(), now).await.unwrap();
+    assert_eq!(decision.remaining, 2);
+}
+
+async fn concurrent_requests_admit_only_burst(repository: Arc<dyn RateLimitRepository>) {
+    let key = sample_rate_limit_key();
+    let now = now_micros();
+
+    let handles: Vec<_> = (0..CONCURRENT_WRITERS)
+        .map(|_| {
+            let repository = Arc::clone(&repository);
+            let key = key.clone();
+            tokio::spawn(async move { repository.acquire(&key, &sample_quota(), now).await })
+        })
+        .collect();
+
+    let mut allowed = 0;
+    for handle in handles {
+        if handle.await.unwrap().expect("acquire failed").allowed {
+            allowed += 1;
+        }
+    }
+    assert_eq!(allowed, 3, "only the burst may pass");
+}
+
+/// A token with a unique hash, for a user no other case uses
+fn sample_reset_token(ttl: Duration) -> PasswordResetToken {
+    let mut token = PasswordResetToken::new(Uuid::new_v4().simple().to_string(), Uuid::new_v4(), ttl);
+    token.created_at = token.created_at.trunc_subsecs(6);
+    token.expires_at = token.expires_at.trunc_subsecs(6);
+    token
+}
+
+async fn reset_token_create_then_find(repository: &dyn PasswordResetTokenRepository) {
+    let token = sample_reset_token(Duration::minutes(15));
+    repository.create(&token).await.unwrap();
+
+    assert_eq!(repository.find(&token.token_hash).await.unwrap(), Some(token));
+    assert_eq!(repository.find(&Uuid::new_v4().simple().to_string()).await.unwrap(), None);
+}
+
+async fn reset_token_create_rejects_duplicate_hash(repository: &dyn PasswordResetTokenRepository) {
+    let token = sample_reset_token(Duration::minutes(15));
+    repository.create(&token).await.unwrap();
+
+    let duplicate = PasswordResetToken {
+        user_id: Uuid::new_v4(),
+        ..token.clone()
+    };
+    assert!(matches!(repository.create(&duplicate).await, Err(DomainError::Conflict(_))));
+    assert_eq!(repository.find(&token.token_hash).await.unwrap(), Some(token));
+}
+
+async fn reset_token_is_consumed_once(repository: &dyn PasswordResetTokenRepository) {
+    let token = sample_reset_token(Duration::minutes(15));
+    repository.create(&token).await.unwrap();
+    let now = now_micros();
+
+    let consumed = repository
+        .consume(&token.token_hash, now)
+        .await
+        .unwrap()
+        .expect("usable token was not consumed");
+    assert_eq!(consumed.user_id, token.user_id);
+    assert_eq!(consumed.used_at, Some(now));
+
+    assert_eq!(repository.consume(&token.token_hash, now).await.unwrap(), None);
+    let stored = repository.find(&token.token_hash).await.unwrap().unwrap();
+    assert!(!stored.is_usable(now));
+}
+
+async fn expired_reset_token_is_not_consumed(repository: &dyn PasswordResetTokenRepository) {
+    let token = sample_reset_token(Duration::seconds(-1));
+    repository.create(&token).await.unwrap();
+
+    assert_eq!(repository.consume(&token.token_hash, now_micros()).await.unwrap(), None);
+    assert_eq!(repository.find(&token.token_hash).await.unwrap().unwrap().used_at, None);
+}
+
+async fn delete_for_user_removes_only_their_tokens(repository: &dyn PasswordResetTokenRepository) {
+    let first = sample_reset_token(Duration::minutes(15));
+    let second = PasswordResetToken {
+        token_hash: Uuid::new_v4().simple().to_string(),
+        ..first.clone()
+    };
+    let other = sample_reset_token(Duration::minutes(15));
+    for token in [&first, &second, &other] {
+        repository.create(token).await.unwrap();
+    }
+
+    assert_eq!(repository.delete_for_user(&first.user_id).await.unwrap(), 2);
+    assert_eq!(repository.find(&first.token_hash).await.unwrap(), None);
+    assert_eq!(repository.find(&second.token_hash).await.unwrap(), None);
+    assert!(repository.find(&other.token_hash).await.unwrap().is_some());
+}
+
+async fn purge_removes_only_expired_reset_tokens(repository: &dyn PasswordResetTokenRepository) {
+    let expired = sample_reset_token(Duration::seconds(-1));
+    let live = sample_reset_token(Duration::minutes(15));
+    repository.create(&expired).await.unwrap();
+    repository.create(&live).await.unwrap();
+
+    assert!(repository.purge_expired(now_micros()).await.unwrap() >= 1);
+
+    assert_eq!(repository.find(&expired.token_hash).await.unwrap(), None);
+    assert!(repository.find(&live.token_hash).await.unwrap().is_some());
+}
+
+async fn concurrent_consumes_of_same_token_admit_one(repository: Arc<dyn PasswordResetTokenRepository>) {
+    let token = sample_reset_token(Duration::minutes(15));
+    repository.create(&token).await.unwrap();
+    let now = now_micros();
+
+    let handles: Vec<_> = (0..CONCURRENT_WRITERS)
+        .map(|_| {
+            let repository = Arc::clone(&repository);
+            let token_hash = token.token_hash.clone();
+            tokio::spawn(async move { repository.consume(&token_hash, now).await })
+        })
+        .collect();
+
+    let mut consumed = 0;
+    for handle in handles {
+        if handle.await.unwrap().expect("consume failed").is_some() {
+            consumed += 1;
+        }
+    }
+    assert_eq!(consumed, 1, "a token may only be redeemed once");
+}
+
+fn sample_verification_token(ttl: Duration) -> EmailVerificationToken {
+    let mut token = EmailVerificationToken::new(
+        Uuid::new_v4().simple().to_string(),
+        Uuid::new_v4(),
+        format!("{}@example.com", Uuid::new_v4().simple()),
+        ttl,
+    );
+    token.created_at = token.created_at.trunc_subsecs(6);
+    token.expires_at = token.expires_at.trunc_subsecs(6);
+    token
+}
+
+async fn verification_token_create_then_find(repository: &dyn EmailVerificationTokenRepository) {
+    let token = sample_verification_token(Duration::hours(24));
+    repository.create(&token).await.unwrap();
+
+    assert_eq!(repository.find(&token.token_hash).await.unwrap(), Some(token.clone()));
+    assert!(matches!(repository.create(&token).await, Err(DomainError::Conflict(_))));
+    assert_eq!(repository.find(&Uuid::new_v4().simple().to_string()).await.unwrap(), None);
+}
+
+async fn verification_token_is_consumed_once(repository: &dyn EmailVerificationTokenRepository) {
+    let token = sample_verification_token(Duration::hours(24));
+    let expired = sample_verification_token(Duration::seconds(-1));
+    repository.create(&token).await.unwrap();
+    repository.create(&expired).await.unwrap();
+    let now = now_micros();
+
+    let consumed = repository
+        .consume(&token.token_hash, now)
+        .await
+        .unwrap()
+        .expect("usable token was not consumed");
+    assert_eq!(consumed.email, token.email);
+    assert_eq!(consumed.used_at, Some(now));
+
+    assert_eq!(repository.consume(&token.token_hash, now).await.unwrap(), None);
+    assert_eq!(repository.consume(&expired.token_hash, now).await.unwrap(), None);
+}
+
+async fn delete_for_user_removes_only_their_verification_tokens(repository: &dyn EmailVerificationTokenRepository) {
+    let first = sample_verification_token(Duration::hours(24));
+    let second = EmailVerificationToken {
+        token_hash: Uuid::new_v4().simple().to_string(),
+        ..first.clone()
+    };
+    let other = sample_verification_token(Duration::hours(24));
+    for token in [&first, &second, &other] {
+        repository.create(token).await.unwrap();
+    }
+
+    assert_eq!(repository.delete_for_user(&first.user_id).await.unwrap(), 2);
+    assert_eq!(repository.find(&second.token_hash).await.unwrap(), None);
+    assert!(repository.find(&other.token_hash).await.unwrap().is_some());
+}
+
+async fn purge_removes_only_expired_verification_tokens(repository: &dyn EmailVerificationTokenRepository) {
+    let expired = sample_verification_token(Duration::seconds(-1));
+    let live = sample_verification_token(Duration::hours(24));
+    repository.create(&expired).await.unwrap();
+    repository.create(&live).await.unwrap();
+
+    assert!(repository.purge_expired(now_micros()).await.unwrap() >= 1);
+
+    assert_eq!(repository.find(&expired.token_hash).await.unwrap(), None);
+    assert!(repository.find(&live.token_hash).await.unwrap().is_some());
+}
+
+fn sample_enrollment() -> TotpEnrollment {
+    let mut enrollment = TotpEnrollment::new(Uuid::new_v4(), "JBSWY3DPEHPK3PXP".to_string());
+    enrollment.created_at = enrollment.created_at.trunc_subsecs(6);
+    enrollment
+}
+
+async fn enrollment_save_then_find(repository: &dyn MfaRepository) {
+    let mut enrollment = sample_enrollment();
+    repository.save(&enrollment).await.unwrap();
+    assert_eq!(repository.find(&enrollment.user_id).await.unwrap(), Some(enrollment.clone()));
+    assert_eq!(repository.find(&Uuid::new_v4()).await.unwrap(), None);
+
+    enrollment.secret = "KRSXG5CTMVRXEZLU".to_string();
+    enrollment.confirmed_at = Some(now_micros());
+    enrollment.last_used_step = Some(42);
+    repository.save(&enrollment).await.unwrap();
+    assert_eq!(repository.find(&enrollment.user_id).await.unwrap(), Some(enrollment));
+}
+
+async fn record_step_rejects_replayed_steps(repository: &dyn MfaRepository) {
+    let enrollment = sample_enrollment();
+    repository.save(&enrollment).await.unwrap();
+
+    assert!(repository.record_step(&enrollment.user_id, 100).await.unwrap());
+    assert!(!repository.record_step(&enrollment.user_id, 100).await.unwrap());
+    assert!(!repository.record_step(&enrollment.user_id, 99).await.unwrap());
+    assert!(repository.record_step(&enrollment.user_id, 101).await.unwrap());
+    assert!(!repository.record_step(&Uuid::new_v4(), 100).await.unwrap());
+
+    let stored = repository.find(&enrollment.user_id).await.unwrap().unwrap();
+    assert_eq!(stored.last_used_step, Some(101));
+}
+
+async fn recovery_codes_are_consumed_once(repository: &dyn MfaRepository) {
+    let user_id = Uuid::new_v4();
+    let other = Uuid::new_v4();
+    let codes = ["first".to_string(), "second".to_string()];
+    repository.replace_recovery_codes(&user_id, &codes).await.unwrap();
+    repository.replace_recovery_codes(&other, &codes[..1]).await.unwrap();
+    assert_eq!(repository.count_recovery_codes(&user_id).await.unwrap(), 2);
+
+    assert!(repository.consume_recovery_code(&user_id, "first").await.unwrap());
+    assert!(!repository.consume_recovery_code(&user_id, "first").await.unwrap());
+    assert!(!repository.consume_recovery_code(&user_id, "unknown").await.unwrap());
+    assert_eq!(repository.count_recovery_codes(&user_id).await.unwrap(), 1);
+    assert_eq!(repository.count_recovery_codes(&other).await.unwrap(), 1);
+
+    repository.replace_recovery_codes(&user_id, &["third".to_string()]).await.unwrap();
+    assert!(!repository.consume_recovery_code(&user_id, "second").await.unwrap());
+    assert!(repository.consume_recovery_code(&user_id, "third").await.unwrap());
+}
+
+async fn delete_removes_enrollment_and_recovery_codes(repository: &dyn MfaRepository) {
+    let enrollment = sample_enrollment();
+    repository.save(&enrollment).await.unwrap();
+    repository
+        .replace_recovery_codes(&enrollment.user_id, &["code".to_string()])
+        .await
+        .unwrap();
+
+    assert!(repository.delete(&enrollment.user_id).await.unwrap());
+    assert_eq!(repository.find(&enrollment.user_id).await.unwrap(), None);
+    assert_eq!(repository.count_recovery_codes(&enrollment.user_id).await.unwrap(), 0);
+    assert!(!repository.delete(&enrollment.user_id).await.unwrap());
+}
+
+async fn concurrent_records_of_same_step_admit_one(repository: Arc<dyn MfaRepository>) {
+    let enrollment = sample_enrollment();
+    repository.save(&enrollment).await.unwrap();
+
+    let handles: Vec<_> = (0..CONCURRENT_WRITERS)
+        .map(|_| {
+            let repository = Arc::clone(&repository);
+            let user_id = enrollment.user_id;
+            tokio::spawn(async move { repository.record_step(&user_id, 7).await })
+        })
+        .collect();
+
+    let mut recorded = 0;
+    for handle in handles {
+        if handle.await.unwrap().expect("record_step failed") {
+            recorded += 1;
+        }
+    }
+    assert_eq!(recorded, 1, "a code may only be used once");
+}
@@ -0,0 +1,345 @@
+use domain::repositories::{
+    EmailVerificationTokenRepository, IdempotencyRepository, LoginAttemptRepository, MfaRepository,
+    PasswordResetTokenRepository, RateLimitRepository, UnitOfWork, UserRepository,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+
+use super::cache::{CacheMetrics, CacheStats, CachedUserRepository};
+use super::memory::{
+    spawn_persistence_tasks, DurableStore, InMemoryEmailVerificationTokenRepository, InMemoryIdempotencyRepository,
+    InMemoryLoginAttemptRepository, InMemoryMfaRepository, InMemoryPasswordResetTokenRepository,
+    InMemoryRateLimitRepository, InMemoryUnitOfWork, InMemoryUserRepository,
+};
+use super::postgres::{
+    create_postgres_pool, PostgresEmailVerificationTokenRepository, PostgresIdempotencyRepository,
+    PostgresLoginAttemptRepository, PostgresMfaRepository, PostgresPasswordResetTokenRepository,
+    PostgresRateLimitRepository, PostgresUnitOfWork, PostgresUserRepository,
+};
+use super::sqlite::{
+    create_sqlite_pool, SqliteEmailVerificationTokenRepository, SqliteIdempotencyRepository,
+    SqliteLoginAttemptRepository, SqliteMfaRepository, SqlitePasswordResetTokenRepository,
+    SqliteRateLimitRepository, SqliteUnitOfWork, SqliteUserRepository,
+};
+use crate::config::{AppConfig, ConfigProvider, LockoutConfig, RetentionConfig, StorageBackend};
+use crate::errors::InfrastructureError;
+
+/// How often expired idempotency keys are deleted
+const IDEMPOTENCY_PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+/// How often failed login counts that no longer matter are deleted
+const FAILED_LOGIN_PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+/// How often rate limit state of keys that are no longer limited is deleted
+const RATE_LIMIT_PURGE_INTERVAL: Duration = Duration::from_secs(300);
+/// How often expired password reset and email verification tokens are deleted
+const EMAILED_TOKEN_PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Repositories of the configured storage backend together with the
+/// background work that keeps them running.
+pub struct Storage {
+    pub user_repository: Arc<dyn UserRepository>,
+    pub unit_of_work: Arc<dyn UnitOfWork>,
+    pub idempotency_repository: Arc<dyn IdempotencyRepository>,
+    pub login_attempt_repository: Arc<dyn LoginAttemptRepository>,
+    pub rate_limit_repository: Arc<dyn RateLimitRepository>,
+    pub password_reset_repository: Arc<dyn PasswordResetTokenRepository>,
+    pub email_verification_repository: Arc<dyn EmailVerificationTokenRepository>,
+    pub mfa_repository: Arc<dyn MfaRepository>,
+    /// In-memory stores to snapshot on shutdown
+    durable_stores: Vec<Arc<dyn DurableStore>>,
+    cache_metrics: Option<Arc<CacheMetrics>>,
+    background_tasks: Vec<JoinHandle<()>>,
+}
+
+impl Storage {
+    #[allow(clippy::too_many_arguments)]
+    fn new<R: UserRepository + 'static>(
+        user_repository: R,
+        unit_of_work: Arc<dyn UnitOfWork>,
+        idempotency_repository: Arc<dyn IdempotencyRepository>,
+        login_attempt_repository: Arc<dyn LoginAttemptRepository>,
+        rate_limit_repository: Arc<dyn RateLimitRepository>,
+        password_reset_repository: Arc<dyn PasswordResetTokenRepository>,
+        email_verification_repository: Arc<dyn EmailVerificationTokenRepository>,
+        mfa_repository: Arc<dyn MfaRepository>,
+        config: &AppConfig,
+    ) -> Self {
+        let cache = &config.cache;
+        let mut storage = if cache.enabled {
+            info!("Caching user lookups (capacity {}, TTL {}s)", cache.capacity, cache.ttl);
+            let cached = CachedUserRepository::new(user_repository, cache);
+
+            Self {
+                cache_metrics: Some(cached.metrics()),
+                unit_of_work: Arc::new(cached.unit_of_work(unit_of_work)),
+                user_repository: Arc::new(cached),
+                idempotency_repository,
+                login_attempt_repository,
+                rate_limit_repository,
+                password_reset_repository,
+                email_verification_repository,
+                mfa_repository,
+                durable_stores: Vec::new(),
+                background_tasks: Vec::new(),
+            }
+        } else {
+            Self {
+                user_repository: Arc::new(user_repository),
+                unit_of_work,
+                idempotency_repository,
+                login_attempt_repository,
+                rate_limit_repository,
+                password_reset_repository,
+                email_verification_repository,
+                mfa_repository,
+                durable_stores: Vec::new(),
+                cache_metrics: None,
+                background_tasks: Vec::new(),
+            }
+        };
+
+        storage
+            .background_tasks
+            .push(spawn_idempotency_purge(Arc::clone(&storage.idempotency_repository)));
+        storage.background_tasks.push(spawn_failed_login_purge(
+            Arc::clone(&storage.login_attempt_repository),
+            &config.lockout,
+        ));
+        storage.background_tasks.push(spawn_emailed_token_purge(
+            Arc::clone(&storage.password_reset_repository),
+            Arc::clone(&storage.email_verification_repository),
+        ));
+        if config.rate_limit.enabled {
+            storage
+                .background_tasks
+                .push(spawn_rate_limit_purge(Arc::clone(&storage.rate_limit_repository)));
+        }
+        if config.retention.deleted_users_days > 0 {
+            storage.background_tasks.push(spawn_deleted_user_purge(
+                Arc::clone(&storage.user_repository),
+                Arc::clone(&storage.mfa_repository),
+                Arc::clone(&storage.password_reset_repository),
+                Arc::clone(&storage.email_verification_repository),
+                &config.retention,
+            ));
+        }
+
+        storage
+    }
+
+    /// Hit and miss counters of the user cache, when caching is enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache_metrics.as_ref().map(|metrics| metrics.stats())
+    }
+
+    /// Stops background tasks and flushes state that must survive a restart.
+    pub async fn shutdown(self) -> Result<(), InfrastructureError> {
+        for task in &self.background_tasks {
+            task.abort();
+        }
+
+        if let Some(stats) = self.cache_stats() {
+            info!(
+                hits = stats.hits,
+                negative_hits = stats.negative_hits,
+                misses = stats.misses,
+                "User cache statistics"
+            );
+        }
+
+        if !self.durable_stores.is_empty() {
+            info!("Writing final snapshot of in-memory repositories");
+            let stores = self.durable_stores;
+            tokio::task::spawn_blocking(move || stores.iter().try_for_each(|store| store.snapshot()))
+                .await
+                .map_err(|e| InfrastructureError::DatabaseError(format!("Snapshot task failed: {}", e)))??;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the repositories for the configured storage backend.
+///
+/// Only the selected backend is initialised, so the in-memory backend does not
+/// need a reachable database.
+pub async fn create_storage(
+    config_provider: Arc<dyn ConfigProvider>,
+) -> Result<Storage, InfrastructureError> {
+    let config = config_provider.get_config();
+
+    match config.storage.backend {
+        StorageBackend::Postgres => {
+            info!("Using PostgreSQL repository");
+            let pool = create_postgres_pool(Arc::clone(&config_provider)).await?;
+            Ok(Storage::new(
+                PostgresUserRepository::new(pool.clone()),
+                Arc::new(PostgresUnitOfWork::new(pool.clone())),
+                Arc::new(PostgresIdempotencyRepository::new(pool.clone())),
+                Arc::new(PostgresLoginAttemptRepository::new(pool.clone())),
+                Arc::new(PostgresRateLimitRepository::new(pool.clone())),
+                Arc::new(PostgresPasswordResetTokenRepository::new(pool.clone())),
+                Arc::new(PostgresEmailVerificationTokenRepository::new(pool.clone())),
+                Arc::new(PostgresMfaRepository::new(pool)),
+                config,
+            ))
+        }
+        StorageBackend::Sqlite => {
+            info!("Using SQLite repository");
+            let pool = create_sqlite_pool(&config.sqlite).await?;
+            Ok(Storage::new(
+                SqliteUserRepository::new(pool.clone()),
+                Arc::new(SqliteUnitOfWork::new(pool.clone())),
+                Arc::new(SqliteIdempotencyRepository::new(pool.clone())),
+                Arc::new(SqliteLoginAttemptRepository::new(pool.clone())),
+                Arc::new(SqliteRateLimitRepository::new(pool.clone())),
+                Arc::new(SqlitePasswordResetTokenRepository::new(pool.clone())),
+                Arc::new(SqliteEmailVerificationTokenRepository::new(pool.clone())),
+                Arc::new(SqliteMfaRepository::new(pool)),
+                config,
+            ))
+        }
+        StorageBackend::Memory => {
+            info!("Using in-memory repository");
+            let repository = Arc::new(InMemoryUserRepository::with_persistence(&config.memory)?);
+            // MFA enrollments and emailed tokens are kept alongside the users,
+            // so a restart does not silently switch off second factors
+            let password_reset_repository =
+                Arc::new(InMemoryPasswordResetTokenRepository::with_persistence(&config.memory)?);
+            let email_verification_repository =
+                Arc::new(InMemoryEmailVerificationTokenRepository::with_persistence(&config.memory)?);
+            let mfa_repository = Arc::new(InMemoryMfaRepository::with_persistence(&config.memory)?);
+
+            let mut storage = Storage::new(
+                Arc::clone(&repository),
+                Arc::new(InMemoryUnitOfWork::new(Arc::clone(&repository))),
+                Arc::new(InMemoryIdempotencyRepository::new()),
+                Arc::new(InMemoryLoginAttemptRepository::new()),
+                Arc::new(InMemoryRateLimitRepository::new()),
+                Arc::clone(&password_reset_repository) as Arc<dyn PasswordResetTokenRepository>,
+                Arc::clone(&email_verification_repository) as Arc<dyn EmailVerificationTokenRepository>,
+                Arc::clone(&mfa_repository) as Arc<dyn MfaRepository>,
+                config,
+            );
+            if repository.is_persistent() {
+                storage.durable_stores = vec![
+                    repository,
+                    password_reset_repository,
+                    email_verification_repository,
+                    mfa_repository,
+                ];
+                storage
+                    .background_tasks
+                    .extend(spawn_persistence_tasks(storage.durable_stores.clone(), &config.memory));
+            }
+
+            Ok(storage)
+        }
+    }
+}
+
+fn spawn_idempotency_purge(repository: Arc<dyn IdempotencyRepository>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(IDEMPOTENCY_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repository.purge_expired().await {
+                Ok(purged) => debug!("Purged {} expired idempotency keys", purged),
+                Err(e) => error!("Purging expired idempotency keys failed: {}", e),
+            }
+        }
+    })
+}
+
+/// Also deletes what the purged users owned, which no foreign key removes
+fn spawn_deleted_user_purge(
+    repository: Arc<dyn UserRepository>,
+    mfa: Arc<dyn MfaRepository>,
+    password_reset: Arc<dyn PasswordResetTokenRepository>,
+    email_verification: Arc<dyn EmailVerificationTokenRepository>,
+    retention: &RetentionConfig,
+) -> JoinHandle<()> {
+    let retention_period = chrono::Duration::days(retention.deleted_users_days as i64);
+    let period = Duration::from_secs(retention.purge_interval.max(1));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match repository.purge_deleted(chrono::Utc::now() - retention_period).await {
+                Ok(purged) if purged.is_empty() => {}
+                Ok(purged) => {
+                    info!(
+                        "Purged {} users deleted more than {} days ago",
+                        purged.len(),
+                        retention_period.num_days()
+                    );
+                    for user_id in &purged {
+                        if let Err(e) = mfa.delete(user_id).await {
+                            error!("Deleting MFA enrollment of purged user {} failed: {}", user_id, e);
+                        }
+                        if let Err(e) = password_reset.delete_for_user(user_id).await {
+                            error!("Deleting password reset tokens of purged user {} failed: {}", user_id, e);
+                        }
+                        if let Err(e) = email_verification.delete_for_user(user_id).await {
+                            error!("Deleting email verification tokens of purged user {} failed: {}", user_id, e);
+                        }
+                    }
+                }
+                Err(e) => error!("Purging deleted users failed: {}", e),
+            }
+        }
+    })
+}
+
+fn spawn_failed_login_purge(
+    repository: Arc<dyn LoginAttemptRepository>,
+    lockout: &LockoutConfig,
+) -> JoinHandle<()> {
+    let reset_after = chrono::Duration::seconds(lockout.reset_after as i64);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FAILED_LOGIN_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repository.purge_stale(chrono::Utc::now() - reset_after).await {
+                Ok(purged) => debug!("Purged {} stale failed login counts", purged),
+                Err(e) => error!("Purging failed login counts failed: {}", e),
+            }
+        }
+    })
+}
+
+fn spawn_rate_limit_purge(repository: Arc<dyn RateLimitRepository>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RATE_LIMIT_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repository.purge_expired(chrono::Utc::now()).await {
+                Ok(purged) => debug!("Purged {} expired rate limit keys", purged),
+                Err(e) => error!("Purging rate limit keys failed: {}", e),
+            }
+        }
+    })
+}
+
+fn spawn_emailed_token_purge(
+    password_reset: Arc<dyn PasswordResetTokenRepository>,
+    email_verification: Arc<dyn EmailVerificationTokenRepository>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EMAILED_TOKEN_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match password_reset.purge_expired(chrono::Utc::now()).await {
+                Ok(purged) => debug!("Purged {} expired password reset tokens", purged),
+                Err(e) => error!("Purging password reset tokens failed: {}", e),
+            }
+            match email_verification.purge_expired(chrono::Utc::now()).await {
+                Ok(purged) => debug!("Purged {} expired email verification tokens", purged),
+                Err(e) => error!("Purging email verification tokens failed: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::EmailVerificationToken;
+use domain::errors::DomainError;
+use domain::repositories::EmailVerificationTokenRepository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::persistence::{DurableStore, Journaled, JournaledState};
+use crate::config::MemoryConfig;
+use crate::errors::InfrastructureError;
+
+/// A single change to the stored email verification tokens, logged before it is applied
+#[derive(Serialize, Deserialize)]
+pub(crate) enum EmailVerificationTokenRecord {
+    Create(EmailVerificationToken),
+    Consume { token_hash: String, used_at: DateTime<Utc> },
+    Delete(Vec<String>),
+}
+
+impl Journaled for HashMap<String, EmailVerificationToken> {
+    const BINARY_MAGIC: &'static [u8; 6] = b"RBSEVT";
+    const DESCRIPTION: &'static str = "email verification tokens";
+
+    type Record = EmailVerificationTokenRecord;
+    type Snapshot = Vec<EmailVerificationToken>;
+
+    fn apply(&mut self, record: EmailVerificationTokenRecord) {
+        match record {
+            EmailVerificationTokenRecord::Create(token) => {
+                self.insert(token.token_hash.clone(), token);
+            }
+            EmailVerificationTokenRecord::Consume { token_hash, used_at } => {
+                if let Some(token) = self.get_mut(&token_hash) {
+                    token.used_at = Some(used_at);
+                }
+            }
+            EmailVerificationTokenRecord::Delete(token_hashes) => {
+                for token_hash in &token_hashes {
+                    self.remove(token_hash);
+                }
+            }
+        }
+    }
+
+    fn to_snapshot(&self) -> Vec<EmailVerificationToken> {
+        self.values().cloned().collect()
+    }
+
+    fn from_snapshot(snapshot: Vec<EmailVerificationToken>) -> Self {
+        snapshot.into_iter().map(|token| (token.token_hash.clone(), token)).collect()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Email verification tokens kept in process memory. They survive a restart only when
+/// the memory backend is persistent; otherwise users request a new one.
+pub struct InMemoryEmailVerificationTokenRepository {
+    tokens: JournaledState<HashMap<String, EmailVerificationToken>>,
+}
+
+impl InMemoryEmailVerificationTokenRepository {
+    pub fn new() -> Self {
+        Self {
+            tokens: JournaledState::new(),
+        }
+    }
+
+    /// Creates a repository kept in `<snapshot>.email_verifications` and its
+    /// write-ahead log when the memory backend is persistent. Otherwise this
+    /// is `new()`.
+    pub fn with_persistence(config: &MemoryConfig) -> Result<Self, InfrastructureError> {
+        Ok(Self {
+            tokens: JournaledState::with_persistence(config, "email_verifications")?,
+        })
+    }
+
+    /// Deletes the tokens matching `predicate` and returns how many there were
+    async fn delete_where(&self, predicate: impl Fn(&EmailVerificationToken) -> bool) -> Result<u64, DomainError> {
+        self.tokens
+            .update(|tokens| {
+                let token_hashes: Vec<String> = tokens
+                    .values()
+                    .filter(|token| predicate(token))
+                    .map(|token| token.token_hash.clone())
+                    .collect();
+                let count = token_hashes.len() as u64;
+                Ok(((count > 0).then_some(EmailVerificationTokenRecord::Delete(token_hashes)), count))
+            })
+            .await
+    }
+}
+
+impl DurableStore for InMemoryEmailVerificationTokenRepository {
+    fn is_persistent(&self) -> bool {
+        self.tokens.is_persistent()
+    }
+
+    fn snapshot(&self) -> Result<(), InfrastructureError> {
+        self.tokens.snapshot()
+    }
+
+    fn sync(&self) -> Result<(), InfrastructureError> {
+        self.tokens.sync()
+    }
+}
+
+#[async_trait]
+impl EmailVerificationTokenRepository for InMemoryEmailVerificationTokenRepository {
+    #[instrument(skip(self, token), fields(user_id = %token.user_id))]
+    async fn create(&self, token: &EmailVerificationToken) -> Result<(), DomainError> {
+        self.tokens
+            .update(|tokens| {
+                if tokens.contains_key(&token.token_hash) {
+                    return Err(DomainError::Conflict("Email verification token already exists".to_string()));
+                }
+                Ok((Some(EmailVerificationTokenRecord::Create(token.clone())), ()))
+            })
+            .await
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn find(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>, DomainError> {
+        Ok(self.tokens.read()?.get(token_hash).cloned())
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn consume(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<EmailVerificationToken>, DomainError> {
+        self.tokens
+            .update(|tokens| match tokens.get(token_hash) {
+                Some(token) if token.is_usable(now) => {
+                    let record = EmailVerificationTokenRecord::Consume {
+                        token_hash: token_hash.to_string(),
+                        used_at: now,
+                    };
+                    let mut token = token.clone();
+                    token.used_at = Some(now);
+                    Ok((Some(record), Some(token)))
+                }
+                _ => Ok((None, None)),
+            })
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_for_user(&self, user_id: &Uuid) -> Result<u64, DomainError> {
+        self.delete_where(|token| token.user_id == *user_id).await
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        self.delete_where(|token| token.expires_at <= now).await
+    }
+}
+
+impl Default for InMemoryEmailVerificationTokenRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{IdempotencyRecord, StoredResponse};
+use domain::errors::DomainError;
+use domain::repositories::IdempotencyRepository;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use tracing::instrument;
+
+/// Idempotency records kept in process memory; they do not survive a restart.
+pub struct InMemoryIdempotencyRepository {
+    records: Mutex<HashMap<String, IdempotencyRecord>>,
+}
+
+impl InMemoryIdempotencyRepository {
+    pub fn new() -> Self {
+        Self {
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, IdempotencyRecord>>, DomainError> {
+        self.records.lock().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire idempotency lock: {}", e))
+        })
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for InMemoryIdempotencyRepository {
+    #[instrument(skip(self, record), fields(key = %record.key))]
+    async fn try_begin(
+        &self,
+        record: &IdempotencyRecord,
+        abandoned_before: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyRecord>, DomainError> {
+        let mut records = self.lock()?;
+
+        if let Some(existing) = records.get(&record.key) {
+            let expired = existing.expires_at <= record.created_at;
+            let abandoned = existing.response.is_none() && existing.created_at < abandoned_before;
+            if !expired && !abandoned {
+                return Ok(Some(existing.clone()));
+            }
+        }
+
+        records.insert(record.key.clone(), record.clone());
+        Ok(None)
+    }
+
+    #[instrument(skip(self, response))]
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), DomainError> {
+        if let Some(record) = self.lock()?.get_mut(key) {
+            record.response = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn remove(&self, key: &str) -> Result<(), DomainError> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self) -> Result<u64, DomainError> {
+        let now = Utc::now();
+        let mut records = self.lock()?;
+
+        let before = records.len();
+        records.retain(|_, record| record.expires_at > now);
+        Ok((before - records.len()) as u64)
+    }
+}
+
+impl Default for InMemoryIdempotencyRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
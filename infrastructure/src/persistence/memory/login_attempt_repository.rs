@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{FailedLogins, LoginAttemptKey, LoginAttemptScope};
+use domain::errors::DomainError;
+use domain::repositories::LoginAttemptRepository;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use tracing::instrument;
+
+/// Failed login counts kept in process memory; they do not survive a restart
+/// and are not shared between instances.
+pub struct InMemoryLoginAttemptRepository {
+    counts: Mutex<HashMap<LoginAttemptKey, FailedLogins>>,
+}
+
+impl InMemoryLoginAttemptRepository {
+    pub fn new() -> Self {
+        Self {
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<LoginAttemptKey, FailedLogins>>, DomainError> {
+        self.counts.lock().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire login attempt lock: {}", e))
+        })
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for InMemoryLoginAttemptRepository {
+    #[instrument(skip(self), fields(scope = %key.scope))]
+    async fn find(&self, key: &LoginAttemptKey) -> Result<Option<FailedLogins>, DomainError> {
+        Ok(self.lock()?.get(key).cloned())
+    }
+
+    #[instrument(skip(self), fields(scope = %key.scope))]
+    async fn record_failure(
+        &self,
+        key: &LoginAttemptKey,
+        failed_at: DateTime<Utc>,
+        reset_before: DateTime<Utc>,
+    ) -> Result<FailedLogins, DomainError> {
+        let mut counts = self.lock()?;
+
+        let count = counts
+            .entry(key.clone())
+            .and_modify(|count| {
+                if count.last_failed_at < reset_before {
+                    count.failures = 0;
+                    count.first_failed_at = failed_at;
+                }
+            })
+            .or_insert_with(|| FailedLogins {
+                key: key.clone(),
+                failures: 0,
+                first_failed_at: failed_at,
+                last_failed_at: failed_at,
+            });
+        count.failures += 1;
+        count.last_failed_at = failed_at;
+
+        Ok(count.clone())
+    }
+
+    #[instrument(skip(self), fields(scope = %key.scope))]
+    async fn reset(&self, key: &LoginAttemptKey) -> Result<(), DomainError> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn find_with_failures(
+        &self,
+        scope: LoginAttemptScope,
+        min_failures: u32,
+        reset_before: DateTime<Utc>,
+    ) -> Result<Vec<FailedLogins>, DomainError> {
+        let mut found: Vec<FailedLogins> = self
+            .lock()?
+            .values()
+            .filter(|count| {
+                count.key.scope == scope
+                    && count.failures >= min_failures
+                    && count.last_failed_at >= reset_before
+            })
+            .cloned()
+            .collect();
+        found.sort_by_key(|failed| std::cmp::Reverse(failed.last_failed_at));
+
+        Ok(found)
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_stale(&self, reset_before: DateTime<Utc>) -> Result<u64, DomainError> {
+        let mut counts = self.lock()?;
+
+        let before = counts.len();
+        counts.retain(|_, count| count.last_failed_at >= reset_before);
+        Ok((before - counts.len()) as u64)
+    }
+}
+
+impl Default for InMemoryLoginAttemptRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use domain::entities::TotpEnrollment;
+use domain::errors::DomainError;
+use domain::repositories::MfaRepository;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::persistence::{DurableStore, Journaled, JournaledState};
+use crate::config::MemoryConfig;
+use crate::errors::InfrastructureError;
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub(crate) struct MfaState {
+    enrollments: HashMap<Uuid, TotpEnrollment>,
+    recovery_codes: HashMap<Uuid, HashSet<String>>,
+}
+
+/// A single change to the MFA state, logged before it is applied
+#[derive(Serialize, Deserialize)]
+pub(crate) enum MfaRecord {
+    Save(TotpEnrollment),
+    Delete(Uuid),
+    RecordStep { user_id: Uuid, step: i64 },
+    ReplaceRecoveryCodes { user_id: Uuid, code_hashes: Vec<String> },
+    ConsumeRecoveryCode { user_id: Uuid, code_hash: String },
+}
+
+impl Journaled for MfaState {
+    const BINARY_MAGIC: &'static [u8; 6] = b"RBSMFA";
+    const DESCRIPTION: &'static str = "MFA enrollments";
+
+    type Record = MfaRecord;
+    type Snapshot = MfaState;
+
+    fn apply(&mut self, record: MfaRecord) {
+        match record {
+            MfaRecord::Save(enrollment) => {
+                self.enrollments.insert(enrollment.user_id, enrollment);
+            }
+            MfaRecord::Delete(user_id) => {
+                self.recovery_codes.remove(&user_id);
+                self.enrollments.remove(&user_id);
+            }
+            MfaRecord::RecordStep { user_id, step } => {
+                if let Some(enrollment) = self.enrollments.get_mut(&user_id) {
+                    enrollment.last_used_step = Some(step);
+                }
+            }
+            MfaRecord::ReplaceRecoveryCodes { user_id, code_hashes } => {
+                self.recovery_codes.insert(user_id, code_hashes.into_iter().collect());
+            }
+            MfaRecord::ConsumeRecoveryCode { user_id, code_hash } => {
+                if let Some(codes) = self.recovery_codes.get_mut(&user_id) {
+                    codes.remove(&code_hash);
+                }
+            }
+        }
+    }
+
+    fn to_snapshot(&self) -> MfaState {
+        self.clone()
+    }
+
+    fn from_snapshot(snapshot: MfaState) -> Self {
+        snapshot
+    }
+
+    fn entry_count(&self) -> usize {
+        self.enrollments.len()
+    }
+}
+
+/// TOTP enrollments kept in process memory. They survive a restart only when
+/// the memory backend is persistent, next to the user snapshot.
+pub struct InMemoryMfaRepository {
+    state: JournaledState<MfaState>,
+}
+
+impl InMemoryMfaRepository {
+    pub fn new() -> Self {
+        Self {
+            state: JournaledState::new(),
+        }
+    }
+
+    /// Creates a repository kept in `<snapshot>.mfa` and its write-ahead log
+    /// when the memory backend is persistent. Otherwise this is `new()`.
+    pub fn with_persistence(config: &MemoryConfig) -> Result<Self, InfrastructureError> {
+        Ok(Self {
+            state: JournaledState::with_persistence(config, "mfa")?,
+        })
+    }
+}
+
+impl DurableStore for InMemoryMfaRepository {
+    fn is_persistent(&self) -> bool {
+        self.state.is_persistent()
+    }
+
+    fn snapshot(&self) -> Result<(), InfrastructureError> {
+        self.state.snapshot()
+    }
+
+    fn sync(&self) -> Result<(), InfrastructureError> {
+        self.state.sync()
+    }
+}
+
+#[async_trait]
+impl MfaRepository for InMemoryMfaRepository {
+    #[instrument(skip(self, enrollment), fields(user_id = %enrollment.user_id))]
+    async fn save(&self, enrollment: &TotpEnrollment) -> Result<(), DomainError> {
+        self.state
+            .update(|_| Ok((Some(MfaRecord::Save(enrollment.clone())), ())))
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn find(&self, user_id: &Uuid) -> Result<Option<TotpEnrollment>, DomainError> {
+        Ok(self.state.read()?.enrollments.get(user_id).cloned())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, user_id: &Uuid) -> Result<bool, DomainError> {
+        self.state
+            .update(|state| {
+                let enrolled = state.enrollments.contains_key(user_id);
+                let enrolled_or_has_codes = enrolled || state.recovery_codes.contains_key(user_id);
+                Ok((enrolled_or_has_codes.then_some(MfaRecord::Delete(*user_id)), enrolled))
+            })
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn record_step(&self, user_id: &Uuid, step: i64) -> Result<bool, DomainError> {
+        self.state
+            .update(|state| match state.enrollments.get(user_id) {
+                Some(enrollment) if enrollment.last_used_step.is_none_or(|last| step > last) => {
+                    Ok((Some(MfaRecord::RecordStep { user_id: *user_id, step }), true))
+                }
+                _ => Ok((None, false)),
+            })
+            .await
+    }
+
+    #[instrument(skip(self, code_hashes))]
+    async fn replace_recovery_codes(&self, user_id: &Uuid, code_hashes: &[String]) -> Result<(), DomainError> {
+        let record = MfaRecord::ReplaceRecoveryCodes {
+            user_id: *user_id,
+            code_hashes: code_hashes.to_vec(),
+        };
+        self.state.update(|_| Ok((Some(record), ()))).await
+    }
+
+    #[instrument(skip(self, code_hash))]
+    async fn consume_recovery_code(&self, user_id: &Uuid, code_hash: &str) -> Result<bool, DomainError> {
+        self.state
+            .update(|state| {
+                let unused = state
+                    .recovery_codes
+                    .get(user_id)
+                    .is_some_and(|codes| codes.contains(code_hash));
+                let record = MfaRecord::ConsumeRecoveryCode {
+                    user_id: *user_id,
+                    code_hash: code_hash.to_string(),
+                };
+                Ok((unused.then_some(record), unused))
+            })
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn count_recovery_codes(&self, user_id: &Uuid) -> Result<u64, DomainError> {
+        Ok(self.state.read()?.recovery_codes.get(user_id).map_or(0, |codes| codes.len() as u64))
+    }
+}
+
+impl Default for InMemoryMfaRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
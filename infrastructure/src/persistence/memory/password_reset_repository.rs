@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::PasswordResetToken;
+use domain::errors::DomainError;
+use domain::repositories::PasswordResetTokenRepository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::persistence::{DurableStore, Journaled, JournaledState};
+use crate::config::MemoryConfig;
+use crate::errors::InfrastructureError;
+
+/// A single change to the stored password reset tokens, logged before it is applied
+#[derive(Serialize, Deserialize)]
+pub(crate) enum PasswordResetTokenRecord {
+    Create(PasswordResetToken),
+    Consume { token_hash: String, used_at: DateTime<Utc> },
+    Delete(Vec<String>),
+}
+
+impl Journaled for HashMap<String, PasswordResetToken> {
+    const BINARY_MAGIC: &'static [u8; 6] = b"RBSPRT";
+    const DESCRIPTION: &'static str = "password reset tokens";
+
+    type Record = PasswordResetTokenRecord;
+    type Snapshot = Vec<PasswordResetToken>;
+
+    fn apply(&mut self, record: PasswordResetTokenRecord) {
+        match record {
+            PasswordResetTokenRecord::Create(token) => {
+                self.insert(token.token_hash.clone(), token);
+            }
+            PasswordResetTokenRecord::Consume { token_hash, used_at } => {
+                if let Some(token) = self.get_mut(&token_hash) {
+                    token.used_at = Some(used_at);
+                }
+            }
+            PasswordResetTokenRecord::Delete(token_hashes) => {
+                for token_hash in &token_hashes {
+                    self.remove(token_hash);
+                }
+            }
+        }
+    }
+
+    fn to_snapshot(&self) -> Vec<PasswordResetToken> {
+        self.values().cloned().collect()
+    }
+
+    fn from_snapshot(snapshot: Vec<PasswordResetToken>) -> Self {
+        snapshot.into_iter().map(|token| (token.token_hash.clone(), token)).collect()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Password reset tokens kept in process memory. They survive a restart only when
+/// the memory backend is persistent; otherwise users request a new one.
+pub struct InMemoryPasswordResetTokenRepository {
+    tokens: JournaledState<HashMap<String, PasswordResetToken>>,
+}
+
+impl InMemoryPasswordResetTokenRepository {
+    pub fn new() -> Self {
+        Self {
+            tokens: JournaledState::new(),
+        }
+    }
+
+    /// Creates a repository kept in `<snapshot>.password_resets` and its
+    /// write-ahead log when the memory backend is persistent. Otherwise this
+    /// is `new()`.
+    pub fn with_persistence(config: &MemoryConfig) -> Result<Self, InfrastructureError> {
+        Ok(Self {
+            tokens: JournaledState::with_persistence(config, "password_resets")?,
+        })
+    }
+
+    /// Deletes the tokens matching `predicate` and returns how many there were
+    async fn delete_where(&self, predicate: impl Fn(&PasswordResetToken) -> bool) -> Result<u64, DomainError> {
+        self.tokens
+            .update(|tokens| {
+                let token_hashes: Vec<String> = tokens
+                    .values()
+                    .filter(|token| predicate(token))
+                    .map(|token| token.token_hash.clone())
+                    .collect();
+                let count = token_hashes.len() as u64;
+                Ok(((count > 0).then_some(PasswordResetTokenRecord::Delete(token_hashes)), count))
+            })
+            .await
+    }
+}
+
+impl DurableStore for InMemoryPasswordResetTokenRepository {
+    fn is_persistent(&self) -> bool {
+        self.tokens.is_persistent()
+    }
+
+    fn snapshot(&self) -> Result<(), InfrastructureError> {
+        self.tokens.snapshot()
+    }
+
+    fn sync(&self) -> Result<(), InfrastructureError> {
+        self.tokens.sync()
+    }
+}
+
+#[async_trait]
+impl PasswordResetTokenRepository for InMemoryPasswordResetTokenRepository {
+    #[instrument(skip(self, token), fields(user_id = %token.user_id))]
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), DomainError> {
+        self.tokens
+            .update(|tokens| {
+                if tokens.contains_key(&token.token_hash) {
+                    return Err(DomainError::Conflict("Password reset token already exists".to_string()));
+                }
+                Ok((Some(PasswordResetTokenRecord::Create(token.clone())), ()))
+            })
+            .await
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, DomainError> {
+        Ok(self.tokens.read()?.get(token_hash).cloned())
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn consume(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<PasswordResetToken>, DomainError> {
+        self.tokens
+            .update(|tokens| match tokens.get(token_hash) {
+                Some(token) if token.is_usable(now) => {
+                    let record = PasswordResetTokenRecord::Consume {
+                        token_hash: token_hash.to_string(),
+                        used_at: now,
+                    };
+                    let mut token = token.clone();
+                    token.used_at = Some(now);
+                    Ok((Some(record), Some(token)))
+                }
+                _ => Ok((None, None)),
+            })
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_for_user(&self, user_id: &Uuid) -> Result<u64, DomainError> {
+        self.delete_where(|token| token.user_id == *user_id).await
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        self.delete_where(|token| token.expires_at <= now).await
+    }
+}
+
+impl Default for InMemoryPasswordResetTokenRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,508 @@
+use chrono::{DateTime, Utc};
+use domain::entities::User;
+use domain::errors::DomainError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::{FsyncPolicy, MemoryConfig, SnapshotFormat};
+use crate::errors::InfrastructureError;
+
+/// Format version written after the magic prefix of binary snapshots.
+///
+/// Version 01 is MessagePack with field names, which honours
+/// `#[serde(default)]` like the JSON format does. Other versions are rejected
+/// rather than misread.
+const BINARY_VERSION: &[u8; 2] = b"01";
+
+/// State kept in memory that a `MemoryPersistence` makes durable.
+///
+/// Every change is a record that is logged before it is applied, so replaying
+/// the log on top of the last snapshot rebuilds the same state.
+pub(crate) trait Journaled: Default + Send + Sync + 'static {
+    /// Prefix of binary snapshots so a JSON file, or a snapshot of other
+    /// state, is never mistaken for one; the format version follows it
+    const BINARY_MAGIC: &'static [u8; 6];
+    /// What the state holds, for log messages
+    const DESCRIPTION: &'static str;
+
+    type Record: Serialize + DeserializeOwned + Send + 'static;
+    type Snapshot: Serialize + DeserializeOwned;
+
+    /// Must be idempotent, since records already covered by a snapshot may be
+    /// replayed again.
+    fn apply(&mut self, record: Self::Record);
+    fn to_snapshot(&self) -> Self::Snapshot;
+    fn from_snapshot(snapshot: Self::Snapshot) -> Self;
+    /// Number of entries, for log messages
+    fn entry_count(&self) -> usize;
+}
+
+/// A single mutation of the users, appended to the write-ahead log before it
+/// is applied.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum WalRecord {
+    Upsert(Box<User>),
+    Delete(Uuid),
+    /// Records of one transaction, logged as a single line so they replay all or nothing
+    Batch(Vec<WalRecord>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    created_at: DateTime<Utc>,
+    users: Vec<User>,
+}
+
+impl Journaled for HashMap<Uuid, User> {
+    const BINARY_MAGIC: &'static [u8; 6] = b"RBSUSR";
+    const DESCRIPTION: &'static str = "users";
+
+    type Record = WalRecord;
+    type Snapshot = Snapshot;
+
+    fn apply(&mut self, record: WalRecord) {
+        match record {
+            WalRecord::Upsert(user) => {
+                self.insert(user.id, *user);
+            }
+            WalRecord::Delete(id) => {
+                self.remove(&id);
+            }
+            WalRecord::Batch(records) => {
+                for record in records {
+                    self.apply(record);
+                }
+            }
+        }
+    }
+
+    fn to_snapshot(&self) -> Snapshot {
+        Snapshot {
+            created_at: Utc::now(),
+            users: self.values().cloned().collect(),
+        }
+    }
+
+    fn from_snapshot(snapshot: Snapshot) -> Self {
+        snapshot.users.into_iter().map(|user| (user.id, user)).collect()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Snapshot file plus append-only write-ahead log backing some `Journaled` state.
+pub(crate) struct MemoryPersistence<S: Journaled> {
+    snapshot_path: PathBuf,
+    wal_path: PathBuf,
+    format: SnapshotFormat,
+    fsync: FsyncPolicy,
+    wal: Mutex<File>,
+    state: PhantomData<fn() -> S>,
+}
+
+impl<S: Journaled> MemoryPersistence<S> {
+    /// Opens the files and rebuilds the last durable state from the snapshot
+    /// and the records logged after it.
+    pub fn open(snapshot_path: PathBuf, wal_path: PathBuf, config: &MemoryConfig) -> Result<(Self, S), InfrastructureError> {
+        let mut state = load_snapshot::<S>(&snapshot_path)?;
+        let replayed = replay_wal(&wal_path, &mut state)?;
+
+        info!(
+            "Restored {} {} from {} ({} WAL records replayed)",
+            state.entry_count(),
+            S::DESCRIPTION,
+            snapshot_path.display(),
+            replayed
+        );
+
+        let wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&wal_path)
+            .map_err(|e| storage_error("open write-ahead log", &wal_path, e))?;
+
+        let persistence = Self {
+            snapshot_path,
+            wal_path,
+            format: config.snapshot_format,
+            fsync: config.fsync,
+            wal: Mutex::new(wal),
+            state: PhantomData,
+        };
+
+        Ok((persistence, state))
+    }
+
+    /// Appends `record` to the log. The write, and the fsync if configured,
+    /// run on the blocking pool so they do not stall the async workers.
+    pub async fn append(self: &Arc<Self>, record: &S::Record) -> Result<(), InfrastructureError> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let persistence = Arc::clone(self);
+        tokio::task::spawn_blocking(move || persistence.write_line(&line))
+            .await
+            .map_err(|e| InfrastructureError::DatabaseError(format!("WAL append task failed: {}", e)))?
+    }
+
+    fn write_line(&self, line: &[u8]) -> Result<(), InfrastructureError> {
+        let mut wal = self.lock_wal()?;
+        wal.write_all(line)
+            .map_err(|e| storage_error("append to write-ahead log", &self.wal_path, e))?;
+
+        if self.fsync == FsyncPolicy::Always {
+            wal.sync_data()
+                .map_err(|e| storage_error("sync write-ahead log", &self.wal_path, e))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn sync(&self) -> Result<(), InfrastructureError> {
+        self.lock_wal()?
+            .sync_data()
+            .map_err(|e| storage_error("sync write-ahead log", &self.wal_path, e))
+    }
+
+    /// Atomically replaces the snapshot with `state` and truncates the log.
+    ///
+    /// The caller must keep writers out for the duration, otherwise records
+    /// appended meanwhile would be truncated away.
+    pub fn write_snapshot(&self, state: &S) -> Result<(), InfrastructureError> {
+        // Holding the log lock also serialises concurrent snapshots
+        let wal = self.lock_wal()?;
+
+        let snapshot = state.to_snapshot();
+        let bytes = match self.format {
+            SnapshotFormat::Json => serde_json::to_vec(&snapshot)?,
+            SnapshotFormat::Binary => {
+                let mut bytes = [S::BINARY_MAGIC.as_slice(), BINARY_VERSION].concat();
+                rmp_serde::encode::write_named(&mut bytes, &snapshot)
+                    .map_err(|e| InfrastructureError::SerializationError(e.to_string()))?;
+                bytes
+            }
+        };
+
+        write_atomically(&self.snapshot_path, &bytes)?;
+
+        // Everything in the log is now part of the snapshot
+        wal.set_len(0)
+            .and_then(|_| wal.sync_all())
+            .map_err(|e| storage_error("truncate write-ahead log", &self.wal_path, e))?;
+
+        info!(
+            "Wrote snapshot of {} {} to {}",
+            state.entry_count(),
+            S::DESCRIPTION,
+            self.snapshot_path.display()
+        );
+        Ok(())
+    }
+
+    fn lock_wal(&self) -> Result<std::sync::MutexGuard<'_, File>, InfrastructureError> {
+        self.wal.lock().map_err(|e| {
+            InfrastructureError::DatabaseError(format!("Failed to acquire WAL lock: {}", e))
+        })
+    }
+}
+
+/// `Journaled` state behind a lock, made durable when opened with persistence.
+pub(crate) struct JournaledState<S: Journaled> {
+    state: RwLock<S>,
+    /// Held by a writer from checking its change until it is applied, so
+    /// records are logged in the order they are applied, and by snapshots, so
+    /// no record is truncated away. Asynchronous because it is held while the
+    /// record is written to the log.
+    writer: tokio::sync::Mutex<()>,
+    persistence: Option<Arc<MemoryPersistence<S>>>,
+}
+
+impl<S: Journaled> JournaledState<S> {
+    pub fn new() -> Self {
+        Self {
+            state: RwLock::new(S::default()),
+            writer: tokio::sync::Mutex::new(()),
+            persistence: None,
+        }
+    }
+
+    /// Restores the state from `snapshot_path` and the log at `wal_path`, and
+    /// logs every later change there.
+    pub fn open(snapshot_path: PathBuf, wal_path: PathBuf, config: &MemoryConfig) -> Result<Self, InfrastructureError> {
+        let (persistence, state) = MemoryPersistence::open(snapshot_path, wal_path, config)?;
+
+        Ok(Self {
+            state: RwLock::new(state),
+            writer: tokio::sync::Mutex::new(()),
+            persistence: Some(Arc::new(persistence)),
+        })
+    }
+
+    /// Opens the state of a store kept next to the user snapshot, in
+    /// `<snapshot>.<name>` and its log `<snapshot>.<name>.wal`. Without a
+    /// configured snapshot path the state is only kept in memory.
+    pub fn with_persistence(config: &MemoryConfig, name: &str) -> Result<Self, InfrastructureError> {
+        let Some(user_snapshot_path) = &config.snapshot_path else {
+            return Ok(Self::new());
+        };
+
+        let snapshot_path = append_extension(Path::new(user_snapshot_path), name);
+        let wal_path = append_extension(&snapshot_path, "wal");
+        Self::open(snapshot_path, wal_path, config)
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.persistence.is_some()
+    }
+
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, S>, DomainError> {
+        self.state.read().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire read lock: {}", e))
+        })
+    }
+
+    /// Checks a change against the current state and makes it.
+    ///
+    /// `change` returns the record to log and apply, if anything changes,
+    /// along with the result for the caller. No other change is made in
+    /// between, so what `change` checked still holds when the record is applied.
+    pub async fn update<T>(
+        &self,
+        change: impl FnOnce(&S) -> Result<(Option<S::Record>, T), DomainError>,
+    ) -> Result<T, DomainError> {
+        let _writer = self.writer.lock().await;
+
+        let (record, result) = {
+            let state = self.read()?;
+            change(&state)?
+        };
+
+        if let Some(record) = record {
+            if let Some(persistence) = &self.persistence {
+                persistence
+                    .append(&record)
+                    .await
+                    .map_err(|e| DomainError::RepositoryError(e.to_string()))?;
+            }
+
+            self.state
+                .write()
+                .map_err(|e| DomainError::RepositoryError(format!("Failed to acquire write lock: {}", e)))?
+                .apply(record);
+        }
+
+        Ok(result)
+    }
+
+    /// Writes a snapshot of the current state and truncates the write-ahead
+    /// log. Blocks, so it must run outside the async workers.
+    pub fn snapshot(&self) -> Result<(), InfrastructureError> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(());
+        };
+
+        let _writer = self.writer.blocking_lock();
+        let state = self.read().map_err(|e| InfrastructureError::DatabaseError(e.to_string()))?;
+        persistence.write_snapshot(&state)
+    }
+
+    /// Flushes the write-ahead log to disk.
+    pub fn sync(&self) -> Result<(), InfrastructureError> {
+        match &self.persistence {
+            Some(persistence) => persistence.sync(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: Journaled> Default for JournaledState<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An in-memory store that can be backed by a snapshot and write-ahead log.
+pub trait DurableStore: Send + Sync {
+    fn is_persistent(&self) -> bool;
+    /// Writes a snapshot of the current state and truncates the write-ahead
+    /// log. Blocks, so it must run outside the async workers.
+    fn snapshot(&self) -> Result<(), InfrastructureError>;
+    /// Flushes the write-ahead log to disk.
+    fn sync(&self) -> Result<(), InfrastructureError>;
+}
+
+/// Starts the periodic snapshot and fsync tasks configured for `stores`.
+pub fn spawn_persistence_tasks(
+    stores: Vec<Arc<dyn DurableStore>>,
+    config: &MemoryConfig,
+) -> Vec<JoinHandle<()>> {
+    let stores = Arc::new(stores);
+    let mut tasks = Vec::new();
+
+    if config.snapshot_interval > 0 {
+        let stores = Arc::clone(&stores);
+        let period = Duration::from_secs(config.snapshot_interval);
+        tasks.push(spawn_periodic(period, move || {
+            for store in stores.iter() {
+                if let Err(e) = store.snapshot() {
+                    error!("Periodic snapshot failed: {}", e);
+                }
+            }
+        }));
+    }
+
+    if config.fsync == FsyncPolicy::Periodic {
+        let period = Duration::from_millis(config.fsync_interval.max(1));
+        tasks.push(spawn_periodic(period, move || {
+            for store in stores.iter() {
+                if let Err(e) = store.sync() {
+                    error!("Periodic WAL sync failed: {}", e);
+                }
+            }
+        }));
+    }
+
+    tasks
+}
+
+fn spawn_periodic<F>(period: Duration, job: F) -> JoinHandle<()>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let job = Arc::new(job);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        // The first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // File I/O must not block the async workers
+            let job = Arc::clone(&job);
+            if let Err(e) = tokio::task::spawn_blocking(move || job()).await {
+                error!("Persistence task panicked: {}", e);
+            }
+        }
+    })
+}
+
+fn load_snapshot<S: Journaled>(path: &Path) -> Result<S, InfrastructureError> {
+    if !path.exists() {
+        return Ok(S::default());
+    }
+
+    let bytes = fs::read(path).map_err(|e| storage_error("read snapshot", path, e))?;
+    let invalid = |e: &dyn std::fmt::Display| InfrastructureError::SerializationError(format!("Invalid snapshot: {}", e));
+
+    // The format is detected from the file so that changing the setting does not orphan old snapshots
+    let snapshot = match bytes.strip_prefix(S::BINARY_MAGIC.as_slice()) {
+        Some(versioned) if versioned.len() >= BINARY_VERSION.len() => {
+            let (version, payload) = versioned.split_at(BINARY_VERSION.len());
+            if version != BINARY_VERSION {
+                return Err(InfrastructureError::SerializationError(format!(
+                    "Unsupported snapshot format version {} in {}",
+                    String::from_utf8_lossy(version),
+                    path.display()
+                )));
+            }
+            rmp_serde::from_slice(payload).map_err(|e| invalid(&e))?
+        }
+        _ => serde_json::from_slice(&bytes).map_err(|e| invalid(&e))?,
+    };
+
+    Ok(S::from_snapshot(snapshot))
+}
+
+fn replay_wal<S: Journaled>(path: &Path, state: &mut S) -> Result<usize, InfrastructureError> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let bytes = fs::read(path).map_err(|e| storage_error("read write-ahead log", path, e))?;
+    let mut offset = 0;
+    let mut replayed = 0;
+
+    for line in bytes.split_inclusive(|b| *b == b'\n') {
+        let is_last = offset + line.len() == bytes.len();
+        let record = if line.ends_with(b"\n") {
+            serde_json::from_slice::<S::Record>(line).ok()
+        } else {
+            None
+        };
+
+        match record {
+            Some(record) => state.apply(record),
+            None if is_last => {
+                // A crash in the middle of an append leaves a partial last record
+                warn!("Discarding incomplete record at the end of {}", path.display());
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .and_then(|file| file.set_len(offset as u64))
+                    .map_err(|e| storage_error("truncate write-ahead log", path, e))?;
+                break;
+            }
+            None => {
+                return Err(InfrastructureError::SerializationError(format!(
+                    "Corrupt record at byte {} of {}",
+                    offset,
+                    path.display()
+                )));
+            }
+        }
+
+        offset += line.len();
+        replayed += 1;
+    }
+
+    Ok(replayed)
+}
+
+/// Writes to a temporary file and renames it over `path`, so readers and a
+/// crash only ever observe the old or the new content.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), InfrastructureError> {
+    let tmp_path = append_extension(path, "tmp");
+
+    let mut tmp = File::create(&tmp_path).map_err(|e| storage_error("create", &tmp_path, e))?;
+    tmp.write_all(bytes)
+        .and_then(|_| tmp.sync_all())
+        .map_err(|e| storage_error("write", &tmp_path, e))?;
+
+    fs::rename(&tmp_path, path).map_err(|e| storage_error("replace", path, e))?;
+
+    // The rename is only durable once the directory entry is synced
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| storage_error("sync directory", dir, e))?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+fn storage_error(action: &str, path: &Path, error: std::io::Error) -> InfrastructureError {
+    InfrastructureError::DatabaseError(format!("Failed to {} {}: {}", action, path.display(), error))
+}
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{RateLimitDecision, RateLimitQuota};
+use domain::errors::DomainError;
+use domain::repositories::RateLimitRepository;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use tracing::instrument;
+
+/// Rate limit state kept in process memory; every instance limits on its own.
+pub struct InMemoryRateLimitRepository {
+    cells: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl InMemoryRateLimitRepository {
+    pub fn new() -> Self {
+        Self {
+            cells: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, DateTime<Utc>>>, DomainError> {
+        self.cells.lock().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire rate limit lock: {}", e))
+        })
+    }
+}
+
+impl Default for InMemoryRateLimitRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitRepository for InMemoryRateLimitRepository {
+    #[instrument(skip(self, quota))]
+    async fn acquire(
+        &self,
+        key: &str,
+        quota: &RateLimitQuota,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitDecision, DomainError> {
+        let mut cells = self.lock()?;
+        let (decision, tat) = quota.check(cells.get(key).copied(), now);
+        if let Some(tat) = tat {
+            cells.insert(key.to_string(), tat);
+        }
+
+        Ok(decision)
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        let mut cells = self.lock()?;
+        let before = cells.len();
+        cells.retain(|_, tat| *tat > now);
+
+        Ok((before - cells.len()) as u64)
+    }
+}
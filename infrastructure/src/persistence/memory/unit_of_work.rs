@@ -0,0 +1,260 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::User;
+use domain::errors::DomainError;
+use domain::repositories::{RepositoryTransaction, UnitOfWork, UserRepository};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use super::user_repository::check_unique;
+use crate::persistence::version_conflict;
+use super::InMemoryUserRepository;
+
+/// A write staged by a transaction, kept until commit
+pub(super) enum StagedChange {
+    Created(User),
+    /// `base_version` is the committed version the update was based on
+    Updated { user: User, base_version: i64 },
+    Deleted,
+}
+
+impl StagedChange {
+    pub(super) fn user(&self) -> Option<&User> {
+        match self {
+            StagedChange::Created(user) | StagedChange::Updated { user, .. } => Some(user),
+            StagedChange::Deleted => None,
+        }
+    }
+
+    pub(super) fn into_user(self) -> Option<User> {
+        match self {
+            StagedChange::Created(user) | StagedChange::Updated { user, .. } => Some(user),
+            StagedChange::Deleted => None,
+        }
+    }
+}
+
+/// Users as seen by a transaction: the committed state overlaid with its staged changes
+pub(super) fn merged_users<'a>(
+    users: &'a HashMap<Uuid, User>,
+    staged: &'a HashMap<Uuid, StagedChange>,
+) -> impl Iterator<Item = &'a User> {
+    users
+        .values()
+        .filter(|user| !staged.contains_key(&user.id))
+        .chain(staged.values().filter_map(StagedChange::user))
+}
+
+pub struct InMemoryUnitOfWork {
+    repository: Arc<InMemoryUserRepository>,
+}
+
+impl InMemoryUnitOfWork {
+    pub fn new(repository: Arc<InMemoryUserRepository>) -> Self {
+        Self { repository }
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for InMemoryUnitOfWork {
+    async fn begin(&self) -> Result<Box<dyn RepositoryTransaction>, DomainError> {
+        Ok(Box::new(InMemoryTransaction {
+            repository: Arc::clone(&self.repository),
+            staged: Mutex::new(HashMap::new()),
+        }))
+    }
+}
+
+/// Copy-on-write view of an `InMemoryUserRepository`.
+///
+/// Writes are staged per user and only applied to the repository on commit,
+/// so rolling back is simply discarding them.
+pub struct InMemoryTransaction {
+    repository: Arc<InMemoryUserRepository>,
+    staged: Mutex<HashMap<Uuid, StagedChange>>,
+}
+
+impl InMemoryTransaction {
+    fn lock_staged(&self) -> Result<MutexGuard<'_, HashMap<Uuid, StagedChange>>, DomainError> {
+        self.staged.lock().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire transaction lock: {}", e))
+        })
+    }
+
+    /// Runs `f` on the staged changes and the committed users it overlays
+    fn with_view<T>(
+        &self,
+        f: impl FnOnce(&mut HashMap<Uuid, StagedChange>, &HashMap<Uuid, User>) -> Result<T, DomainError>,
+    ) -> Result<T, DomainError> {
+        let mut staged = self.lock_staged()?;
+        let users = self.repository.read_users()?;
+        f(&mut staged, &users)
+    }
+
+    /// Finds a user that is not soft-deleted
+    fn find(&self, predicate: impl Fn(&User) -> bool) -> Result<Option<User>, DomainError> {
+        self.with_view(|staged, users| {
+            Ok(merged_users(users, staged)
+                .find(|user| !user.is_deleted() && predicate(user))
+                .cloned())
+        })
+    }
+}
+
+#[async_trait]
+impl RepositoryTransaction for InMemoryTransaction {
+    fn users(&self) -> &dyn UserRepository {
+        self
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DomainError> {
+        let staged = self.staged.into_inner().map_err(|e| {
+            DomainError::RepositoryError(format!("Failed to acquire transaction lock: {}", e))
+        })?;
+        self.repository.commit_changes(staged).await
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DomainError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryTransaction {
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        info!("Staging user creation in in-memory transaction");
+
+        self.with_view(|staged, users| {
+            check_unique(merged_users(users, staged), user)?;
+
+            // Recreating a user deleted earlier in the transaction replaces it
+            let change = match (staged.get(&user.id), users.get(&user.id)) {
+                (Some(StagedChange::Deleted), Some(base)) => StagedChange::Updated {
+                    user: user.clone(),
+                    base_version: base.version,
+                },
+                _ => StagedChange::Created(user.clone()),
+            };
+            staged.insert(user.id, change);
+            Ok(())
+        })
+    }
+
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        info!("Staging user update in in-memory transaction");
+
+        self.with_view(|staged, users| {
+            match merged_users(users, staged).find(|existing| existing.id == user.id) {
+                None => {
+                    return Err(DomainError::NotFound(format!("User with ID {} not found", user.id)));
+                }
+                Some(current) if current.version != user.version - 1 => {
+                    return Err(version_conflict(user, current));
+                }
+                Some(_) => {}
+            }
+
+            check_unique(
+                merged_users(users, staged).filter(|existing| existing.id != user.id),
+                user,
+            )?;
+
+            let change = match staged.get(&user.id) {
+                Some(StagedChange::Created(_)) => StagedChange::Created(user.clone()),
+                Some(StagedChange::Updated { base_version, .. }) => StagedChange::Updated {
+                    user: user.clone(),
+                    base_version: *base_version,
+                },
+                _ => StagedChange::Updated {
+                    user: user.clone(),
+                    base_version: user.version - 1,
+                },
+            };
+            staged.insert(user.id, change);
+            Ok(())
+        })
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        info!("Staging user deletion in in-memory transaction");
+
+        self.with_view(|staged, users| {
+            if !merged_users(users, staged).any(|existing| existing.id == *id) {
+                return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
+            }
+
+            // A user created in this transaction never reaches the repository
+            if let Some(StagedChange::Created(_)) = staged.remove(id) {
+                return Ok(());
+            }
+            staged.insert(*id, StagedChange::Deleted);
+            Ok(())
+        })
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        self.find(|user| user.id == *id)
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        self.with_view(|staged, users| {
+            Ok(match staged.get(id) {
+                Some(change) => change.user().cloned(),
+                None => users.get(id).cloned(),
+            })
+        })
+    }
+
+    #[instrument(skip(self), fields(username = %username))]
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        self.find(|user| user.username == username)
+    }
+
+    #[instrument(skip(self), fields(email = %email))]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        self.find(|user| user.email == email)
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        self.with_view(|staged, users| {
+            Ok(merged_users(users, staged)
+                .filter(|user| !user.is_deleted())
+                .cloned()
+                .collect())
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        self.with_view(|staged, users| Ok(merged_users(users, staged).cloned().collect()))
+    }
+
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        info!("Staging purge of deleted users in in-memory transaction");
+
+        self.with_view(|staged, users| {
+            let purged: Vec<Uuid> = merged_users(users, staged)
+                .filter(|user| user.deleted_at.is_some_and(|deleted_at| deleted_at < deleted_before))
+                .map(|user| user.id)
+                .collect();
+
+            for id in &purged {
+                // A user created in this transaction never reaches the repository
+                if let Some(StagedChange::Created(_)) = staged.remove(id) {
+                    continue;
+                }
+                staged.insert(*id, StagedChange::Deleted);
+            }
+            Ok(purged)
+        })
+    }
+}
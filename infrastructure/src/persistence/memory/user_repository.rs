@@ -1,22 +1,131 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use domain::entities::User;
 use domain::errors::DomainError;
 use domain::repositories::UserRepository;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::path::PathBuf;
+use std::sync::RwLockReadGuard;
 use tracing::{info, instrument};
 use uuid::Uuid;
 
+use super::persistence::{append_extension, DurableStore, JournaledState, WalRecord};
+use super::unit_of_work::{merged_users, StagedChange};
+use crate::config::MemoryConfig;
+use crate::persistence::version_conflict;
+use crate::errors::InfrastructureError;
+
 pub struct InMemoryUserRepository {
-    users: Arc<RwLock<HashMap<Uuid, User>>>,
+    users: JournaledState<HashMap<Uuid, User>>,
 }
 
 impl InMemoryUserRepository {
     pub fn new() -> Self {
         Self {
-            users: Arc::new(RwLock::new(HashMap::new())),
+            users: JournaledState::new(),
+        }
+    }
+
+    /// Creates a repository backed by the configured snapshot and write-ahead
+    /// log, restoring their contents. Without `snapshot_path` this is `new()`.
+    pub fn with_persistence(config: &MemoryConfig) -> Result<Self, InfrastructureError> {
+        let Some(snapshot_path) = &config.snapshot_path else {
+            return Ok(Self::new());
+        };
+
+        let snapshot_path = PathBuf::from(snapshot_path);
+        let wal_path = config
+            .wal_path
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| append_extension(&snapshot_path, "wal"));
+
+        Ok(Self {
+            users: JournaledState::open(snapshot_path, wal_path, config)?,
+        })
+    }
+
+    pub(super) fn read_users(&self) -> Result<RwLockReadGuard<'_, HashMap<Uuid, User>>, DomainError> {
+        self.users.read()
+    }
+
+    /// Applies the changes staged by a transaction as one logged batch.
+    ///
+    /// Other transactions may have committed since the changes were staged,
+    /// so they are validated again against the current state.
+    pub(super) async fn commit_changes(&self, changes: HashMap<Uuid, StagedChange>) -> Result<(), DomainError> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.users
+            .update(|users| {
+                for (id, change) in &changes {
+                    match (change, users.get(id)) {
+                        (StagedChange::Updated { .. } | StagedChange::Deleted, None) => {
+                            return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
+                        }
+                        (StagedChange::Updated { base_version, .. }, Some(stored)) if stored.version != *base_version => {
+                            return Err(DomainError::Conflict(format!(
+                                "User with ID {} was modified concurrently (expected version {}, found {})",
+                                id, base_version, stored.version
+                            )));
+                        }
+                        _ => {}
+                    }
+                    if let Some(user) = change.user() {
+                        check_unique(merged_users(users, &changes).filter(|other| other.id != *id), user)?;
+                    }
+                }
+
+                let records = changes
+                    .into_iter()
+                    .map(|(id, change)| match change.into_user() {
+                        Some(user) => WalRecord::Upsert(Box::new(user)),
+                        None => WalRecord::Delete(id),
+                    })
+                    .collect();
+                Ok((Some(WalRecord::Batch(records)), ()))
+            })
+            .await
+    }
+}
+
+impl DurableStore for InMemoryUserRepository {
+    fn is_persistent(&self) -> bool {
+        self.users.is_persistent()
+    }
+
+    fn snapshot(&self) -> Result<(), InfrastructureError> {
+        self.users.snapshot()
+    }
+
+    fn sync(&self) -> Result<(), InfrastructureError> {
+        self.users.sync()
+    }
+}
+
+/// Rejects `user` if one of `others` already has its username or email.
+pub(super) fn check_unique<'a>(
+    others: impl IntoIterator<Item = &'a User>,
+    user: &User,
+) -> Result<(), DomainError> {
+    for existing_user in others {
+        if existing_user.username == user.username {
+            return Err(DomainError::ValidationError(format!(
+                "Username {} already exists",
+                user.username
+            )));
+        }
+        if existing_user.email == user.email {
+            return Err(DomainError::ValidationError(format!(
+                "Email {} already exists",
+                user.email
+            )));
         }
     }
+
+    Ok(())
 }
 
 #[async_trait]
@@ -25,101 +134,76 @@ impl UserRepository for InMemoryUserRepository {
     async fn create(&self, user: &User) -> Result<(), DomainError> {
         info!("Creating user in in-memory repository");
         
-        let mut users = self.users.write().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire write lock: {}", e))
-        })?;
-
-        // Check if username already exists
-        for existing_user in users.values() {
-            if existing_user.username == user.username {
-                return Err(DomainError::ValidationError(format!(
-                    "Username {} already exists",
-                    user.username
-                )));
-            }
-            if existing_user.email == user.email {
-                return Err(DomainError::ValidationError(format!(
-                    "Email {} already exists",
-                    user.email
-                )));
-            }
-        }
-
-        users.insert(user.id, user.clone());
-        Ok(())
+        self.users
+            .update(|users| {
+                check_unique(users.values(), user)?;
+                Ok((Some(WalRecord::Upsert(Box::new(user.clone()))), ()))
+            })
+            .await
     }
 
     #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
     async fn update(&self, user: &User) -> Result<(), DomainError> {
         info!("Updating user in in-memory repository");
         
-        let mut users = self.users.write().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire write lock: {}", e))
-        })?;
-
-        if !users.contains_key(&user.id) {
-            return Err(DomainError::NotFound(format!("User with ID {} not found", user.id)));
-        }
-
-        // Check if username already exists for another user
-        for (id, existing_user) in users.iter() {
-            if *id != user.id {
-                if existing_user.username == user.username {
-                    return Err(DomainError::ValidationError(format!(
-                        "Username {} already exists",
-                        user.username
-                    )));
-                }
-                if existing_user.email == user.email {
-                    return Err(DomainError::ValidationError(format!(
-                        "Email {} already exists",
-                        user.email
-                    )));
+        self.users
+            .update(|users| {
+                match users.get(&user.id) {
+                    None => {
+                        return Err(DomainError::NotFound(format!("User with ID {} not found", user.id)));
+                    }
+                    Some(stored) if stored.version != user.version - 1 => {
+                        return Err(version_conflict(user, stored));
+                    }
+                    Some(_) => {}
                 }
-            }
-        }
 
-        users.insert(user.id, user.clone());
-        Ok(())
+                // The user may keep its own username and email
+                check_unique(users.values().filter(|existing| existing.id != user.id), user)?;
+
+                Ok((Some(WalRecord::Upsert(Box::new(user.clone()))), ()))
+            })
+            .await
     }
 
     #[instrument(skip(self), fields(user_id = %id))]
     async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
         info!("Deleting user in in-memory repository");
         
-        let mut users = self.users.write().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire write lock: {}", e))
-        })?;
-
-        if users.remove(id).is_none() {
-            return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
-        }
-
-        Ok(())
+        self.users
+            .update(|users| {
+                if !users.contains_key(id) {
+                    return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
+                }
+                Ok((Some(WalRecord::Delete(*id)), ()))
+            })
+            .await
     }
 
     #[instrument(skip(self), fields(user_id = %id))]
     async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
         info!("Finding user by ID in in-memory repository");
         
-        let users = self.users.read().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire read lock: {}", e))
-        })?;
+        let users = self.read_users()?;
+
+        Ok(users.get(id).filter(|user| !user.is_deleted()).cloned())
+    }
 
-        Ok(users.get(id).cloned())
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        info!("Finding user by ID, including deleted users, in in-memory repository");
+        Ok(self.read_users()?.get(id).cloned())
     }
 
     #[instrument(skip(self), fields(username = %username))]
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
         info!("Finding user by username in in-memory repository");
         
-        let users = self.users.read().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire read lock: {}", e))
-        })?;
+        let users = self.read_users()?;
 
         Ok(users
             .values()
-            .find(|user| user.username == username)
+            .find(|user| user.username == username && !user.is_deleted())
             .cloned())
     }
 
@@ -127,13 +211,11 @@ impl UserRepository for InMemoryUserRepository {
     async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
         info!("Finding user by email in in-memory repository");
         
-        let users = self.users.read().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire read lock: {}", e))
-        })?;
+        let users = self.read_users()?;
 
         Ok(users
             .values()
-            .find(|user| user.email == email)
+            .find(|user| user.email == email && !user.is_deleted())
             .cloned())
     }
 
@@ -141,11 +223,34 @@ impl UserRepository for InMemoryUserRepository {
     async fn find_all(&self) -> Result<Vec<User>, DomainError> {
         info!("Finding all users in in-memory repository");
         
-        let users = self.users.read().map_err(|e| {
-            DomainError::RepositoryError(format!("Failed to acquire read lock: {}", e))
-        })?;
+        let users = self.read_users()?;
+
+        Ok(users.values().filter(|user| !user.is_deleted()).cloned().collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        info!("Finding all users, including deleted users, in in-memory repository");
+        Ok(self.read_users()?.values().cloned().collect())
+    }
+
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        info!("Purging deleted users in in-memory repository");
+
+        self.users
+            .update(|users| {
+                let purged: Vec<Uuid> = users
+                    .values()
+                    .filter(|user| user.deleted_at.is_some_and(|deleted_at| deleted_at < deleted_before))
+                    .map(|user| user.id)
+                    .collect();
+                let record = (!purged.is_empty())
+                    .then(|| WalRecord::Batch(purged.iter().copied().map(WalRecord::Delete).collect()));
 
-        Ok(users.values().cloned().collect())
+                Ok((record, purged))
+            })
+            .await
     }
 }
 
@@ -1,5 +1,48 @@
+mod cache;
+#[cfg(any(test, feature = "testing"))]
+pub mod conformance;
+mod factory;
 mod postgres;
 mod memory;
+mod sqlite;
 
+pub use cache::*;
+pub use factory::*;
 pub use postgres::*;
 pub use memory::*;
+pub use sqlite::*;
+
+use domain::entities::User;
+use domain::errors::DomainError;
+
+/// Maps an error from a SQL write of `user`, reporting unique constraint
+/// violations the same way as `InMemoryUserRepository`.
+pub(crate) fn map_write_error(error: sqlx::Error, user: &User) -> DomainError {
+    if let Some(db_error) = error.as_database_error() {
+        if db_error.is_unique_violation() {
+            // Postgres names the constraint (users_email_key), SQLite only the column (users.email)
+            let on_email = db_error
+                .constraint()
+                .unwrap_or_else(|| db_error.message())
+                .contains("email");
+
+            return if on_email {
+                DomainError::ValidationError(format!("Email {} already exists", user.email))
+            } else {
+                DomainError::ValidationError(format!("Username {} already exists", user.username))
+            };
+        }
+    }
+
+    DomainError::RepositoryError(format!("Database error: {}", error))
+}
+
+/// Error for an update of `user` that was based on an older version than `stored`.
+pub(crate) fn version_conflict(user: &User, stored: &User) -> DomainError {
+    DomainError::Conflict(format!(
+        "User with ID {} was modified concurrently (expected version {}, found {})",
+        user.id,
+        user.version - 1,
+        stored.version
+    ))
+}
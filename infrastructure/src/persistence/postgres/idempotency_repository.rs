@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{IdempotencyRecord, StoredResponse};
+use domain::errors::DomainError;
+use domain::repositories::IdempotencyRepository;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use tracing::{debug, instrument};
+
+pub struct PostgresIdempotencyRepository {
+    pool: PgPool,
+}
+
+impl PostgresIdempotencyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_row(row: &PgRow) -> Result<IdempotencyRecord, DomainError> {
+    let status: Option<i32> = row.get("status");
+
+    let response = match status {
+        Some(status) => {
+            let headers: serde_json::Value = row.get("headers");
+            let headers = serde_json::from_value(headers)
+                .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+            Some(StoredResponse {
+                status: status as u16,
+                headers,
+                body: row.get("body"),
+            })
+        }
+        None => None,
+    };
+
+    Ok(IdempotencyRecord {
+        key: row.get("key"),
+        fingerprint: row.get("fingerprint"),
+        response,
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+    })
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+#[async_trait]
+impl IdempotencyRepository for PostgresIdempotencyRepository {
+    #[instrument(skip(self, record), fields(key = %record.key))]
+    async fn try_begin(
+        &self,
+        record: &IdempotencyRecord,
+        abandoned_before: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyRecord>, DomainError> {
+        loop {
+            sqlx::query(
+                r#"
+                DELETE FROM idempotency_keys
+                WHERE key = $1 AND (expires_at <= $2 OR (status IS NULL AND created_at < $3))
+                "#,
+            )
+            .bind(&record.key)
+            .bind(record.created_at)
+            .bind(abandoned_before)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+            let inserted = sqlx::query(
+                r#"
+                INSERT INTO idempotency_keys (key, fingerprint, created_at, expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (key) DO NOTHING
+                "#,
+            )
+            .bind(&record.key)
+            .bind(&record.fingerprint)
+            .bind(record.created_at)
+            .bind(record.expires_at)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+            if inserted.rows_affected() == 1 {
+                return Ok(None);
+            }
+
+            let existing = sqlx::query(
+                "SELECT key, fingerprint, status, headers, body, created_at, expires_at FROM idempotency_keys WHERE key = $1",
+            )
+            .bind(&record.key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+            match existing {
+                Some(row) => return map_row(&row).map(Some),
+                // Removed since the insert conflicted; try to claim it again
+                None => debug!("Idempotency key disappeared, retrying"),
+            }
+        }
+    }
+
+    #[instrument(skip(self, response))]
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), DomainError> {
+        let headers = serde_json::to_value(&response.headers)
+            .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
+
+        sqlx::query("UPDATE idempotency_keys SET status = $1, headers = $2, body = $3 WHERE key = $4")
+            .bind(response.status as i32)
+            .bind(headers)
+            .bind(&response.body)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn remove(&self, key: &str) -> Result<(), DomainError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= $1")
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{FailedLogins, LoginAttemptKey, LoginAttemptScope};
+use domain::errors::DomainError;
+use domain::repositories::LoginAttemptRepository;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use tracing::instrument;
+
+pub struct PostgresLoginAttemptRepository {
+    pool: PgPool,
+}
+
+impl PostgresLoginAttemptRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_row(key: LoginAttemptKey, row: &PgRow) -> FailedLogins {
+    let failures: i32 = row.get("failures");
+
+    FailedLogins {
+        key,
+        failures: failures as u32,
+        first_failed_at: row.get("first_failed_at"),
+        last_failed_at: row.get("last_failed_at"),
+    }
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+#[async_trait]
+impl LoginAttemptRepository for PostgresLoginAttemptRepository {
+    #[instrument(skip(self), fields(scope = %key.scope))]
+    async fn find(&self, key: &LoginAttemptKey) -> Result<Option<FailedLogins>, DomainError> {
+        let row = sqlx::query(
+            r#"
+            SELECT failures, first_failed_at, last_failed_at
+            FROM failed_logins
+            WHERE scope = $1 AND subject = $2
+            "#,
+        )
+        .bind(key.scope.as_str())
+        .bind(&key.subject)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(row.map(|row| map_row(key.clone(), &row)))
+    }
+
+    #[instrument(skip(self), fields(scope = %key.scope))]
+    async fn record_failure(
+        &self,
+        key: &LoginAttemptKey,
+        failed_at: DateTime<Utc>,
+        reset_before: DateTime<Utc>,
+    ) -> Result<FailedLogins, DomainError> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO failed_logins (scope, subject, failures, first_failed_at, last_failed_at)
+            VALUES ($1, $2, 1, $3, $3)
+            ON CONFLICT (scope, subject) DO UPDATE SET
+                failures = CASE WHEN failed_logins.last_failed_at < $4 THEN 1
+                                ELSE failed_logins.failures + 1 END,
+                first_failed_at = CASE WHEN failed_logins.last_failed_at < $4 THEN $3
+                                       ELSE failed_logins.first_failed_at END,
+                last_failed_at = $3
+            RETURNING failures, first_failed_at, last_failed_at
+            "#,
+        )
+        .bind(key.scope.as_str())
+        .bind(&key.subject)
+        .bind(failed_at)
+        .bind(reset_before)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(map_row(key.clone(), &row))
+    }
+
+    #[instrument(skip(self), fields(scope = %key.scope))]
+    async fn reset(&self, key: &LoginAttemptKey) -> Result<(), DomainError> {
+        sqlx::query("DELETE FROM failed_logins WHERE scope = $1 AND subject = $2")
+            .bind(key.scope.as_str())
+            .bind(&key.subject)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn find_with_failures(
+        &self,
+        scope: LoginAttemptScope,
+        min_failures: u32,
+        reset_before: DateTime<Utc>,
+    ) -> Result<Vec<FailedLogins>, DomainError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT subject, failures, first_failed_at, last_failed_at
+            FROM failed_logins
+            WHERE scope = $1 AND failures >= $2 AND last_failed_at >= $3
+            ORDER BY last_failed_at DESC
+            "#,
+        )
+        .bind(scope.as_str())
+        .bind(min_failures as i32)
+        .bind(reset_before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let key = LoginAttemptKey {
+                    scope,
+                    subject: row.get("subject"),
+                };
+                map_row(key, row)
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_stale(&self, reset_before: DateTime<Utc>) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM failed_logins WHERE last_failed_at < $1")
+            .bind(reset_before)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use domain::entities::TotpEnrollment;
+use domain::errors::DomainError;
+use domain::repositories::MfaRepository;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use tracing::instrument;
+use uuid::Uuid;
+
+pub struct PostgresMfaRepository {
+    pool: PgPool,
+}
+
+impl PostgresMfaRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_row(row: &PgRow) -> TotpEnrollment {
+    TotpEnrollment {
+        user_id: row.get("user_id"),
+        secret: row.get("secret"),
+        created_at: row.get("created_at"),
+        confirmed_at: row.get("confirmed_at"),
+        last_used_step: row.get("last_used_step"),
+    }
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+#[async_trait]
+impl MfaRepository for PostgresMfaRepository {
+    #[instrument(skip(self, enrollment), fields(user_id = %enrollment.user_id))]
+    async fn save(&self, enrollment: &TotpEnrollment) -> Result<(), DomainError> {
+        sqlx::query(
+            r#"
+            INSERT INTO totp_enrollments (user_id, secret, created_at, confirmed_at, last_used_step)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id) DO UPDATE SET
+                secret = EXCLUDED.secret,
+                created_at = EXCLUDED.created_at,
+                confirmed_at = EXCLUDED.confirmed_at,
+                last_used_step = EXCLUDED.last_used_step
+            "#,
+        )
+        .bind(enrollment.user_id)
+        .bind(&enrollment.secret)
+        .bind(enrollment.created_at)
+        .bind(enrollment.confirmed_at)
+        .bind(enrollment.last_used_step)
+        .execute(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn find(&self, user_id: &Uuid) -> Result<Option<TotpEnrollment>, DomainError> {
+        let row = sqlx::query(
+            "SELECT user_id, secret, created_at, confirmed_at, last_used_step FROM totp_enrollments WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, user_id: &Uuid) -> Result<bool, DomainError> {
+        let mut transaction = self.pool.begin().await.map_err(database_error)?;
+
+        sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(database_error)?;
+        let deleted = sqlx::query("DELETE FROM totp_enrollments WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(database_error)?;
+
+        transaction.commit().await.map_err(database_error)?;
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn record_step(&self, user_id: &Uuid, step: i64) -> Result<bool, DomainError> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE totp_enrollments SET last_used_step = $1
+            WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+            "#,
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(updated.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self, code_hashes))]
+    async fn replace_recovery_codes(&self, user_id: &Uuid, code_hashes: &[String]) -> Result<(), DomainError> {
+        let mut transaction = self.pool.begin().await.map_err(database_error)?;
+
+        sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(database_error)?;
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO mfa_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&mut *transaction)
+                .await
+                .map_err(database_error)?;
+        }
+
+        transaction.commit().await.map_err(database_error)
+    }
+
+    #[instrument(skip(self, code_hash))]
+    async fn consume_recovery_code(&self, user_id: &Uuid, code_hash: &str) -> Result<bool, DomainError> {
+        let deleted = sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_id = $1 AND code_hash = $2")
+            .bind(user_id)
+            .bind(code_hash)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn count_recovery_codes(&self, user_id: &Uuid) -> Result<u64, DomainError> {
+        let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM mfa_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(database_error)?
+            .get("count");
+
+        Ok(count as u64)
+    }
+}
@@ -1,5 +1,19 @@
+mod email_verification_repository;
+mod idempotency_repository;
+mod login_attempt_repository;
+mod mfa_repository;
+mod password_reset_repository;
+mod rate_limit_repository;
+mod unit_of_work;
 mod user_repository;
 
+pub use email_verification_repository::*;
+pub use idempotency_repository::*;
+pub use login_attempt_repository::*;
+pub use mfa_repository::*;
+pub use password_reset_repository::*;
+pub use rate_limit_repository::*;
+pub use unit_of_work::*;
 pub use user_repository::*;
 
 use crate::config::ConfigProvider;
@@ -13,7 +27,7 @@ pub async fn create_postgres_pool(
     config_provider: Arc<dyn ConfigProvider>,
 ) -> Result<PgPool, InfrastructureError> {
     let config = config_provider.get_config();
-    let database_url = &config.database.url;
+    let database_url = config.database.url.expose();
     let max_connections = config.database.max_connections;
 
     info!(
@@ -29,7 +43,7 @@ pub async fn create_postgres_pool(
 
     // Run migrations
     info!("Running database migrations");
-    sqlx::migrate!("./migrations")
+    sqlx::migrate!("../migrations")
         .run(&pool)
         .await
         .map_err(|e| InfrastructureError::DatabaseError(format!("Migration error: {}", e)))?;
@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::PasswordResetToken;
+use domain::errors::DomainError;
+use domain::repositories::PasswordResetTokenRepository;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use tracing::instrument;
+use uuid::Uuid;
+
+pub struct PostgresPasswordResetTokenRepository {
+    pool: PgPool,
+}
+
+impl PostgresPasswordResetTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_row(row: &PgRow) -> PasswordResetToken {
+    PasswordResetToken {
+        token_hash: row.get("token_hash"),
+        user_id: row.get("user_id"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        used_at: row.get("used_at"),
+    }
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+#[async_trait]
+impl PasswordResetTokenRepository for PostgresPasswordResetTokenRepository {
+    #[instrument(skip(self, token), fields(user_id = %token.user_id))]
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), DomainError> {
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at, used_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (token_hash) DO NOTHING
+            "#,
+        )
+        .bind(&token.token_hash)
+        .bind(token.user_id)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        if inserted.rows_affected() == 0 {
+            return Err(DomainError::Conflict("Password reset token already exists".to_string()));
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, DomainError> {
+        let row = sqlx::query(
+            "SELECT token_hash, user_id, created_at, expires_at, used_at FROM password_reset_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn consume(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<PasswordResetToken>, DomainError> {
+        let row = sqlx::query(
+            r#"
+            UPDATE password_reset_tokens SET used_at = $1
+            WHERE token_hash = $2 AND used_at IS NULL AND expires_at > $1
+            RETURNING token_hash, user_id, created_at, expires_at, used_at
+            "#,
+        )
+        .bind(now)
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_for_user(&self, user_id: &Uuid) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{RateLimitDecision, RateLimitQuota};
+use domain::errors::DomainError;
+use domain::repositories::RateLimitRepository;
+use sqlx::PgPool;
+use tracing::instrument;
+
+/// Rate limit state shared by every instance using the same database.
+pub struct PostgresRateLimitRepository {
+    pool: PgPool,
+}
+
+impl PostgresRateLimitRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+#[async_trait]
+impl RateLimitRepository for PostgresRateLimitRepository {
+    #[instrument(skip(self, quota))]
+    async fn acquire(
+        &self,
+        key: &str,
+        quota: &RateLimitQuota,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitDecision, DomainError> {
+        // The same check as `RateLimitQuota::check`, in one statement so that
+        // concurrent requests from several instances cannot both pass
+        let allowed_tat: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"
+            INSERT INTO rate_limits (key, tat)
+            VALUES ($1, $2 + $3)
+            ON CONFLICT (key) DO UPDATE SET tat = GREATEST(rate_limits.tat, $2) + $3
+            WHERE GREATEST(rate_limits.tat, $2) + $3 - $2 <= $4
+            RETURNING tat
+            "#,
+        )
+        .bind(key)
+        .bind(now)
+        .bind(quota.emission_interval())
+        .bind(quota.tolerance())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        if let Some(tat) = allowed_tat {
+            return Ok(quota.decision(tat, now, true));
+        }
+
+        let tat: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT tat FROM rate_limits WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(quota.decision(tat.unwrap_or(now).max(now), now, false))
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM rate_limits WHERE tat <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::User;
+use domain::errors::DomainError;
+use domain::repositories::{RepositoryTransaction, UnitOfWork, UserRepository};
+use sqlx::{PgPool, Postgres};
+use tokio::sync::Mutex;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use super::user_repository::{
+    delete_user, find_all_users, find_user_by, find_user_by_id, insert_user, purge_deleted_users,
+    update_user,
+};
+
+pub struct PostgresUnitOfWork {
+    pool: PgPool,
+}
+
+impl PostgresUnitOfWork {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for PostgresUnitOfWork {
+    async fn begin(&self) -> Result<Box<dyn RepositoryTransaction>, DomainError> {
+        let transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+        Ok(Box::new(PostgresTransaction {
+            transaction: Mutex::new(transaction),
+        }))
+    }
+}
+
+/// Repositories running on one database transaction.
+///
+/// sqlx rolls the transaction back when it is dropped without a commit.
+pub struct PostgresTransaction {
+    // Statements on one connection run one at a time
+    transaction: Mutex<sqlx::Transaction<'static, Postgres>>,
+}
+
+#[async_trait]
+impl RepositoryTransaction for PostgresTransaction {
+    fn users(&self) -> &dyn UserRepository {
+        self
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DomainError> {
+        self.transaction
+            .into_inner()
+            .commit()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DomainError> {
+        self.transaction
+            .into_inner()
+            .rollback()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgresTransaction {
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        info!("Creating user in PostgreSQL transaction");
+        insert_user(&mut **self.transaction.lock().await, user).await
+    }
+
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        info!("Updating user in PostgreSQL transaction");
+        update_user(&mut **self.transaction.lock().await, user).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        info!("Deleting user in PostgreSQL transaction");
+        delete_user(&mut **self.transaction.lock().await, id).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        find_user_by_id(&mut **self.transaction.lock().await, id, false).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        find_user_by_id(&mut **self.transaction.lock().await, id, true).await
+    }
+
+    #[instrument(skip(self), fields(username = %username))]
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        find_user_by(&mut **self.transaction.lock().await, "username", username).await
+    }
+
+    #[instrument(skip(self), fields(email = %email))]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        find_user_by(&mut **self.transaction.lock().await, "email", email).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        find_all_users(&mut **self.transaction.lock().await, false).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        find_all_users(&mut **self.transaction.lock().await, true).await
+    }
+
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        info!("Purging deleted users in PostgreSQL transaction");
+        purge_deleted_users(&mut **self.transaction.lock().await, deleted_before).await
+    }
+}
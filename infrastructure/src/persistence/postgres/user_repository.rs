@@ -1,14 +1,17 @@
 use async_trait::async_trait;
-use domain::entities::role::{Role, RoleName};
-use domain::entities::User;
+use chrono::{DateTime, Utc};
+use domain::entities::{Role, RoleName};
+use domain::entities::{User, UserStatus};
 use domain::errors::DomainError;
 use domain::repositories::UserRepository;
-use sqlx::{PgPool, Row};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgExecutor, PgPool, Row};
 use std::collections::HashSet;
-use std::str::FromStr;
 use tracing::{info, instrument};
 use uuid::Uuid;
 
+use crate::persistence::{map_write_error, version_conflict};
+
 pub struct PostgresUserRepository {
     pool: PgPool,
 }
@@ -19,268 +22,306 @@ impl PostgresUserRepository {
     }
 }
 
+const USER_COLUMNS: &str =
+    "id, username, email, password_hash, role_name, role_permissions, created_at, updated_at, version, deleted_at, status, status_reason, status_changed_at, password_changed_at, password_history, token_version, email_verified_at";
+
+fn map_row(row: &PgRow) -> Result<User, DomainError> {
+    let role_name: serde_json::Value = row.get("role_name");
+    let role_name = serde_json::from_value::<RoleName>(role_name)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let permissions: serde_json::Value = row.get("role_permissions");
+    let permissions = serde_json::from_value::<HashSet<String>>(permissions)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let role = Role {
+        name: role_name,
+        permissions,
+    };
+
+    let password_history: serde_json::Value = row.get("password_history");
+    let password_history = serde_json::from_value::<Vec<String>>(password_history)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let status: String = row.get("status");
+    let status = status
+        .parse::<UserStatus>()
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    Ok(User {
+        id: row.get("id"),
+        username: row.get("username"),
+        email: row.get("email"),
+        password_hash: row.get("password_hash"),
+        role,
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
+        deleted_at: row.get("deleted_at"),
+        status,
+        status_reason: row.get("status_reason"),
+        status_changed_at: row.get("status_changed_at"),
+        password_changed_at: row.get("password_changed_at"),
+        password_history,
+        token_version: row.get("token_version"),
+        email_verified_at: row.get("email_verified_at"),
+    })
+}
+
+fn serialize_role(role: &Role) -> Result<(serde_json::Value, serde_json::Value), DomainError> {
+    let role_name = serde_json::to_value(&role.name)
+        .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
+
+    let permissions = serde_json::to_value(&role.permissions)
+        .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
+
+    Ok((role_name, permissions))
+}
+
+fn serialize_password_history(history: &[String]) -> Result<serde_json::Value, DomainError> {
+    serde_json::to_value(history).map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))
+}
+
+// The queries take any executor so that `PostgresTransaction` runs the same
+// statements on its transaction that the repository runs on the pool.
+
+pub(super) async fn insert_user<'e>(executor: impl PgExecutor<'e>, user: &User) -> Result<(), DomainError> {
+    let (role_name, permissions) = serialize_role(&user.role)?;
+    let password_history = serialize_password_history(&user.password_history)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO users (id, username, email, password_hash, role_name, role_permissions, created_at, updated_at, version, deleted_at,
+                           status, status_reason, status_changed_at, password_changed_at, password_history, token_version,
+                           email_verified_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+        "#,
+    )
+    .bind(user.id)
+    .bind(&user.username)
+    .bind(&user.email)
+    .bind(&user.password_hash)
+    .bind(role_name)
+    .bind(permissions)
+    .bind(user.created_at)
+    .bind(user.updated_at)
+    .bind(user.version)
+    .bind(user.deleted_at)
+    .bind(user.status.as_str())
+    .bind(&user.status_reason)
+    .bind(user.status_changed_at)
+    .bind(user.password_changed_at)
+    .bind(password_history)
+    .bind(user.token_version)
+    .bind(user.email_verified_at)
+    .execute(executor)
+    .await
+    .map_err(|e| map_write_error(e, user))?;
+
+    Ok(())
+}
+
+/// Needs a connection rather than any executor: telling a conflict from a
+/// missing user takes a second query.
+pub(super) async fn update_user(connection: &mut PgConnection, user: &User) -> Result<(), DomainError> {
+    let (role_name, permissions) = serialize_role(&user.role)?;
+    let password_history = serialize_password_history(&user.password_history)?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET username = $1, email = $2, password_hash = $3,
+            role_name = $4, role_permissions = $5, updated_at = $6, version = $7, deleted_at = $8,
+            status = $9, status_reason = $10, status_changed_at = $11, password_changed_at = $12, password_history = $13,
+            token_version = $14, email_verified_at = $15
+        WHERE id = $16 AND version = $7 - 1
+        "#,
+    )
+    .bind(&user.username)
+    .bind(&user.email)
+    .bind(&user.password_hash)
+    .bind(role_name)
+    .bind(permissions)
+    .bind(user.updated_at)
+    .bind(user.version)
+    .bind(user.deleted_at)
+    .bind(user.status.as_str())
+    .bind(&user.status_reason)
+    .bind(user.status_changed_at)
+    .bind(user.password_changed_at)
+    .bind(password_history)
+    .bind(user.token_version)
+    .bind(user.email_verified_at)
+    .bind(user.id)
+    .execute(&mut *connection)
+    .await
+    .map_err(|e| map_write_error(e, user))?;
+
+    if result.rows_affected() == 0 {
+        return Err(match find_user_by_id(connection, &user.id, true).await? {
+            Some(stored) => version_conflict(user, &stored),
+            None => DomainError::NotFound(format!("User with ID {} not found", user.id)),
+        });
+    }
+
+    Ok(())
+}
+
+pub(super) async fn delete_user<'e>(executor: impl PgExecutor<'e>, id: &Uuid) -> Result<(), DomainError> {
+    let result = sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(id)
+        .execute(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
+    }
+
+    Ok(())
+}
+
+pub(super) async fn find_user_by_id<'e>(
+    executor: impl PgExecutor<'e>,
+    id: &Uuid,
+    include_deleted: bool,
+) -> Result<Option<User>, DomainError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM users WHERE id = $1{}",
+        USER_COLUMNS,
+        deleted_filter(include_deleted)
+    ))
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    row.as_ref().map(map_row).transpose()
+}
+
+pub(super) async fn find_user_by<'e>(
+    executor: impl PgExecutor<'e>,
+    column: &str,
+    value: &str,
+) -> Result<Option<User>, DomainError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM users WHERE {} = $1{}",
+        USER_COLUMNS,
+        column,
+        deleted_filter(false)
+    ))
+        .bind(value)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    row.as_ref().map(map_row).transpose()
+}
+
+pub(super) async fn find_all_users<'e>(
+    executor: impl PgExecutor<'e>,
+    include_deleted: bool,
+) -> Result<Vec<User>, DomainError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM users WHERE TRUE{}",
+        USER_COLUMNS,
+        deleted_filter(include_deleted)
+    ))
+        .fetch_all(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    rows.iter().map(map_row).collect()
+}
+
+pub(super) async fn purge_deleted_users<'e>(
+    executor: impl PgExecutor<'e>,
+    deleted_before: DateTime<Utc>,
+) -> Result<Vec<Uuid>, DomainError> {
+    let rows = sqlx::query("DELETE FROM users WHERE deleted_at < $1 RETURNING id")
+        .bind(deleted_before)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    Ok(rows.iter().map(|row| row.get("id")).collect())
+}
+
+/// Condition appended to a `WHERE` clause to skip soft-deleted users
+fn deleted_filter(include_deleted: bool) -> &'static str {
+    if include_deleted {
+        ""
+    } else {
+        " AND deleted_at IS NULL"
+    }
+}
+
 #[async_trait]
 impl UserRepository for PostgresUserRepository {
     #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
     async fn create(&self, user: &User) -> Result<(), DomainError> {
         info!("Creating user in PostgreSQL repository");
-        
-        let role_name = serde_json::to_value(&user.role.name)
-            .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
-            
-        let permissions = serde_json::to_value(&user.role.permissions)
-            .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
-
-        sqlx::query(
-            r#"
-            INSERT INTO users (id, username, email, password_hash, role_name, role_permissions, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            "#,
-        )
-        .bind(user.id)
-        .bind(&user.username)
-        .bind(&user.email)
-        .bind(&user.password_hash)
-        .bind(role_name)
-        .bind(permissions)
-        .bind(user.created_at)
-        .bind(user.updated_at)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
-
-        Ok(())
+        insert_user(&self.pool, user).await
     }
 
     #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
     async fn update(&self, user: &User) -> Result<(), DomainError> {
         info!("Updating user in PostgreSQL repository");
-        
-        let role_name = serde_json::to_value(&user.role.name)
-            .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
-            
-        let permissions = serde_json::to_value(&user.role.permissions)
-            .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
-
-        let result = sqlx::query(
-            r#"
-            UPDATE users
-            SET username = $1, email = $2, password_hash = $3, 
-                role_name = $4, role_permissions = $5, updated_at = $6
-            WHERE id = $7
-            "#,
-        )
-        .bind(&user.username)
-        .bind(&user.email)
-        .bind(&user.password_hash)
-        .bind(role_name)
-        .bind(permissions)
-        .bind(user.updated_at)
-        .bind(user.id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
 
-        if result.rows_affected() == 0 {
-            return Err(DomainError::NotFound(format!("User with ID {} not found", user.id)));
-        }
+        let mut connection = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
 
-        Ok(())
+        update_user(&mut connection, user).await
     }
 
     #[instrument(skip(self), fields(user_id = %id))]
     async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
         info!("Deleting user in PostgreSQL repository");
-        
-        let result = sqlx::query("DELETE FROM users WHERE id = $1")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
-
-        if result.rows_affected() == 0 {
-            return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
-        }
-
-        Ok(())
+        delete_user(&self.pool, id).await
     }
 
     #[instrument(skip(self), fields(user_id = %id))]
     async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
         info!("Finding user by ID in PostgreSQL repository");
-        
-        let user = sqlx::query(
-            r#"
-            SELECT id, username, email, password_hash, role_name, role_permissions, created_at, updated_at
-            FROM users
-            WHERE id = $1
-            "#,
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+        find_user_by_id(&self.pool, id, false).await
+    }
 
-        match user {
-            Some(row) => {
-                let role_name: String = row.get("role_name");
-                let role_name = serde_json::from_str::<RoleName>(&role_name)
-                    .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-                let permissions: String = row.get("role_permissions");
-                let permissions = serde_json::from_str::<HashSet<String>>(&permissions)
-                    .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-                let role = Role {
-                    name: role_name,
-                    permissions,
-                };
-
-                Ok(Some(User {
-                    id: row.get("id"),
-                    username: row.get("username"),
-                    email: row.get("email"),
-                    password_hash: row.get("password_hash"),
-                    role,
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                }))
-            }
-            None => Ok(None),
-        }
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        info!("Finding user by ID, including deleted users, in PostgreSQL repository");
+        find_user_by_id(&self.pool, id, true).await
     }
 
     #[instrument(skip(self), fields(username = %username))]
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
         info!("Finding user by username in PostgreSQL repository");
-        
-        let user = sqlx::query(
-            r#"
-            SELECT id, username, email, password_hash, role_name, role_permissions, created_at, updated_at
-            FROM users
-            WHERE username = $1
-            "#,
-        )
-        .bind(username)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
-
-        match user {
-            Some(row) => {
-                let role_name: String = row.get("role_name");
-                let role_name = serde_json::from_str::<RoleName>(&role_name)
-                    .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-                let permissions: String = row.get("role_permissions");
-                let permissions = serde_json::from_str::<HashSet<String>>(&permissions)
-                    .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-                let role = Role {
-                    name: role_name,
-                    permissions,
-                };
-
-                Ok(Some(User {
-                    id: row.get("id"),
-                    username: row.get("username"),
-                    email: row.get("email"),
-                    password_hash: row.get("password_hash"),
-                    role,
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                }))
-            }
-            None => Ok(None),
-        }
+        find_user_by(&self.pool, "username", username).await
     }
 
     #[instrument(skip(self), fields(email = %email))]
     async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
         info!("Finding user by email in PostgreSQL repository");
-        
-        let user = sqlx::query(
-            r#"
-            SELECT id, username, email, password_hash, role_name, role_permissions, created_at, updated_at
-            FROM users
-            WHERE email = $1
-            "#,
-        )
-        .bind(email)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
-
-        match user {
-            Some(row) => {
-                let role_name: String = row.get("role_name");
-                let role_name = serde_json::from_str::<RoleName>(&role_name)
-                    .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-                let permissions: String = row.get("role_permissions");
-                let permissions = serde_json::from_str::<HashSet<String>>(&permissions)
-                    .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-                let role = Role {
-                    name: role_name,
-                    permissions,
-                };
-
-                Ok(Some(User {
-                    id: row.get("id"),
-                    username: row.get("username"),
-                    email: row.get("email"),
-                    password_hash: row.get("password_hash"),
-                    role,
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                }))
-            }
-            None => Ok(None),
-        }
+        find_user_by(&self.pool, "email", email).await
     }
 
     #[instrument(skip(self))]
     async fn find_all(&self) -> Result<Vec<User>, DomainError> {
         info!("Finding all users in PostgreSQL repository");
-        
-        let rows = sqlx::query(
-            r#"
-            SELECT id, username, email, password_hash, role_name, role_permissions, created_at, updated_at
-            FROM users
-            "#,
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+        find_all_users(&self.pool, false).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        info!("Finding all users, including deleted users, in PostgreSQL repository");
+        find_all_users(&self.pool, true).await
+    }
 
-        let mut users = Vec::with_capacity(rows.len());
-
-        for row in rows {
-            let role_name: String = row.get("role_name");
-            let role_name = serde_json::from_str::<RoleName>(&role_name)
-                .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-            let permissions: String = row.get("role_permissions");
-            let permissions = serde_json::from_str::<HashSet<String>>(&permissions)
-                .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
-
-            let role = Role {
-                name: role_name,
-                permissions,
-            };
-
-            users.push(User {
-                id: row.get("id"),
-                username: row.get("username"),
-                email: row.get("email"),
-                password_hash: row.get("password_hash"),
-                role,
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            });
-        }
-
-        Ok(users)
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        info!("Purging deleted users in PostgreSQL repository");
+        purge_deleted_users(&self.pool, deleted_before).await
     }
 }
@@ -0,0 +1,58 @@
+mod email_verification_repository;
+mod idempotency_repository;
+mod login_attempt_repository;
+mod mfa_repository;
+mod password_reset_repository;
+mod rate_limit_repository;
+mod unit_of_work;
+mod user_repository;
+
+pub use email_verification_repository::*;
+pub use idempotency_repository::*;
+pub use login_attempt_repository::*;
+pub use mfa_repository::*;
+pub use password_reset_repository::*;
+pub use rate_limit_repository::*;
+pub use unit_of_work::*;
+pub use user_repository::*;
+
+use crate::config::SqliteConfig;
+use crate::errors::InfrastructureError;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::SqlitePool;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+
+pub async fn create_sqlite_pool(config: &SqliteConfig) -> Result<SqlitePool, InfrastructureError> {
+    let journal_mode = SqliteJournalMode::from_str(&config.journal_mode).map_err(|e| {
+        InfrastructureError::ConfigurationError(format!("Invalid SQLite journal mode: {}", e))
+    })?;
+
+    info!(
+        "Creating SQLite connection pool for {} with journal mode {:?}",
+        config.path, journal_mode
+    );
+
+    let options = SqliteConnectOptions::new()
+        .filename(&config.path)
+        .create_if_missing(true)
+        .journal_mode(journal_mode)
+        .busy_timeout(Duration::from_millis(config.busy_timeout_ms));
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .connect_with(options)
+        .await
+        .map_err(|e| InfrastructureError::DatabaseError(format!("Database connection error: {}", e)))?;
+
+    // Run migrations
+    info!("Running SQLite migrations");
+    sqlx::migrate!("../migrations/sqlite")
+        .run(&pool)
+        .await
+        .map_err(|e| InfrastructureError::DatabaseError(format!("Migration error: {}", e)))?;
+
+    info!("SQLite connection pool created successfully");
+    Ok(pool)
+}
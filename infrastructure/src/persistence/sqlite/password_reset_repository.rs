@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::PasswordResetToken;
+use domain::errors::DomainError;
+use domain::repositories::PasswordResetTokenRepository;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use tracing::instrument;
+use uuid::Uuid;
+
+pub struct SqlitePasswordResetTokenRepository {
+    pool: SqlitePool,
+}
+
+impl SqlitePasswordResetTokenRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_row(row: &SqliteRow) -> Result<PasswordResetToken, DomainError> {
+    let user_id: String = row.get("user_id");
+    let user_id = Uuid::parse_str(&user_id)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    Ok(PasswordResetToken {
+        token_hash: row.get("token_hash"),
+        user_id,
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        used_at: row.get("used_at"),
+    })
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+#[async_trait]
+impl PasswordResetTokenRepository for SqlitePasswordResetTokenRepository {
+    #[instrument(skip(self, token), fields(user_id = %token.user_id))]
+    async fn create(&self, token: &PasswordResetToken) -> Result<(), DomainError> {
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at, used_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (token_hash) DO NOTHING
+            "#,
+        )
+        .bind(&token.token_hash)
+        .bind(token.user_id.to_string())
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        if inserted.rows_affected() == 0 {
+            return Err(DomainError::Conflict("Password reset token already exists".to_string()));
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, DomainError> {
+        let row = sqlx::query(
+            "SELECT token_hash, user_id, created_at, expires_at, used_at FROM password_reset_tokens WHERE token_hash = ?",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        row.as_ref().map(map_row).transpose()
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn consume(&self, token_hash: &str, now: DateTime<Utc>) -> Result<Option<PasswordResetToken>, DomainError> {
+        // Timestamps are stored as RFC 3339 UTC text, which sorts chronologically.
+        // RETURNING is not used, as in the login attempt repository.
+        let updated = sqlx::query(
+            r#"
+            UPDATE password_reset_tokens SET used_at = ?
+            WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?
+            "#,
+        )
+        .bind(now)
+        .bind(token_hash)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(database_error)?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.find(token_hash).await
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_for_user(&self, user_id: &Uuid) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use domain::entities::{RateLimitDecision, RateLimitQuota};
+use domain::errors::DomainError;
+use domain::repositories::RateLimitRepository;
+use sqlx::SqlitePool;
+use tracing::instrument;
+
+/// Rate limit state shared by every process using the same database file.
+///
+/// Times are stored as microseconds since the Unix epoch, since SQLite has no
+/// timestamp type to compute with.
+pub struct SqliteRateLimitRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRateLimitRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn database_error(e: sqlx::Error) -> DomainError {
+    DomainError::RepositoryError(format!("Database error: {}", e))
+}
+
+fn micros(duration: Duration) -> i64 {
+    duration.num_microseconds().unwrap_or(i64::MAX)
+}
+
+fn from_micros(micros: i64) -> Result<DateTime<Utc>, DomainError> {
+    DateTime::from_timestamp_micros(micros)
+        .ok_or_else(|| DomainError::RepositoryError(format!("Invalid rate limit time: {}", micros)))
+}
+
+#[async_trait]
+impl RateLimitRepository for SqliteRateLimitRepository {
+    #[instrument(skip(self, quota))]
+    async fn acquire(
+        &self,
+        key: &str,
+        quota: &RateLimitQuota,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitDecision, DomainError> {
+        // The write comes first, so the transaction holds the write lock from
+        // the start and concurrent requests cannot both pass
+        let mut transaction = self.pool.begin().await.map_err(database_error)?;
+
+        // The same check as `RateLimitQuota::check`; RETURNING is not used
+        // since a refused request returns no row but still has to read the TAT
+        let allowed = sqlx::query(
+            r#"
+            INSERT INTO rate_limits (key, tat)
+            VALUES (?1, ?2 + ?3)
+            ON CONFLICT (key) DO UPDATE SET tat = MAX(rate_limits.tat, ?2) + ?3
+            WHERE MAX(rate_limits.tat, ?2) + ?3 - ?2 <= ?4
+            "#,
+        )
+        .bind(key)
+        .bind(now.timestamp_micros())
+        .bind(micros(quota.emission_interval()))
+        .bind(micros(quota.tolerance()))
+        .execute(&mut *transaction)
+        .await
+        .map_err(database_error)?
+        .rows_affected()
+            > 0;
+
+        let tat: i64 = sqlx::query_scalar("SELECT tat FROM rate_limits WHERE key = ?")
+            .bind(key)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(database_error)?;
+
+        transaction.commit().await.map_err(database_error)?;
+
+        Ok(quota.decision(from_micros(tat)?.max(now), now, allowed))
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, DomainError> {
+        let result = sqlx::query("DELETE FROM rate_limits WHERE tat <= ?")
+            .bind(now.timestamp_micros())
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::User;
+use domain::errors::DomainError;
+use domain::repositories::{RepositoryTransaction, UnitOfWork, UserRepository};
+use sqlx::{Sqlite, SqlitePool};
+use tokio::sync::Mutex;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use super::user_repository::{
+    delete_user, find_all_users, find_user_by, insert_user, purge_deleted_users, update_user,
+};
+
+pub struct SqliteUnitOfWork {
+    pool: SqlitePool,
+}
+
+impl SqliteUnitOfWork {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for SqliteUnitOfWork {
+    async fn begin(&self) -> Result<Box<dyn RepositoryTransaction>, DomainError> {
+        let transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+        Ok(Box::new(SqliteTransaction {
+            transaction: Mutex::new(transaction),
+        }))
+    }
+}
+
+/// Repositories running on one database transaction.
+///
+/// sqlx rolls the transaction back when it is dropped without a commit.
+pub struct SqliteTransaction {
+    // Statements on one connection run one at a time
+    transaction: Mutex<sqlx::Transaction<'static, Sqlite>>,
+}
+
+#[async_trait]
+impl RepositoryTransaction for SqliteTransaction {
+    fn users(&self) -> &dyn UserRepository {
+        self
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DomainError> {
+        self.transaction
+            .into_inner()
+            .commit()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DomainError> {
+        self.transaction
+            .into_inner()
+            .rollback()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteTransaction {
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        info!("Creating user in SQLite transaction");
+        insert_user(&mut **self.transaction.lock().await, user).await
+    }
+
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        info!("Updating user in SQLite transaction");
+        update_user(&mut **self.transaction.lock().await, user).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        info!("Deleting user in SQLite transaction");
+        delete_user(&mut **self.transaction.lock().await, id).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        find_user_by(&mut **self.transaction.lock().await, "id", id.to_string(), false).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        find_user_by(&mut **self.transaction.lock().await, "id", id.to_string(), true).await
+    }
+
+    #[instrument(skip(self), fields(username = %username))]
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        find_user_by(&mut **self.transaction.lock().await, "username", username.to_string(), false).await
+    }
+
+    #[instrument(skip(self), fields(email = %email))]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        find_user_by(&mut **self.transaction.lock().await, "email", email.to_string(), false).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        find_all_users(&mut **self.transaction.lock().await, false).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        find_all_users(&mut **self.transaction.lock().await, true).await
+    }
+
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        info!("Purging deleted users in SQLite transaction");
+        purge_deleted_users(&mut **self.transaction.lock().await, deleted_before).await
+    }
+}
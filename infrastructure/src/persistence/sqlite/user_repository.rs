@@ -0,0 +1,318 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use domain::entities::{Role, RoleName};
+use domain::entities::{User, UserStatus};
+use domain::errors::DomainError;
+use domain::repositories::UserRepository;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqliteConnection, SqliteExecutor, SqlitePool};
+use std::collections::HashSet;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::persistence::{map_write_error, version_conflict};
+
+const USER_COLUMNS: &str =
+    "id, username, email, password_hash, role_name, role_permissions, created_at, updated_at, version, deleted_at, status, status_reason, status_changed_at, password_changed_at, password_history, token_version, email_verified_at";
+
+pub struct SqliteUserRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteUserRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_row(row: &SqliteRow) -> Result<User, DomainError> {
+    let id: String = row.get("id");
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let role_name: String = row.get("role_name");
+    let role_name = serde_json::from_str::<RoleName>(&role_name)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let permissions: String = row.get("role_permissions");
+    let permissions = serde_json::from_str::<HashSet<String>>(&permissions)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let password_history: String = row.get("password_history");
+    let password_history = serde_json::from_str::<Vec<String>>(&password_history)
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    let status: String = row.get("status");
+    let status = status
+        .parse::<UserStatus>()
+        .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))?;
+
+    Ok(User {
+        id,
+        username: row.get("username"),
+        email: row.get("email"),
+        password_hash: row.get("password_hash"),
+        role: Role {
+            name: role_name,
+            permissions,
+        },
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
+        deleted_at: row.get("deleted_at"),
+        status,
+        status_reason: row.get("status_reason"),
+        status_changed_at: row.get("status_changed_at"),
+        password_changed_at: row.get("password_changed_at"),
+        password_history,
+        token_version: row.get("token_version"),
+        email_verified_at: row.get("email_verified_at"),
+    })
+}
+
+fn serialize_role(role: &Role) -> Result<(String, String), DomainError> {
+    let role_name = serde_json::to_string(&role.name)
+        .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
+
+    let permissions = serde_json::to_string(&role.permissions)
+        .map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))?;
+
+    Ok((role_name, permissions))
+}
+
+fn serialize_password_history(history: &[String]) -> Result<String, DomainError> {
+    serde_json::to_string(history).map_err(|e| DomainError::RepositoryError(format!("Serialization error: {}", e)))
+}
+
+// The queries take any executor so that `SqliteTransaction` runs the same
+// statements on its transaction that the repository runs on the pool.
+
+pub(super) async fn insert_user<'e>(executor: impl SqliteExecutor<'e>, user: &User) -> Result<(), DomainError> {
+    let (role_name, permissions) = serialize_role(&user.role)?;
+    let password_history = serialize_password_history(&user.password_history)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO users (id, username, email, password_hash, role_name, role_permissions, created_at, updated_at, version, deleted_at,
+                           status, status_reason, status_changed_at, password_changed_at, password_history, token_version,
+                           email_verified_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(user.id.to_string())
+    .bind(&user.username)
+    .bind(&user.email)
+    .bind(&user.password_hash)
+    .bind(role_name)
+    .bind(permissions)
+    .bind(user.created_at)
+    .bind(user.updated_at)
+    .bind(user.version)
+    .bind(user.deleted_at)
+    .bind(user.status.as_str())
+    .bind(&user.status_reason)
+    .bind(user.status_changed_at)
+    .bind(user.password_changed_at)
+    .bind(password_history)
+    .bind(user.token_version)
+    .bind(user.email_verified_at)
+    .execute(executor)
+    .await
+    .map_err(|e| map_write_error(e, user))?;
+
+    Ok(())
+}
+
+/// Needs a connection rather than any executor: telling a conflict from a
+/// missing user takes a second query.
+pub(super) async fn update_user(connection: &mut SqliteConnection, user: &User) -> Result<(), DomainError> {
+    let (role_name, permissions) = serialize_role(&user.role)?;
+    let password_history = serialize_password_history(&user.password_history)?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET username = ?, email = ?, password_hash = ?,
+            role_name = ?, role_permissions = ?, updated_at = ?, version = ?, deleted_at = ?,
+            status = ?, status_reason = ?, status_changed_at = ?, password_changed_at = ?, password_history = ?,
+            token_version = ?, email_verified_at = ?
+        WHERE id = ? AND version = ?
+        "#,
+    )
+    .bind(&user.username)
+    .bind(&user.email)
+    .bind(&user.password_hash)
+    .bind(role_name)
+    .bind(permissions)
+    .bind(user.updated_at)
+    .bind(user.version)
+    .bind(user.deleted_at)
+    .bind(user.status.as_str())
+    .bind(&user.status_reason)
+    .bind(user.status_changed_at)
+    .bind(user.password_changed_at)
+    .bind(password_history)
+    .bind(user.token_version)
+    .bind(user.email_verified_at)
+    .bind(user.id.to_string())
+    .bind(user.version - 1)
+    .execute(&mut *connection)
+    .await
+    .map_err(|e| map_write_error(e, user))?;
+
+    if result.rows_affected() == 0 {
+        return Err(match find_user_by(connection, "id", user.id.to_string(), true).await? {
+            Some(stored) => version_conflict(user, &stored),
+            None => DomainError::NotFound(format!("User with ID {} not found", user.id)),
+        });
+    }
+
+    Ok(())
+}
+
+pub(super) async fn delete_user<'e>(executor: impl SqliteExecutor<'e>, id: &Uuid) -> Result<(), DomainError> {
+    let result = sqlx::query("DELETE FROM users WHERE id = ?")
+        .bind(id.to_string())
+        .execute(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err(DomainError::NotFound(format!("User with ID {} not found", id)));
+    }
+
+    Ok(())
+}
+
+pub(super) async fn find_user_by<'e>(
+    executor: impl SqliteExecutor<'e>,
+    column: &str,
+    value: String,
+    include_deleted: bool,
+) -> Result<Option<User>, DomainError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM users WHERE {} = ?{}",
+        USER_COLUMNS,
+        column,
+        deleted_filter(include_deleted)
+    ))
+        .bind(value)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    row.as_ref().map(map_row).transpose()
+}
+
+pub(super) async fn find_all_users<'e>(
+    executor: impl SqliteExecutor<'e>,
+    include_deleted: bool,
+) -> Result<Vec<User>, DomainError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM users WHERE TRUE{}",
+        USER_COLUMNS,
+        deleted_filter(include_deleted)
+    ))
+        .fetch_all(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    rows.iter().map(map_row).collect()
+}
+
+pub(super) async fn purge_deleted_users<'e>(
+    executor: impl SqliteExecutor<'e>,
+    deleted_before: DateTime<Utc>,
+) -> Result<Vec<Uuid>, DomainError> {
+    let rows = sqlx::query("DELETE FROM users WHERE deleted_at < ? RETURNING id")
+        .bind(deleted_before)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+    rows.iter()
+        .map(|row| {
+            Uuid::parse_str(row.get("id"))
+                .map_err(|e| DomainError::RepositoryError(format!("Deserialization error: {}", e)))
+        })
+        .collect()
+}
+
+/// Condition appended to a `WHERE` clause to skip soft-deleted users
+fn deleted_filter(include_deleted: bool) -> &'static str {
+    if include_deleted {
+        ""
+    } else {
+        " AND deleted_at IS NULL"
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteUserRepository {
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn create(&self, user: &User) -> Result<(), DomainError> {
+        info!("Creating user in SQLite repository");
+        insert_user(&self.pool, user).await
+    }
+
+    #[instrument(skip(self, user), fields(user_id = %user.id, username = %user.username))]
+    async fn update(&self, user: &User) -> Result<(), DomainError> {
+        info!("Updating user in SQLite repository");
+
+        let mut connection = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| DomainError::RepositoryError(format!("Database error: {}", e)))?;
+
+        update_user(&mut connection, user).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn delete(&self, id: &Uuid) -> Result<(), DomainError> {
+        info!("Deleting user in SQLite repository");
+        delete_user(&self.pool, id).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        info!("Finding user by ID in SQLite repository");
+        find_user_by(&self.pool, "id", id.to_string(), false).await
+    }
+
+    #[instrument(skip(self), fields(user_id = %id))]
+    async fn find_by_id_including_deleted(&self, id: &Uuid) -> Result<Option<User>, DomainError> {
+        info!("Finding user by ID, including deleted users, in SQLite repository");
+        find_user_by(&self.pool, "id", id.to_string(), true).await
+    }
+
+    #[instrument(skip(self), fields(username = %username))]
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, DomainError> {
+        info!("Finding user by username in SQLite repository");
+        find_user_by(&self.pool, "username", username.to_string(), false).await
+    }
+
+    #[instrument(skip(self), fields(email = %email))]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, DomainError> {
+        info!("Finding user by email in SQLite repository");
+        find_user_by(&self.pool, "email", email.to_string(), false).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all(&self) -> Result<Vec<User>, DomainError> {
+        info!("Finding all users in SQLite repository");
+        find_all_users(&self.pool, false).await
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_including_deleted(&self) -> Result<Vec<User>, DomainError> {
+        info!("Finding all users, including deleted users, in SQLite repository");
+        find_all_users(&self.pool, true).await
+    }
+
+    #[instrument(skip(self), fields(deleted_before = %deleted_before))]
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        info!("Purging deleted users in SQLite repository");
+        purge_deleted_users(&self.pool, deleted_before).await
+    }
+}
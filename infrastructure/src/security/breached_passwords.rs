@@ -0,0 +1,60 @@
+use application::errors::ApplicationError;
+use application::services::BreachedPasswordChecker;
+use async_trait::async_trait;
+use sha1::{Digest, Sha1};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tracing::instrument;
+
+/// Looks passwords up in a local copy of the Have I Been Pwned password
+/// ranges, so that no part of a password hash leaves the server.
+///
+/// The directory holds one file per five-hex-digit SHA-1 prefix, named
+/// `<PREFIX>` or `<PREFIX>.txt`, with `SUFFIX:COUNT` lines as served by
+/// `https://api.pwnedpasswords.com/range/<PREFIX>`.
+pub struct HibpRangeDirectory {
+    directory: PathBuf,
+}
+
+impl HibpRangeDirectory {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    async fn read_range(&self, prefix: &str) -> Result<Option<String>, std::io::Error> {
+        for name in [prefix.to_string(), format!("{}.txt", prefix)] {
+            match tokio::fs::read_to_string(self.directory.join(name)).await {
+                Ok(contents) => return Ok(Some(contents)),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl BreachedPasswordChecker for HibpRangeDirectory {
+    #[instrument(skip(self, password))]
+    async fn occurrences(&self, password: &str) -> Result<u64, ApplicationError> {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let range = self.read_range(prefix).await.map_err(|e| {
+            ApplicationError::UnexpectedError(format!("Failed to read breached password range {}: {}", prefix, e))
+        })?;
+        // A range without a file has no breached passwords in this copy
+        let Some(range) = range else {
+            return Ok(0);
+        };
+
+        let count = range
+            .lines()
+            .filter_map(|line| line.trim_end_matches('\r').split_once(':'))
+            .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+            .map_or(0, |(_, count)| count.trim().parse::<u64>().unwrap_or(1));
+        Ok(count)
+    }
+}
@@ -0,0 +1,111 @@
+use application::errors::ApplicationError;
+use argon2::password_hash::{PasswordHash, PasswordVerifier};
+use argon2::Argon2;
+use pbkdf2::Pbkdf2;
+use scrypt::Scrypt;
+use sha1::{Digest, Sha1};
+use subtle::ConstantTimeEq;
+
+use crate::errors::InfrastructureError;
+
+/// Prefix of a bcrypt hash computed over the hex SHA-1 digest of the password
+const BCRYPT_SHA1_PREFIX: &str = "bcrypt-sha1$";
+/// Prefix of `sha1$<salt>$<hex digest of salt + password>`
+const SALTED_SHA1_PREFIX: &str = "sha1$";
+
+/// Formats of stored password hashes that can be verified.
+///
+/// Only `Argon2` is produced by the current hashing; the others exist to
+/// verify hashes created by earlier versions or imported from other systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HashScheme {
+    /// PHC string, `$argon2id$...`
+    Argon2,
+    /// Modular crypt format, `$2b$...`
+    Bcrypt,
+    /// PHC string, `$pbkdf2-sha256$i=...,l=...$<salt>$<hash>`
+    Pbkdf2,
+    /// PHC string, `$scrypt$ln=...,r=...,p=...$<salt>$<hash>`
+    Scrypt,
+    SaltedSha1,
+    BcryptSha1,
+}
+
+impl HashScheme {
+    pub(crate) fn of(hash: &str) -> Option<Self> {
+        if hash.starts_with("$argon2") {
+            Some(Self::Argon2)
+        } else if is_bcrypt(hash) {
+            Some(Self::Bcrypt)
+        } else if hash.starts_with("$pbkdf2") {
+            Some(Self::Pbkdf2)
+        } else if hash.starts_with("$scrypt$") {
+            Some(Self::Scrypt)
+        } else if hash.starts_with(SALTED_SHA1_PREFIX) {
+            Some(Self::SaltedSha1)
+        } else if hash.starts_with(BCRYPT_SHA1_PREFIX) {
+            Some(Self::BcryptSha1)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `hash` is well-formed for its scheme, so that verifying it can
+    /// only fail because the password is wrong.
+    pub(crate) fn is_well_formed(hash: &str) -> bool {
+        match Self::of(hash) {
+            Some(Self::Argon2) | Some(Self::Pbkdf2) | Some(Self::Scrypt) => {
+                PasswordHash::new(hash).is_ok_and(|parsed| parsed.hash.is_some())
+            }
+            Some(Self::Bcrypt) => hash.parse::<bcrypt::HashParts>().is_ok(),
+            Some(Self::SaltedSha1) => parse_salted_sha1(hash).is_some(),
+            Some(Self::BcryptSha1) => hash[BCRYPT_SHA1_PREFIX.len()..].parse::<bcrypt::HashParts>().is_ok(),
+            None => false,
+        }
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix))
+}
+
+fn verify_error(e: impl std::fmt::Display) -> ApplicationError {
+    let err = InfrastructureError::PasswordError(format!("Failed to verify password: {}", e));
+    ApplicationError::from(err)
+}
+
+/// Verifies `password` against a stored hash of any supported scheme.
+pub(crate) fn verify_any(password: &str, hash: &str) -> Result<bool, ApplicationError> {
+    match HashScheme::of(hash) {
+        Some(HashScheme::Argon2) | Some(HashScheme::Pbkdf2) | Some(HashScheme::Scrypt) => {
+            let parsed = PasswordHash::new(hash).map_err(verify_error)?;
+            // The algorithm and its parameters are taken from the hash
+            let verifiers: [&dyn PasswordVerifier; 3] = [&Argon2::default(), &Pbkdf2, &Scrypt];
+            match parsed.verify_password(&verifiers, password.as_bytes()) {
+                Ok(()) => Ok(true),
+                Err(argon2::password_hash::Error::Password) => Ok(false),
+                Err(e) => Err(verify_error(e)),
+            }
+        }
+        Some(HashScheme::Bcrypt) => bcrypt::verify(password, hash).map_err(verify_error),
+        Some(HashScheme::SaltedSha1) => {
+            let (salt, expected) = parse_salted_sha1(hash).ok_or_else(|| verify_error("malformed SHA-1 hash"))?;
+            let digest = Sha1::new()
+                .chain_update(salt.as_bytes())
+                .chain_update(password.as_bytes())
+                .finalize();
+            Ok(digest.as_slice().ct_eq(&expected).into())
+        }
+        Some(HashScheme::BcryptSha1) => {
+            let prehashed = hex::encode(Sha1::digest(password.as_bytes()));
+            bcrypt::verify(prehashed, &hash[BCRYPT_SHA1_PREFIX.len()..]).map_err(verify_error)
+        }
+        None => Err(verify_error("unsupported hash format")),
+    }
+}
+
+fn parse_salted_sha1(hash: &str) -> Option<(&str, Vec<u8>)> {
+    let (salt, digest) = hash.strip_prefix(SALTED_SHA1_PREFIX)?.split_once('$')?;
+    let digest = hex::decode(digest).ok().filter(|digest| digest.len() == 20)?;
+    Some((salt, digest))
+}
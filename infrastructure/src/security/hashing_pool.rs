@@ -0,0 +1,182 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tracing::{debug, error, warn};
+
+use crate::errors::InfrastructureError;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Point-in-time view of a `HashingPool`'s metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HashingPoolStats {
+    /// Jobs waiting for a free worker
+    pub queue_depth: usize,
+    /// Jobs currently running on a worker
+    pub running: usize,
+    pub completed: u64,
+    /// Jobs refused because the queue was full
+    pub rejected: u64,
+    /// Average time a job spent running, excluding the wait in the queue
+    pub mean_latency: Duration,
+    pub max_latency: Duration,
+    /// Average time a job waited in the queue before a worker picked it up
+    pub mean_queue_wait: Duration,
+}
+
+#[derive(Debug, Default)]
+struct HashingPoolMetrics {
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    total_latency_micros: AtomicU64,
+    max_latency_micros: AtomicU64,
+    total_queue_wait_micros: AtomicU64,
+}
+
+impl HashingPoolMetrics {
+    fn stats(&self) -> HashingPoolStats {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let mean = |total: &AtomicU64| {
+            Duration::from_micros(total.load(Ordering::Relaxed).checked_div(completed).unwrap_or(0))
+        };
+
+        HashingPoolStats {
+            queue_depth: self.queued.load(Ordering::Relaxed),
+            running: self.running.load(Ordering::Relaxed),
+            completed,
+            rejected: self.rejected.load(Ordering::Relaxed),
+            mean_latency: mean(&self.total_latency_micros),
+            max_latency: Duration::from_micros(self.max_latency_micros.load(Ordering::Relaxed)),
+            mean_queue_wait: mean(&self.total_queue_wait_micros),
+        }
+    }
+}
+
+/// Why a job could not be run by a `HashingPool`
+#[derive(Debug)]
+pub enum HashingPoolError {
+    /// Every worker is busy and the queue is full
+    Saturated,
+    /// The job panicked or the pool has shut down
+    Failed,
+}
+
+/// A fixed set of OS threads for CPU-heavy password hashing, so it never
+/// blocks the async runtime's workers.
+///
+/// Jobs wait in a bounded queue; once it is full, new jobs are refused at once
+/// instead of piling up behind a burst of logins. The threads stop when the
+/// pool is dropped.
+pub struct HashingPool {
+    sender: SyncSender<Job>,
+    metrics: Arc<HashingPoolMetrics>,
+}
+
+impl HashingPool {
+    /// Starts `workers` threads that share a queue of up to `queue_limit` jobs.
+    pub fn new(workers: usize, queue_limit: usize) -> Result<Self, InfrastructureError> {
+        if workers == 0 || queue_limit == 0 {
+            return Err(InfrastructureError::ConfigurationError(
+                "Password hashing needs at least one worker and a queue limit of at least one".to_string(),
+            ));
+        }
+
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_limit);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..workers {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("password-hash-{}", index))
+                .spawn(move || run_worker(&receiver))?;
+        }
+
+        Ok(Self {
+            sender,
+            metrics: Arc::new(HashingPoolMetrics::default()),
+        })
+    }
+
+    /// Runs `job` on a worker thread and waits for its result.
+    pub async fn run<T, F>(&self, job: F) -> Result<T, HashingPoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let metrics = Arc::clone(&self.metrics);
+        let enqueued_at = Instant::now();
+
+        let wrapped: Job = Box::new(move || {
+            let started_at = Instant::now();
+            metrics.queued.fetch_sub(1, Ordering::Relaxed);
+            metrics.running.fetch_add(1, Ordering::Relaxed);
+
+            // A panicking job only fails its caller; the worker keeps running
+            let result = catch_unwind(AssertUnwindSafe(job));
+
+            let latency = started_at.elapsed().as_micros() as u64;
+            let queue_wait = (started_at - enqueued_at).as_micros() as u64;
+            metrics.running.fetch_sub(1, Ordering::Relaxed);
+            metrics.completed.fetch_add(1, Ordering::Relaxed);
+            metrics.total_latency_micros.fetch_add(latency, Ordering::Relaxed);
+            metrics.max_latency_micros.fetch_max(latency, Ordering::Relaxed);
+            metrics.total_queue_wait_micros.fetch_add(queue_wait, Ordering::Relaxed);
+            debug!(latency_us = latency, queue_wait_us = queue_wait, "Password hashing job finished");
+
+            if let Ok(result) = result {
+                // The caller may have gone away meanwhile
+                let _ = result_sender.send(result);
+            }
+        });
+
+        self.metrics.queued.fetch_add(1, Ordering::Relaxed);
+        match self.sender.try_send(wrapped) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+                self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    queue_depth = self.metrics.queued.load(Ordering::Relaxed),
+                    "Password hashing queue is full, rejecting request"
+                );
+                return Err(HashingPoolError::Saturated);
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+                return Err(HashingPoolError::Failed);
+            }
+        }
+
+        result_receiver.await.map_err(|_| HashingPoolError::Failed)
+    }
+
+    pub fn stats(&self) -> HashingPoolStats {
+        self.metrics.stats()
+    }
+}
+
+fn run_worker(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // The lock is only held while waiting for the next job, not while running it
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => {
+                error!("Password hashing queue is poisoned, stopping worker");
+                return;
+            }
+        };
+
+        match job {
+            Ok(job) => job(),
+            // The pool was dropped
+            Err(_) => return,
+        }
+    }
+}
@@ -27,7 +27,7 @@ impl JwtService for JwtServiceImpl {
         info!("Generating JWT token");
         
         let config = self.config_provider.get_config();
-        let secret = config.jwt.secret.as_bytes();
+        let secret = config.jwt.secret.expose().as_bytes();
         
         let token = encode(
             &Header::default(),
@@ -47,7 +47,7 @@ impl JwtService for JwtServiceImpl {
         info!("Validating JWT token");
         
         let config = self.config_provider.get_config();
-        let secret = config.jwt.secret.as_bytes();
+        let secret = config.jwt.secret.expose().as_bytes();
         
         let token_data = decode::<JwtClaims>(
             token,
@@ -73,7 +73,7 @@ impl JwtService for JwtServiceImpl {
 
 pub fn create_token(
     user_id: &str,
-    role: domain::entities::role::RoleName,
+    role: domain::entities::RoleName,
     jwt_service: &dyn JwtService,
     expiration_seconds: i64,
 ) -> Result<JwtToken, ApplicationError> {
@@ -1,5 +1,16 @@
+mod breached_passwords;
+mod hash_scheme;
+mod hashing_pool;
 mod jwt_service;
+mod one_time_tokens;
 mod password_service;
+mod secret_cipher;
+mod totp;
 
+pub use breached_passwords::*;
+pub use hashing_pool::*;
 pub use jwt_service::*;
+pub use one_time_tokens::*;
 pub use password_service::*;
+pub use secret_cipher::*;
+pub use totp::*;
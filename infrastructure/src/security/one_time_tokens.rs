@@ -0,0 +1,24 @@
+use application::services::OneTimeTokenService;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Bytes of randomness in a token
+const TOKEN_BYTES: usize = 32;
+
+/// Hex-encoded random tokens, stored as their SHA-256 hash.
+///
+/// The tokens carry enough entropy that a fast unsalted hash suffices: a
+/// leaked hash cannot be reversed by guessing.
+pub struct RandomTokenService;
+
+impl OneTimeTokenService for RandomTokenService {
+    fn generate(&self) -> String {
+        let mut bytes = [0u8; TOKEN_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+
+    fn hash(&self, token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+}
@@ -1,15 +1,103 @@
 use application::errors::ApplicationError;
 use application::services::PasswordService;
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
 use async_trait::async_trait;
+use rand_core::OsRng;
+use std::sync::Arc;
 use tracing::{info, instrument};
 
+use super::hash_scheme::{verify_any, HashScheme};
+use super::hashing_pool::{HashingPool, HashingPoolError, HashingPoolStats};
+use crate::config::PasswordHashingConfig;
 use crate::errors::InfrastructureError;
 
-pub struct BcryptPasswordService {
+/// A password hashing algorithm. Its work is CPU-bound and blocks the calling
+/// thread, so `PooledPasswordService` runs it off the async runtime.
+pub trait PasswordHasher: Send + Sync + 'static {
+    fn hash_password(&self, password: &str) -> Result<String, ApplicationError>;
+    fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError>;
+    /// See `PasswordService::needs_rehash`
+    fn needs_rehash(&self, hash: &str) -> bool;
+    /// See `PasswordService::is_supported_hash`
+    fn is_supported_hash(&self, hash: &str) -> bool;
+}
+
+fn hash_error(e: impl std::fmt::Display) -> ApplicationError {
+    let err = InfrastructureError::PasswordError(format!("Failed to hash password: {}", e));
+    ApplicationError::from(err)
+}
+
+/// Hashes passwords with Argon2id in PHC string format.
+///
+/// Hashes of the other schemes in `HashScheme`, such as bcrypt from before the
+/// switch to Argon2id or imported legacy hashes, are still verified and
+/// reported by `needs_rehash`, so they are replaced on the next login.
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(config: &PasswordHashingConfig) -> Result<Self, InfrastructureError> {
+        let params = Params::new(config.memory_kib, config.iterations, config.parallelism, None)
+            .map_err(|e| {
+                InfrastructureError::ConfigurationError(format!("Invalid Argon2 parameters: {}", e))
+            })?;
+
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'_> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    #[instrument(skip(self, password))]
+    fn hash_password(&self, password: &str) -> Result<String, ApplicationError> {
+        info!("Hashing password");
+
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(hash_error)
+    }
+
+    #[instrument(skip(self, password, hash))]
+    fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError> {
+        info!("Verifying password");
+        verify_any(password, hash)
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident() || parsed.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn is_supported_hash(&self, hash: &str) -> bool {
+        HashScheme::is_well_formed(hash)
+    }
+}
+
+pub struct BcryptHasher {
     cost: u32,
 }
 
-impl BcryptPasswordService {
+impl BcryptHasher {
     pub fn new(cost: Option<u32>) -> Self {
         Self {
             cost: cost.unwrap_or(12), // Default cost
@@ -17,25 +105,97 @@ impl BcryptPasswordService {
     }
 }
 
-#[async_trait]
-impl PasswordService for BcryptPasswordService {
+impl PasswordHasher for BcryptHasher {
     #[instrument(skip(self, password))]
     fn hash_password(&self, password: &str) -> Result<String, ApplicationError> {
         info!("Hashing password");
         
-        bcrypt::hash(password, self.cost).map_err(|e| {
-            let err = InfrastructureError::PasswordError(format!("Failed to hash password: {}", e));
-            ApplicationError::from(err)
-        })
+        bcrypt::hash(password, self.cost).map_err(hash_error)
     }
 
     #[instrument(skip(self, password, hash))]
     fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError> {
         info!("Verifying password");
         
-        bcrypt::verify(password, hash).map_err(|e| {
-            let err = InfrastructureError::PasswordError(format!("Failed to verify password: {}", e));
-            ApplicationError::from(err)
+        verify_any(password, hash)
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        HashScheme::of(hash) != Some(HashScheme::Bcrypt)
+            || hash.parse::<bcrypt::HashParts>().map_or(true, |parts| parts.get_cost() != self.cost)
+    }
+
+    fn is_supported_hash(&self, hash: &str) -> bool {
+        HashScheme::is_well_formed(hash)
+    }
+}
+
+/// Implements `PasswordService` by running a `PasswordHasher` on a dedicated
+/// `HashingPool`.
+///
+/// When the pool's queue is full, hashing fails at once with
+/// `ServiceUnavailable` rather than making the request wait.
+pub struct PooledPasswordService {
+    hasher: Arc<dyn PasswordHasher>,
+    pool: HashingPool,
+}
+
+impl PooledPasswordService {
+    pub fn new(
+        hasher: impl PasswordHasher,
+        config: &PasswordHashingConfig,
+    ) -> Result<Self, InfrastructureError> {
+        info!(
+            "Hashing passwords on {} threads (queue limit {})",
+            config.workers, config.queue_limit
+        );
+
+        Ok(Self {
+            hasher: Arc::new(hasher),
+            pool: HashingPool::new(config.workers, config.queue_limit)?,
         })
     }
+
+    /// Queue depth, hash latency and rejections of the hashing pool.
+    pub fn stats(&self) -> HashingPoolStats {
+        self.pool.stats()
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        job: impl FnOnce(&dyn PasswordHasher) -> Result<T, ApplicationError> + Send + 'static,
+    ) -> Result<T, ApplicationError> {
+        let hasher = Arc::clone(&self.hasher);
+        match self.pool.run(move || job(hasher.as_ref())).await {
+            Ok(result) => result,
+            Err(HashingPoolError::Saturated) => Err(ApplicationError::ServiceUnavailable {
+                message: "Too many password operations in progress".to_string(),
+                retry_after: 1,
+            }),
+            Err(HashingPoolError::Failed) => Err(ApplicationError::from(InfrastructureError::PasswordError(
+                "Password hashing job failed".to_string(),
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl PasswordService for PooledPasswordService {
+    async fn hash_password(&self, password: &str) -> Result<String, ApplicationError> {
+        let password = password.to_string();
+        self.run(move |hasher| hasher.hash_password(&password)).await
+    }
+
+    async fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError> {
+        let (password, hash) = (password.to_string(), hash.to_string());
+        self.run(move |hasher| hasher.verify_password(&password, &hash)).await
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        self.hasher.needs_rehash(hash)
+    }
+
+    fn is_supported_hash(&self, hash: &str) -> bool {
+        self.hasher.is_supported_hash(hash)
+    }
 }
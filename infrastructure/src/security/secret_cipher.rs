@@ -0,0 +1,64 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use application::errors::ApplicationError;
+use application::services::SecretCipher;
+use data_encoding::BASE64;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::config::Secret;
+
+/// Marks the format of stored ciphertexts, so the scheme can change later
+const VERSION_PREFIX: &str = "v1:";
+const NONCE_BYTES: usize = 12;
+
+/// AES-256-GCM with a random nonce per value, stored as
+/// `v1:` followed by the base64 of nonce and ciphertext.
+///
+/// The key is the SHA-256 of the configured secret, so any string of enough
+/// entropy can be used as one.
+pub struct AesGcmSecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl AesGcmSecretCipher {
+    pub fn new(key: &Secret) -> Self {
+        let key = Sha256::digest(key.expose().as_bytes());
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        }
+    }
+}
+
+impl SecretCipher for AesGcmSecretCipher {
+    fn encrypt(&self, plaintext: &str) -> Result<String, ApplicationError> {
+        let mut nonce = [0u8; NONCE_BYTES];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| ApplicationError::UnexpectedError("Failed to encrypt secret".to_string()))?;
+
+        Ok(format!("{}{}", VERSION_PREFIX, BASE64.encode(&[nonce.as_slice(), &ciphertext].concat())))
+    }
+
+    fn decrypt(&self, ciphertext: &str) -> Result<String, ApplicationError> {
+        // A wrong key and a tampered value look the same
+        let undecryptable =
+            || ApplicationError::UnexpectedError("Stored secret cannot be decrypted with the configured key".to_string());
+
+        let encoded = ciphertext.strip_prefix(VERSION_PREFIX).ok_or_else(undecryptable)?;
+        let bytes = BASE64.decode(encoded.as_bytes()).map_err(|_| undecryptable())?;
+        if bytes.len() < NONCE_BYTES {
+            return Err(undecryptable());
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_BYTES);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| undecryptable())?;
+        String::from_utf8(plaintext).map_err(|_| undecryptable())
+    }
+}
@@ -0,0 +1,1149 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use application::errors::ApplicationError;
+use application::services::{EmailMessage, JwtService, Notifier, PasswordService};
+use domain::repositories::UserRepository;
+use domain::value_objects::JwtClaims;
+
+use crate::config::{
+    CacheConfig, FsyncPolicy, MemoryConfig, PasswordHashingConfig, Secret, SnapshotFormat, SqliteConfig,
+};
+use crate::persistence::conformance::{
+    run_email_verification_repository_conformance, run_idempotency_repository_conformance,
+    run_login_attempt_repository_conformance, run_mfa_repository_conformance, run_password_reset_repository_conformance,
+    run_rate_limit_repository_conformance, run_unit_of_work_conformance, run_user_repository_conformance, sample_user,
+};
+use crate::persistence::{
+    create_sqlite_pool, CachedUserRepository, DurableStore, InMemoryEmailVerificationTokenRepository, InMemoryIdempotencyRepository,
+    InMemoryLoginAttemptRepository, InMemoryMfaRepository, InMemoryPasswordResetTokenRepository,
+    InMemoryRateLimitRepository, InMemoryUnitOfWork, InMemoryUserRepository, PostgresEmailVerificationTokenRepository,
+    PostgresIdempotencyRepository, PostgresLoginAttemptRepository, PostgresMfaRepository,
+    PostgresPasswordResetTokenRepository, PostgresRateLimitRepository, PostgresUnitOfWork, PostgresUserRepository,
+    SqliteEmailVerificationTokenRepository, SqliteIdempotencyRepository, SqliteLoginAttemptRepository,
+    SqliteMfaRepository, SqlitePasswordResetTokenRepository, SqliteRateLimitRepository, SqliteUnitOfWork,
+    SqliteUserRepository,
+};
+use crate::security::{
+    Argon2Hasher, BcryptHasher, HashingPool, HashingPoolError, HibpRangeDirectory, PasswordHasher,
+    PooledPasswordService,
+};
+
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-basic-server-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn in_memory_user_repository_conforms() {
+    run_user_repository_conformance(Arc::new(InMemoryUserRepository::new())).await;
+}
+
+#[tokio::test]
+async fn in_memory_unit_of_work_conforms() {
+    let repository = Arc::new(InMemoryUserRepository::new());
+    run_unit_of_work_conformance(Arc::new(InMemoryUnitOfWork::new(Arc::clone(&repository))), repository).await;
+}
+
+#[tokio::test]
+async fn in_memory_idempotency_repository_conforms() {
+    run_idempotency_repository_conformance(Arc::new(InMemoryIdempotencyRepository::new())).await;
+}
+
+#[tokio::test]
+async fn in_memory_login_attempt_repository_conforms() {
+    run_login_attempt_repository_conformance(Arc::new(InMemoryLoginAttemptRepository::new())).await;
+}
+
+#[tokio::test]
+async fn in_memory_rate_limit_repository_conforms() {
+    run_rate_limit_repository_conformance(Arc::new(InMemoryRateLimitRepository::new())).await;
+}
+
+#[tokio::test]
+async fn in_memory_password_reset_repository_conforms() {
+    run_password_reset_repository_conformance(Arc::new(InMemoryPasswordResetTokenRepository::new())).await;
+}
+
+#[tokio::test]
+async fn in_memory_email_verification_repository_conforms() {
+    run_email_verification_repository_conformance(Arc::new(InMemoryEmailVerificationTokenRepository::new())).await;
+}
+
+#[tokio::test]
+async fn in_memory_mfa_repository_conforms() {
+    run_mfa_repository_conformance(Arc::new(InMemoryMfaRepository::new())).await;
+}
+
+#[tokio::test]
+async fn persistent_in_memory_user_repository_conforms() {
+    let dir = scratch_dir();
+    let config = MemoryConfig {
+        snapshot_path: Some(dir.join("users.snapshot").to_string_lossy().into_owned()),
+        snapshot_format: SnapshotFormat::Json,
+        snapshot_interval: 0,
+        wal_path: None,
+        fsync: FsyncPolicy::Never,
+        fsync_interval: 1000,
+    };
+
+    let repository = Arc::new(InMemoryUserRepository::with_persistence(&config).unwrap());
+    run_user_repository_conformance(Arc::clone(&repository) as Arc<dyn UserRepository>).await;
+    run_unit_of_work_conformance(
+        Arc::new(InMemoryUnitOfWork::new(Arc::clone(&repository))),
+        Arc::clone(&repository) as Arc<dyn UserRepository>,
+    )
+    .await;
+
+    // Committed transactions are replayed from the log like single writes
+    let mut live = repository.find_all().await.unwrap();
+    let mut restored = InMemoryUserRepository::with_persistence(&config)
+        .unwrap()
+        .find_all()
+        .await
+        .unwrap();
+    live.sort_by_key(|user| user.id);
+    restored.sort_by_key(|user| user.id);
+    assert_eq!(restored, live);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn mfa_enrollments_and_reset_tokens_survive_a_restart() {
+    use domain::entities::{PasswordResetToken, TotpEnrollment};
+    use domain::repositories::{MfaRepository, PasswordResetTokenRepository};
+
+    let dir = scratch_dir();
+    let config = MemoryConfig {
+        snapshot_path: Some(dir.join("users.snapshot").to_string_lossy().into_owned()),
+        snapshot_format: SnapshotFormat::Binary,
+        snapshot_interval: 0,
+        wal_path: None,
+        fsync: FsyncPolicy::Never,
+        fsync_interval: 1000,
+    };
+    let user_id = Uuid::new_v4();
+
+    let mfa = Arc::new(InMemoryMfaRepository::with_persistence(&config).unwrap());
+    mfa.save(&TotpEnrollment::new(user_id, "JBSWY3DPEHPK3PXP".to_string())).await.unwrap();
+    assert!(mfa.record_step(&user_id, 42).await.unwrap());
+    mfa.replace_recovery_codes(&user_id, &["a".to_string(), "b".to_string()]).await.unwrap();
+    assert!(mfa.consume_recovery_code(&user_id, "a").await.unwrap());
+
+    let resets = InMemoryPasswordResetTokenRepository::with_persistence(&config).unwrap();
+    resets
+        .create(&PasswordResetToken::new("hash".to_string(), user_id, chrono::Duration::minutes(30)))
+        .await
+        .unwrap();
+    assert!(resets.consume("hash", chrono::Utc::now()).await.unwrap().is_some());
+
+    // Once from the log alone, then from a snapshot
+    for _ in 0..2 {
+        let restored = Arc::new(InMemoryMfaRepository::with_persistence(&config).unwrap());
+        let enrollment = restored.find(&user_id).await.unwrap().unwrap();
+        assert_eq!(enrollment.last_used_step, Some(42));
+        assert!(!restored.record_step(&user_id, 42).await.unwrap());
+        assert_eq!(restored.count_recovery_codes(&user_id).await.unwrap(), 1);
+        assert!(!restored.consume_recovery_code(&user_id, "a").await.unwrap());
+
+        let resets = InMemoryPasswordResetTokenRepository::with_persistence(&config).unwrap();
+        assert!(resets.consume("hash", chrono::Utc::now()).await.unwrap().is_none());
+
+        tokio::task::spawn_blocking(move || restored.snapshot()).await.unwrap().unwrap();
+    }
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn binary_snapshots_are_versioned() {
+    let dir = scratch_dir();
+    let snapshot_path = dir.join("users.snapshot");
+    let config = MemoryConfig {
+        snapshot_path: Some(snapshot_path.to_string_lossy().into_owned()),
+        snapshot_format: SnapshotFormat::Binary,
+        snapshot_interval: 0,
+        wal_path: None,
+        fsync: FsyncPolicy::Never,
+        fsync_interval: 1000,
+    };
+    let user = sample_user(domain::entities::RoleName::User);
+
+    let repository = Arc::new(InMemoryUserRepository::with_persistence(&config).unwrap());
+    repository.create(&user).await.unwrap();
+    let snapshotting = Arc::clone(&repository);
+    tokio::task::spawn_blocking(move || snapshotting.snapshot()).await.unwrap().unwrap();
+
+    assert!(std::fs::read(&snapshot_path).unwrap().starts_with(b"RBSUSR01"));
+    let restored = InMemoryUserRepository::with_persistence(&config).unwrap();
+    assert_eq!(restored.find_by_id(&user.id).await.unwrap(), Some(user));
+
+    // A version this build does not know is refused rather than misread
+    let mut unknown = b"RBSUSR99".to_vec();
+    unknown.extend_from_slice(&std::fs::read(&snapshot_path).unwrap()[8..]);
+    std::fs::write(&snapshot_path, unknown).unwrap();
+    assert!(InMemoryUserRepository::with_persistence(&config).is_err());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn secrets_are_read_from_files_before_the_environment() {
+    use crate::config::{resolve_secret, EnvSecretProvider};
+
+    // A key of its own, since tests share the process environment
+    let key = format!("TEST_SECRET_{}", Uuid::new_v4().simple()).to_uppercase();
+    assert!(resolve_secret(&EnvSecretProvider, &key).unwrap().is_none());
+
+    std::env::set_var(&key, "from the environment");
+    assert_eq!(resolve_secret(&EnvSecretProvider, &key).unwrap().unwrap().expose(), "from the environment");
+
+    // `<KEY>_FILE` wins, without the line ending the file was saved with
+    let path = scratch_dir().join("secret");
+    std::fs::write(&path, "from a file\r\n").unwrap();
+    std::env::set_var(format!("{}_FILE", key), &path);
+    let secret = resolve_secret(&EnvSecretProvider, &key).unwrap().unwrap();
+    assert_eq!(secret.expose(), "from a file");
+    assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+
+    std::env::set_var(format!("{}_FILE", key), path.with_file_name("missing"));
+    assert!(resolve_secret(&EnvSecretProvider, &key).is_err());
+}
+
+fn cache_config() -> CacheConfig {
+    CacheConfig {
+        enabled: true,
+        capacity: 100,
+        ttl: 60,
+        negative_ttl: 60,
+    }
+}
+
+#[tokio::test]
+async fn cached_user_repository_conforms() {
+    let repository = CachedUserRepository::new(InMemoryUserRepository::new(), &cache_config());
+    run_user_repository_conformance(Arc::new(repository)).await;
+}
+
+#[tokio::test]
+async fn cached_unit_of_work_conforms() {
+    let inner = Arc::new(InMemoryUserRepository::new());
+    let repository = CachedUserRepository::new(Arc::clone(&inner), &cache_config());
+    let unit_of_work = repository.unit_of_work(Arc::new(InMemoryUnitOfWork::new(inner)));
+
+    run_unit_of_work_conformance(Arc::new(unit_of_work), Arc::new(repository)).await;
+}
+
+#[tokio::test]
+async fn cached_user_repository_serves_repeated_lookups_from_cache() {
+    let repository = CachedUserRepository::new(InMemoryUserRepository::new(), &cache_config());
+    let user = sample_user(domain::entities::RoleName::User);
+    repository.create(&user).await.unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(repository.find_by_username(&user.username).await.unwrap(), Some(user.clone()));
+    }
+    let missing = Uuid::new_v4();
+    assert_eq!(repository.find_by_id(&missing).await.unwrap(), None);
+    assert_eq!(repository.find_by_id(&missing).await.unwrap(), None);
+
+    let stats = repository.metrics().stats();
+    assert_eq!(stats.hits, 3);
+    assert_eq!(stats.negative_hits, 1);
+    assert_eq!(stats.misses, 1);
+}
+
+fn hashing_config() -> PasswordHashingConfig {
+    PasswordHashingConfig {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+        workers: 1,
+        queue_limit: 1,
+        stats_interval: 0,
+    }
+}
+
+#[test]
+fn argon2_password_service_upgrades_outdated_hashes() {
+    let config = hashing_config();
+    let service = Argon2Hasher::new(&config).unwrap();
+
+    let hash = service.hash_password("secret").unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+    assert!(service.verify_password("secret", &hash).unwrap());
+    assert!(!service.verify_password("wrong", &hash).unwrap());
+    assert!(!service.needs_rehash(&hash));
+
+    let stronger = PasswordHashingConfig { iterations: 2, ..config };
+    assert!(Argon2Hasher::new(&stronger).unwrap().needs_rehash(&hash));
+
+    let legacy = BcryptHasher::new(Some(4)).hash_password("secret").unwrap();
+    assert!(service.verify_password("secret", &legacy).unwrap());
+    assert!(service.needs_rehash(&legacy));
+    assert!(service.verify_password("secret", "plaintext").is_err());
+}
+
+#[test]
+fn legacy_password_hashes_are_verified_and_upgraded() {
+    use argon2::password_hash::{PasswordHasher as _, SaltString};
+    use sha1::{Digest, Sha1};
+
+    let config = hashing_config();
+    let service = Argon2Hasher::new(&config).unwrap();
+    let salt = SaltString::encode_b64(b"legacy-salt").unwrap();
+
+    let pbkdf2_params = pbkdf2::Params { rounds: 1000, output_length: 32 };
+    let pbkdf2 = pbkdf2::Pbkdf2
+        .hash_password_customized(b"secret", Some(pbkdf2::Algorithm::Pbkdf2Sha256.ident()), None, pbkdf2_params, &salt)
+        .unwrap()
+        .to_string();
+    let scrypt = scrypt::Scrypt
+        .hash_password_customized(b"secret", None, None, scrypt::Params::new(4, 8, 1, 32).unwrap(), &salt)
+        .unwrap()
+        .to_string();
+    let salted_sha1 = "sha1$NaCl$a8f8c29293990fec55fa166d2b15fe348383065e".to_string();
+    let bcrypt_sha1 = format!(
+        "bcrypt-sha1${}",
+        bcrypt::hash(hex::encode(Sha1::digest(b"secret")), 4).unwrap()
+    );
+
+    for hash in [pbkdf2, scrypt, salted_sha1, bcrypt_sha1] {
+        assert!(service.is_supported_hash(&hash), "{}", hash);
+        assert!(service.verify_password("secret", &hash).unwrap(), "{}", hash);
+        assert!(!service.verify_password("wrong", &hash).unwrap(), "{}", hash);
+        assert!(service.needs_rehash(&hash), "{}", hash);
+    }
+
+    assert!(!service.is_supported_hash("sha1$NaCl$not-hex"));
+    assert!(!service.is_supported_hash("$pbkdf2-sha256$i=1000,l=32$c2FsdA"));
+    assert!(!service.is_supported_hash("md5$NaCl$0123"));
+}
+
+#[tokio::test]
+async fn hashing_pool_rejects_jobs_beyond_its_queue_limit() {
+    let pool = Arc::new(HashingPool::new(1, 1).unwrap());
+    let (release, blocked) = std::sync::mpsc::channel::<()>();
+
+    // Occupies the only worker until released
+    let running = tokio::spawn({
+        let pool = Arc::clone(&pool);
+        async move { pool.run(move || blocked.recv().is_ok()).await }
+    });
+    while pool.stats().running == 0 {
+        tokio::task::yield_now().await;
+    }
+    let queued = tokio::spawn({
+        let pool = Arc::clone(&pool);
+        async move { pool.run(|| true).await }
+    });
+    while pool.stats().queue_depth == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    assert!(matches!(pool.run(|| true).await, Err(HashingPoolError::Saturated)));
+
+    release.send(()).unwrap();
+    assert!(running.await.unwrap().unwrap());
+    assert!(queued.await.unwrap().unwrap());
+
+    let stats = pool.stats();
+    assert_eq!((stats.completed, stats.rejected, stats.queue_depth, stats.running), (2, 1, 0, 0));
+}
+
+#[tokio::test]
+async fn password_validator_applies_policy_and_breached_ranges() {
+    use application::errors::ApplicationError;
+    use application::services::{estimate_strength, PasswordPolicy, PasswordValidator};
+
+    for (password, weak) in [
+        ("password", true),
+        ("P@ssw0rd1", true),
+        ("qwertyuiop", true),
+        ("abcd1234", true),
+        ("aaaaaaaaaaaa", true),
+        ("alice1990", true),
+        ("correct horse battery staple", false),
+        ("Tr0ub4dour&3-vX", false),
+    ] {
+        let strength = estimate_strength(password, &["alice"]);
+        assert_eq!(strength.score < 2, weak, "{}: {:?}", password, strength);
+    }
+
+    // The range of SHA-1("horse staple battery correct 7"), in the downloaded format
+    let dir = scratch_dir();
+    std::fs::write(
+        dir.join("68C55.txt"),
+        "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\nC8818D8518313FCD1769D9350275A9D5B44:12\r\n",
+    )
+    .unwrap();
+
+    let validator = PasswordValidator::new(PasswordPolicy {
+        min_length: 10,
+        max_length: 64,
+        require_lowercase: true,
+        require_uppercase: false,
+        require_digit: true,
+        require_symbol: false,
+        reject_account_names: true,
+        min_strength: 2,
+    })
+    .with_breached_check(Arc::new(HibpRangeDirectory::new(&dir)), 1);
+
+    let codes = |result: Result<(), ApplicationError>| match result {
+        Err(ApplicationError::PasswordRejected(violations)) => {
+            violations.into_iter().map(|v| v.code).collect::<Vec<_>>()
+        }
+        other => panic!("expected a rejected password, got {:?}", other),
+    };
+
+    assert_eq!(
+        codes(validator.validate("Alice", "alice", "alice@example.com").await),
+        ["too_short", "missing_digit", "similar_to_username", "similar_to_email", "too_weak"]
+    );
+    assert_eq!(
+        codes(validator.validate("horse staple battery correct 7", "bob", "bob@example.com").await),
+        ["breached"]
+    );
+    assert!(validator
+        .validate("gravel lantern 7 mosaic drift", "bob", "bob@example.com")
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn password_rotation_rejects_recent_passwords_and_expires_old_ones() {
+    use application::errors::ApplicationError;
+    use application::services::{PasswordPolicy, PasswordRotationPolicy, PasswordValidator};
+    use domain::entities::RoleName;
+
+    let hasher = BcryptHasher::new(Some(4));
+    let service = PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap();
+    let validator = PasswordValidator::new(PasswordPolicy {
+        min_length: 1,
+        max_length: 64,
+        require_lowercase: false,
+        require_uppercase: false,
+        require_digit: false,
+        require_symbol: false,
+        reject_account_names: false,
+        min_strength: 0,
+    })
+    .with_rotation(PasswordRotationPolicy {
+        reuse_limit: 3,
+        max_age: [(RoleName::Admin, chrono::Duration::days(90))].into(),
+    });
+    let history_size = validator.rotation().history_size();
+
+    let mut user = sample_user(RoleName::Admin);
+    user.password_hash = hasher.hash_password("first").unwrap();
+    for password in ["second", "third", "fourth"] {
+        user.set_password(hasher.hash_password(password).unwrap(), history_size);
+    }
+    assert_eq!(user.password_history.len(), 2);
+
+    for (password, reused) in [("fourth", true), ("second", true), ("first", false), ("fifth", false)] {
+        let result = validator
+            .validate_change(password, &user, &user.username, &user.email, &service)
+            .await;
+        assert_eq!(
+            matches!(result, Err(ApplicationError::PasswordRejected(ref v)) if v[0].code == "reused"),
+            reused,
+            "{}",
+            password
+        );
+    }
+
+    assert!(!validator.rotation().is_expired(&user));
+    user.password_changed_at = Some(chrono::Utc::now() - chrono::Duration::days(91));
+    assert!(validator.rotation().is_expired(&user));
+    user.role = domain::entities::Role::new(RoleName::User);
+    assert!(!validator.rotation().is_expired(&user));
+}
+
+/// Email settings that deliver to a maildir in `dir`
+fn file_email_config(dir: &std::path::Path) -> crate::config::EmailConfig {
+    use crate::config::{EmailConfig, EmailDelivery, SmtpConfig, SmtpTls};
+
+    EmailConfig {
+        delivery: EmailDelivery::File,
+        from: "no-reply@example.com".to_string(),
+        smtp: SmtpConfig {
+            host: "localhost".to_string(),
+            port: 25,
+            username: None,
+            password: None,
+            tls: SmtpTls::None,
+        },
+        file_dir: dir.to_string_lossy().into_owned(),
+    }
+}
+
+/// Waits for the first email delivered in the background to the maildir in
+/// `dir`, checks that no other follows and returns it.
+async fn single_delivered_email(dir: &std::path::Path) -> String {
+    let mut delivered = Vec::new();
+    for _ in 0..100 {
+        delivered = std::fs::read_dir(dir.join("new")).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        if !delivered.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(std::fs::read_dir(dir.join("new")).unwrap().count(), 1);
+    std::fs::read_to_string(delivered[0].path()).unwrap()
+}
+
+/// The one-time token in an email
+fn emailed_token(email: &str) -> String {
+    email
+        .split_whitespace()
+        .find(|word| word.len() == 64 && word.chars().all(|c| c.is_ascii_hexdigit()))
+        .expect("no token in the email")
+        .to_string()
+}
+
+#[tokio::test]
+async fn password_reset_tokens_are_emailed_and_single_use() {
+    use application::dtos::{PasswordResetConfirmDto, PasswordResetRequestDto};
+    use application::errors::ApplicationError;
+    use application::services::{
+        PasswordPolicy, PasswordResetService, PasswordResetServiceImpl, PasswordService, PasswordValidator,
+    };
+    use domain::entities::RoleName;
+
+    use crate::email::FileNotifier;
+    use crate::security::RandomTokenService;
+
+    let dir = scratch_dir();
+    let email_config = file_email_config(&dir);
+
+    let users = Arc::new(InMemoryUserRepository::new());
+    let password_service = Arc::new(PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap());
+    let mut user = sample_user(RoleName::User);
+    user.password_hash = password_service.hash_password("old password").await.unwrap();
+    users.create(&user).await.unwrap();
+
+    let service = PasswordResetServiceImpl::new(
+        Arc::clone(&users) as Arc<dyn UserRepository>,
+        Arc::new(InMemoryPasswordResetTokenRepository::new()),
+        Arc::new(RandomTokenService),
+        Arc::clone(&password_service) as Arc<dyn PasswordService>,
+        Arc::new(PasswordValidator::new(PasswordPolicy {
+            min_length: 10,
+            max_length: 64,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_account_names: false,
+            min_strength: 0,
+        })),
+        Arc::new(FileNotifier::new(&email_config).unwrap()),
+    );
+
+    let request = |email: &str| PasswordResetRequestDto { email: email.to_string() };
+    service.request_reset(request("nobody@example.com")).await.unwrap();
+    service.request_reset(request(&user.email)).await.unwrap();
+
+    // Delivered in the background; only the known address gets an email
+    let email = single_delivered_email(&dir).await;
+    assert!(email.contains(&format!("To: {}", user.email)));
+    let token = emailed_token(&email);
+
+    let confirm = |token: &str, password: &str| PasswordResetConfirmDto {
+        token: token.to_string(),
+        new_password: password.to_string(),
+    };
+    assert!(matches!(
+        service.confirm_reset(confirm(&"0".repeat(64), "new password")).await,
+        Err(ApplicationError::ValidationError(_))
+    ));
+    // A rejected password does not use up the token
+    assert!(matches!(
+        service.confirm_reset(confirm(&token, "short")).await,
+        Err(ApplicationError::PasswordRejected(_))
+    ));
+    service.confirm_reset(confirm(&token, "new password")).await.unwrap();
+    assert!(matches!(
+        service.confirm_reset(confirm(&token, "another password")).await,
+        Err(ApplicationError::ValidationError(_))
+    ));
+
+    let updated = users.find_by_id(&user.id).await.unwrap().unwrap();
+    assert!(password_service.verify_password("new password", &updated.password_hash).await.unwrap());
+    assert_eq!(updated.token_version, user.token_version + 1);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+fn user_service(users: &Arc<InMemoryUserRepository>) -> application::services::UserServiceImpl {
+    use application::services::{PasswordPolicy, PasswordValidator, UserServiceImpl};
+
+    let password_service = PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap();
+    UserServiceImpl::new(
+        Arc::clone(users) as Arc<dyn UserRepository>,
+        Arc::new(InMemoryUnitOfWork::new(Arc::clone(users))),
+        Arc::new(password_service) as Arc<dyn PasswordService>,
+        Arc::new(PasswordValidator::new(PasswordPolicy {
+            min_length: 10,
+            max_length: 64,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_account_names: false,
+            min_strength: 0,
+        })),
+    )
+}
+
+#[tokio::test]
+async fn email_changes_take_effect_once_confirmed() {
+    use application::dtos::{EmailVerificationConfirmDto, UpdateUserDto};
+    use application::errors::ApplicationError;
+    use application::services::{EmailVerificationService, EmailVerificationServiceImpl, UserService};
+    use domain::entities::RoleName;
+
+    use crate::email::FileNotifier;
+    use crate::security::RandomTokenService;
+
+    let dir = scratch_dir();
+    let users = Arc::new(InMemoryUserRepository::new());
+    let user = sample_user(RoleName::User);
+    users.create(&user).await.unwrap();
+
+    let verification = Arc::new(EmailVerificationServiceImpl::new(
+        Arc::clone(&users) as Arc<dyn UserRepository>,
+        Arc::new(InMemoryEmailVerificationTokenRepository::new()),
+        Arc::new(RandomTokenService),
+        Arc::new(FileNotifier::new(&file_email_config(&dir)).unwrap()),
+    ));
+    let user_service =
+        user_service(&users).with_email_verification(Arc::clone(&verification) as Arc<dyn EmailVerificationService>);
+
+    let new_email = format!("{}@example.org", Uuid::new_v4().simple());
+    let update = UpdateUserDto {
+        username: None,
+        email: Some(new_email.clone()),
+        role: None,
+    };
+    let updated = user_service.update_user(&user.id.to_string(), update, None).await.unwrap();
+    assert_eq!(updated.email, user.email);
+
+    // The token goes to the new address, which is only used once confirmed
+    let email = single_delivered_email(&dir).await;
+    assert!(email.contains(&format!("To: {}", new_email)));
+    let confirm = |token: &str| EmailVerificationConfirmDto { token: token.to_string() };
+    assert!(matches!(
+        verification.confirm_verification(confirm(&"0".repeat(64))).await,
+        Err(ApplicationError::ValidationError(_))
+    ));
+    let token = emailed_token(&email);
+    verification.confirm_verification(confirm(&token)).await.unwrap();
+    assert!(matches!(
+        verification.confirm_verification(confirm(&token)).await,
+        Err(ApplicationError::ValidationError(_))
+    ));
+
+    let stored = users.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, new_email);
+    assert!(stored.is_email_verified());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn user_patches_apply_merge_and_json_patches() {
+    use application::dtos::{PatchableUserDto, UserPatch};
+    use domain::entities::RoleName;
+    use serde_json::json;
+
+    let user = PatchableUserDto {
+        username: "alice".to_string(),
+        email: "alice@example.com".to_string(),
+        role: RoleName::User,
+    };
+    let merge = |patch: serde_json::Value| UserPatch::Merge(patch).apply(&user);
+    let json = |patch: serde_json::Value| UserPatch::Json(serde_json::from_value(patch).unwrap()).apply(&user);
+    let invalid = |result: Result<PatchableUserDto, ApplicationError>| {
+        matches!(result, Err(ApplicationError::ValidationError(_)))
+    };
+
+    // Absent fields are kept, `null` removes a field, which every user needs
+    let patched = merge(json!({ "email": "alice@example.org" })).unwrap();
+    assert_eq!((patched.username.as_str(), patched.email.as_str()), ("alice", "alice@example.org"));
+    assert!(invalid(merge(json!({ "username": null }))));
+
+    let patched = json(json!([
+        { "op": "test", "path": "/username", "value": "alice" },
+        { "op": "replace", "path": "/username", "value": "bob" },
+    ]))
+    .unwrap();
+    assert_eq!(patched.username, "bob");
+    assert!(invalid(json(json!([{ "op": "remove", "path": "/email" }]))));
+
+    // A failing test discards the whole patch
+    assert!(invalid(json(json!([
+        { "op": "replace", "path": "/username", "value": "bob" },
+        { "op": "test", "path": "/username", "value": "alice" },
+    ]))));
+
+    // Only the patchable fields exist; read-only and unknown ones are refused
+    for field in ["id", "password", "version", "nickname"] {
+        assert!(invalid(merge(json!({ field: "x" }))), "{}", field);
+        let path = format!("/{}", field);
+        assert!(invalid(json(json!([{ "op": "add", "path": path, "value": "x" }]))), "{}", field);
+        assert!(invalid(json(json!([{ "op": "replace", "path": path, "value": "x" }]))), "{}", field);
+    }
+}
+
+#[tokio::test]
+async fn patched_users_are_validated_like_updates() {
+    use application::dtos::UserPatch;
+    use application::services::UserService;
+    use domain::entities::RoleName;
+    use serde_json::json;
+
+    let users = Arc::new(InMemoryUserRepository::new());
+    let user = sample_user(RoleName::User);
+    let other = sample_user(RoleName::User);
+    users.create(&user).await.unwrap();
+    users.create(&other).await.unwrap();
+    let user_service = user_service(&users);
+    let id = user.id.to_string();
+
+    for patch in [
+        json!({ "username": " " }),
+        json!({ "email": "not an email" }),
+        json!({ "email": "@example.com" }),
+        json!({ "username": other.username }),
+        json!({ "email": other.email }),
+    ] {
+        let result = user_service.patch_user(&id, UserPatch::Merge(patch.clone()), None).await;
+        assert!(matches!(result, Err(ApplicationError::ValidationError(_))), "{}", patch);
+    }
+    assert_eq!(users.find_by_id(&user.id).await.unwrap(), Some(user));
+}
+
+#[test]
+fn totp_codes_match_rfc_6238_vectors() {
+    use application::services::TotpService;
+    use chrono::{TimeZone, Utc};
+
+    use crate::security::HmacTotpService;
+
+    // The SHA-1 seed of RFC 6238 appendix B, base32 encoded; codes are the last six digits
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    for (time, code) in [(59, "287082"), (1111111109, "081804"), (1234567890, "005924"), (2000000000, "279037")] {
+        assert_eq!(HmacTotpService::code(secret, time / 30).as_deref(), Some(code));
+    }
+
+    // Codes of neighbouring steps are accepted only within the skew
+    let late = Utc.timestamp_opt(59 + 30, 0).unwrap();
+    assert_eq!(HmacTotpService::new("test".to_string(), 1).verify(secret, "287082", late), Some(1));
+    assert_eq!(HmacTotpService::new("test".to_string(), 0).verify(secret, "287082", late), None);
+
+    let totp = HmacTotpService::new("Example Co".to_string(), 1);
+    let generated = totp.generate_secret();
+    assert_eq!(generated.len(), 32);
+    assert!(totp
+        .provisioning_uri(&generated, "alice@example.org")
+        .starts_with(&format!("otpauth://totp/Example%20Co:alice%40example%2Eorg?secret={}&", generated)));
+    assert!(totp.qr_code_svg("otpauth://totp/x").unwrap().starts_with("<?xml"));
+}
+
+#[tokio::test]
+async fn mfa_codes_and_recovery_codes_are_single_use() {
+    use application::dtos::{MfaCodeDto, MfaDisableDto};
+    use application::errors::ApplicationError;
+    use application::services::{MfaService, MfaServiceImpl};
+    use domain::entities::LoginAttemptKey;
+    use domain::repositories::{LoginAttemptRepository, MfaRepository};
+    use domain::entities::RoleName;
+
+    use crate::security::{AesGcmSecretCipher, HmacTotpService, RandomTokenService};
+
+    let password_service: Arc<dyn PasswordService> =
+        Arc::new(PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap());
+    let users = Arc::new(InMemoryUserRepository::new());
+    let mut user = sample_user(RoleName::Admin);
+    user.password_hash = password_service.hash_password("long enough password").await.unwrap();
+    users.create(&user).await.unwrap();
+    let repository = Arc::new(InMemoryMfaRepository::new());
+    let login_attempts = Arc::new(InMemoryLoginAttemptRepository::new());
+    let mfa = MfaServiceImpl::new(
+        Arc::clone(&users) as Arc<dyn UserRepository>,
+        Arc::clone(&repository) as Arc<dyn MfaRepository>,
+        Arc::new(HmacTotpService::new("test".to_string(), 1)),
+        Arc::new(AesGcmSecretCipher::new(&Secret::new("test"))),
+        Arc::new(RandomTokenService),
+        password_service,
+        login_throttle(&login_attempts),
+    )
+    .with_required_roles([RoleName::Admin].into())
+    .with_recovery_code_count(3);
+    let user_id = user.id.to_string();
+    let code = |secret: &str, offset: i64| MfaCodeDto {
+        code: HmacTotpService::code(secret, chrono::Utc::now().timestamp() / 30 + offset).unwrap(),
+    };
+
+    assert!(mfa.is_required(&user));
+    assert!(!mfa.is_enabled(&user).await.unwrap());
+
+    // Enrolling again before confirming replaces the secret
+    let abandoned = mfa.begin_enrollment(&user_id).await.unwrap();
+    let enrollment = mfa.begin_enrollment(&user_id).await.unwrap();
+    assert_ne!(abandoned.secret, enrollment.secret);
+    assert!(matches!(
+        mfa.confirm_enrollment(&user_id, code(&abandoned.secret, 0)).await,
+        Err(ApplicationError::ValidationError(_))
+    ));
+    let recovery = mfa.confirm_enrollment(&user_id, code(&enrollment.secret, 0)).await.unwrap();
+    assert_eq!(recovery.recovery_codes.len(), 3);
+    let stored = repository.find(&user.id).await.unwrap().unwrap();
+    assert!(stored.secret.starts_with("v1:") && !stored.secret.contains(&enrollment.secret));
+    assert!(mfa.is_enabled(&user).await.unwrap());
+    assert!(matches!(mfa.begin_enrollment(&user_id).await, Err(ApplicationError::Conflict(_))));
+
+    // The code used to confirm, and any code after it, works only once
+    assert!(!mfa.verify(&user, &code(&enrollment.secret, 0).code).await.unwrap());
+    let next = code(&enrollment.secret, 1).code;
+    assert!(mfa.verify(&user, &next).await.unwrap());
+    assert!(!mfa.verify(&user, &next).await.unwrap());
+
+    // Recovery codes are accepted without the dash and in lowercase, once
+    let recovery_code = recovery.recovery_codes[0].replace('-', "").to_lowercase();
+    assert!(mfa.verify(&user, &recovery_code).await.unwrap());
+    assert!(!mfa.verify(&user, &recovery.recovery_codes[0]).await.unwrap());
+    let status = mfa.status(&user_id).await.unwrap();
+    assert!(status.enabled && status.required);
+    assert_eq!(status.recovery_codes_remaining, 2);
+
+    // Disabling takes the password as well, and wrong ones count as failed logins
+    let disable = |password: &str, code: &str| MfaDisableDto {
+        password: password.to_string(),
+        code: code.to_string(),
+    };
+    let account = LoginAttemptKey::account(&user.username);
+    assert!(matches!(
+        mfa.disable(&user_id, disable("wrong password", &recovery.recovery_codes[1]), None).await,
+        Err(ApplicationError::AuthenticationError(_))
+    ));
+    assert!(matches!(
+        mfa.disable(&user_id, disable("long enough password", "XXXXX-XXXXX"), None).await,
+        Err(ApplicationError::AuthenticationError(_))
+    ));
+    assert_eq!(login_attempts.find(&account).await.unwrap().unwrap().failures, 2);
+    // The recovery code given with the wrong password was not used up
+    mfa.disable(&user_id, disable("long enough password", &recovery.recovery_codes[1]), None)
+        .await
+        .unwrap();
+    assert!(!mfa.is_enabled(&user).await.unwrap());
+    assert!(login_attempts.find(&account).await.unwrap().is_none());
+    assert!(matches!(mfa.reset(&user_id, &user_id).await, Err(ApplicationError::Conflict(_))));
+
+    // An administrator's reset also ends the user's sessions
+    let enrollment = mfa.begin_enrollment(&user_id).await.unwrap();
+    mfa.confirm_enrollment(&user_id, code(&enrollment.secret, 0)).await.unwrap();
+    mfa.reset(&user_id, &Uuid::new_v4().to_string()).await.unwrap();
+    assert!(!mfa.is_enabled(&user).await.unwrap());
+    let reset = users.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(reset.token_version, user.token_version + 1);
+}
+
+/// Tokens that are just their claims as JSON, for tests that only need tokens
+/// to round-trip
+struct JsonJwtService;
+
+impl JwtService for JsonJwtService {
+    fn generate_token(&self, claims: JwtClaims) -> Result<String, ApplicationError> {
+        Ok(serde_json::to_string(&claims).unwrap())
+    }
+
+    fn validate_token(&self, token: &str) -> Result<JwtClaims, ApplicationError> {
+        serde_json::from_str(token).map_err(|e| ApplicationError::AuthenticationError(e.to_string()))
+    }
+}
+
+/// Counts the passwords verified by the wrapped service
+struct CountingPasswordService {
+    inner: Arc<dyn PasswordService>,
+    verified: std::sync::atomic::AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl PasswordService for CountingPasswordService {
+    async fn hash_password(&self, password: &str) -> Result<String, ApplicationError> {
+        self.inner.hash_password(password).await
+    }
+
+    async fn verify_password(&self, password: &str, hash: &str) -> Result<bool, ApplicationError> {
+        self.verified.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.inner.verify_password(password, hash).await
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        self.inner.needs_rehash(hash)
+    }
+
+    fn is_supported_hash(&self, hash: &str) -> bool {
+        self.inner.is_supported_hash(hash)
+    }
+}
+
+/// Hands every message to the test instead of delivering it
+struct RecordingNotifier(tokio::sync::mpsc::UnboundedSender<EmailMessage>);
+
+#[async_trait::async_trait]
+impl Notifier for RecordingNotifier {
+    async fn send(&self, message: EmailMessage) -> Result<(), ApplicationError> {
+        self.0.send(message).unwrap();
+        Ok(())
+    }
+}
+
+/// Locks accounts after three failed logins
+fn login_throttle(login_attempts: &Arc<InMemoryLoginAttemptRepository>) -> Arc<application::services::LoginThrottle> {
+    use application::services::{LockoutPolicy, LoginThrottle};
+
+    Arc::new(LoginThrottle::new(
+        Arc::clone(login_attempts) as _,
+        LockoutPolicy {
+            account_threshold: 3,
+            ip_threshold: 0,
+            base_delay: chrono::Duration::minutes(1),
+            max_delay: chrono::Duration::hours(1),
+            reset_after: chrono::Duration::days(1),
+        },
+    ))
+}
+
+/// An auth service over in-memory repositories, throttled by `login_throttle`
+fn auth_service(
+    users: &Arc<InMemoryUserRepository>,
+    password_service: Arc<dyn PasswordService>,
+    login_attempts: &Arc<InMemoryLoginAttemptRepository>,
+) -> application::services::AuthServiceImpl {
+    use application::services::{AuthServiceImpl, PasswordPolicy, PasswordValidator};
+
+    AuthServiceImpl::new(
+        Arc::clone(users) as Arc<dyn UserRepository>,
+        Arc::new(JsonJwtService),
+        3600,
+        password_service,
+        login_throttle(login_attempts),
+        Arc::new(PasswordValidator::new(PasswordPolicy {
+            min_length: 10,
+            max_length: 64,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_account_names: false,
+            min_strength: 0,
+        })),
+    )
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_verified_against_a_dummy_hash_and_counted() {
+    use application::dtos::LoginRequestDto;
+    use application::services::AuthService;
+    use domain::entities::LoginAttemptKey;
+    use domain::repositories::LoginAttemptRepository;
+
+    let password_service = Arc::new(CountingPasswordService {
+        inner: Arc::new(PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap()),
+        verified: Default::default(),
+    });
+    let login_attempts = Arc::new(InMemoryLoginAttemptRepository::new());
+    let service = auth_service(
+        &Arc::new(InMemoryUserRepository::new()),
+        Arc::clone(&password_service) as _,
+        &login_attempts,
+    );
+
+    let login = LoginRequestDto {
+        username: "nobody".to_string(),
+        password: "some password".to_string(),
+    };
+    assert!(matches!(
+        service.login(login, None).await,
+        Err(ApplicationError::AuthenticationError(_))
+    ));
+
+    assert_eq!(password_service.verified.load(std::sync::atomic::Ordering::SeqCst), 1);
+    let failed = login_attempts.find(&LoginAttemptKey::account("nobody")).await.unwrap().unwrap();
+    assert_eq!(failed.failures, 1);
+}
+
+#[tokio::test]
+async fn uniform_registration_is_accepted_and_notified_in_every_case() {
+    use application::dtos::{RegisterOutcome, RegisterRequestDto};
+    use application::services::AuthService;
+    use domain::entities::RoleName;
+
+    let users = Arc::new(InMemoryUserRepository::new());
+    let existing = sample_user(RoleName::User);
+    users.create(&existing).await.unwrap();
+    let (sender, mut messages) = tokio::sync::mpsc::unbounded_channel();
+    let service = auth_service(
+        &users,
+        Arc::new(PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap()),
+        &Arc::new(InMemoryLoginAttemptRepository::new()),
+    )
+    .with_uniform_registration(Arc::new(RecordingNotifier(sender)));
+
+    let register = |username: &str, email: &str| RegisterRequestDto {
+        username: username.to_string(),
+        email: email.to_string(),
+        password: "long enough password".to_string(),
+        role: None,
+    };
+    // Email taken, username taken, and neither
+    let cases = [
+        ("newcomer", existing.email.as_str(), "Registration attempt with your email address"),
+        (existing.username.as_str(), "other@example.com", "Your registration could not be completed"),
+        ("newcomer", "newcomer@example.com", "Your account has been created"),
+    ];
+
+    for (username, email, subject) in cases {
+        let request = register(username, email);
+        assert!(matches!(service.register(request).await, Ok(RegisterOutcome::Accepted)));
+
+        // Sent in the background
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), messages.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.to, email);
+        assert_eq!(message.subject, subject);
+    }
+
+    // Only the last request created an account
+    let created = users.find_by_username("newcomer").await.unwrap().unwrap();
+    assert_eq!(created.email, "newcomer@example.com");
+    assert_eq!(users.find_all().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn password_changes_count_wrong_passwords_and_revoke_older_tokens() {
+    use application::dtos::{ChangePasswordRequestDto, LoginOutcome, LoginRequestDto};
+    use application::services::AuthService;
+    use domain::entities::RoleName;
+    use domain::entities::LoginAttemptKey;
+    use domain::repositories::LoginAttemptRepository;
+
+    let password_service: Arc<dyn PasswordService> =
+        Arc::new(PooledPasswordService::new(BcryptHasher::new(Some(4)), &hashing_config()).unwrap());
+    let users = Arc::new(InMemoryUserRepository::new());
+    let mut user = sample_user(RoleName::User);
+    user.password_hash = password_service.hash_password("first long password").await.unwrap();
+    users.create(&user).await.unwrap();
+    let login_attempts = Arc::new(InMemoryLoginAttemptRepository::new());
+    let service = auth_service(&users, Arc::clone(&password_service), &login_attempts);
+    let account = LoginAttemptKey::account(&user.username);
+
+    let login = LoginRequestDto {
+        username: user.username.clone(),
+        password: "first long password".to_string(),
+    };
+    let Ok(LoginOutcome::Authenticated(old)) = service.login(login, None).await else {
+        panic!("login failed");
+    };
+    let change = |current: &str, new: &str| ChangePasswordRequestDto {
+        current_password: current.to_string(),
+        new_password: new.to_string(),
+    };
+    let user_id = user.id.to_string();
+
+    // Wrong current passwords are rejected and count as failed logins
+    for failures in 1..=2 {
+        let request = change("wrong password", "second long password");
+        assert!(matches!(
+            service.change_password(&user_id, request, None).await,
+            Err(ApplicationError::AuthenticationError(_))
+        ));
+        assert_eq!(login_attempts.find(&account).await.unwrap().unwrap().failures, failures);
+    }
+
+    let request = change("first long password", "second long password");
+    let new = service.change_password(&user_id, request, None).await.unwrap();
+    assert!(login_attempts.find(&account).await.unwrap().is_none());
+
+    // Tokens issued before the change are revoked, the one it returned is not
+    assert_eq!(users.find_by_id(&user.id).await.unwrap().unwrap().token_version, user.token_version + 1);
+    assert!(matches!(
+        service.validate_token(&old.token).await,
+        Err(ApplicationError::AuthenticationError(message)) if message == "Token has been revoked"
+    ));
+    assert_eq!(service.validate_token(&new.token).await.unwrap().sub, user_id);
+
+    // The third wrong password locks the account, even for the right one
+    for _ in 0..3 {
+        let request = change("wrong password", "third long password");
+        assert!(service.change_password(&user_id, request, None).await.is_err());
+    }
+    let request = change("second long password", "third long password");
+    assert!(matches!(
+        service.change_password(&user_id, request, None).await,
+        Err(ApplicationError::TooManyRequests { .. })
+    ));
+}
+
+#[tokio::test]
+async fn sqlite_user_repository_conforms() {
+    let dir = scratch_dir();
+    let config = SqliteConfig {
+        path: dir.join("users.db").to_string_lossy().into_owned(),
+        max_connections: 5,
+        journal_mode: "wal".to_string(),
+        busy_timeout_ms: 5000,
+    };
+    let pool = create_sqlite_pool(&config).await.unwrap();
+
+    run_user_repository_conformance(Arc::new(SqliteUserRepository::new(pool.clone()))).await;
+    run_unit_of_work_conformance(
+        Arc::new(SqliteUnitOfWork::new(pool.clone())),
+        Arc::new(SqliteUserRepository::new(pool.clone())),
+    )
+    .await;
+    run_idempotency_repository_conformance(Arc::new(SqliteIdempotencyRepository::new(pool.clone()))).await;
+    run_login_attempt_repository_conformance(Arc::new(SqliteLoginAttemptRepository::new(pool.clone()))).await;
+    run_rate_limit_repository_conformance(Arc::new(SqliteRateLimitRepository::new(pool.clone()))).await;
+    run_password_reset_repository_conformance(Arc::new(SqlitePasswordResetTokenRepository::new(pool.clone()))).await;
+    run_email_verification_repository_conformance(Arc::new(SqliteEmailVerificationTokenRepository::new(pool.clone()))).await;
+    run_mfa_repository_conformance(Arc::new(SqliteMfaRepository::new(pool))).await;
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+/// Needs `TEST_DATABASE_URL` pointing at a disposable Postgres database.
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn postgres_user_repository_conforms() {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL must point at a disposable Postgres database to run this test");
+
+    let pool = sqlx::PgPool::connect(&url).await.unwrap();
+    sqlx::migrate!("../migrations").run(&pool).await.unwrap();
+
+    run_user_repository_conformance(Arc::new(PostgresUserRepository::new(pool.clone()))).await;
+    run_unit_of_work_conformance(
+        Arc::new(PostgresUnitOfWork::new(pool.clone())),
+        Arc::new(PostgresUserRepository::new(pool.clone())),
+    )
+    .await;
+    run_idempotency_repository_conformance(Arc::new(PostgresIdempotencyRepository::new(pool.clone()))).await;
+    run_login_attempt_repository_conformance(Arc::new(PostgresLoginAttemptRepository::new(pool.clone()))).await;
+    run_rate_limit_repository_conformance(Arc::new(PostgresRateLimitRepository::new(pool.clone()))).await;
+    run_password_reset_repository_conformance(Arc::new(PostgresPasswordResetTokenRepository::new(pool.clone()))).await;
+    run_email_verification_repository_conformance(Arc::new(PostgresEmailVerificationTokenRepository::new(pool.clone()))).await;
+    run_mfa_repository_conformance(Arc::new(PostgresMfaRepository::new(pool))).await;
+}
@@ -0,0 +1,2 @@
+-- Version for optimistic concurrency control, incremented by every update
+ALTER TABLE users ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
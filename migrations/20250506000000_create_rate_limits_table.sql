@@ -0,0 +1,8 @@
+-- GCRA state of rate limited keys, shared by all instances
+CREATE TABLE IF NOT EXISTS rate_limits (
+    key TEXT PRIMARY KEY,
+    -- Theoretical arrival time: when the key's bucket is full again
+    tat TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_rate_limits_tat ON rate_limits(tat);
@@ -0,0 +1,3 @@
+-- When the password was last changed, and hashes of earlier passwords (most recent first)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_history JSONB NOT NULL DEFAULT '[]';
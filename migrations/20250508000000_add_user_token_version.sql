@@ -0,0 +1,2 @@
+-- Incremented to revoke every token issued to the user
+ALTER TABLE users ADD COLUMN IF NOT EXISTS token_version BIGINT NOT NULL DEFAULT 0;
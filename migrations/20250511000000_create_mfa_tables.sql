@@ -0,0 +1,15 @@
+-- TOTP authenticators, at most one per user; unconfirmed while enrolling
+CREATE TABLE IF NOT EXISTS totp_enrollments (
+    user_id UUID PRIMARY KEY,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    confirmed_at TIMESTAMPTZ,
+    last_used_step BIGINT
+);
+
+-- Unused recovery codes, stored by their hash
+CREATE TABLE IF NOT EXISTS mfa_recovery_codes (
+    user_id UUID NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,2 @@
+-- Version for optimistic concurrency control, incremented by every update
+ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,5 @@
+-- Soft deletion: set while a user is deleted, cleared on restore
+ALTER TABLE users ADD COLUMN deleted_at TEXT;
+
+-- Lets the retention purge find deleted users without scanning the table
+CREATE INDEX IF NOT EXISTS idx_users_deleted_at ON users(deleted_at) WHERE deleted_at IS NOT NULL;
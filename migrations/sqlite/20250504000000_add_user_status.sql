@@ -0,0 +1,4 @@
+-- Account lifecycle: active, suspended, locked or deactivated
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active';
+ALTER TABLE users ADD COLUMN status_reason TEXT;
+ALTER TABLE users ADD COLUMN status_changed_at TEXT;
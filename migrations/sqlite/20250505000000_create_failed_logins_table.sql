@@ -0,0 +1,11 @@
+-- Consecutive failed logins per account and per source address
+CREATE TABLE IF NOT EXISTS failed_logins (
+    scope TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    failures INTEGER NOT NULL,
+    first_failed_at TEXT NOT NULL,
+    last_failed_at TEXT NOT NULL,
+    PRIMARY KEY (scope, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_logins_last_failed_at ON failed_logins(last_failed_at);
@@ -0,0 +1,9 @@
+-- GCRA state of rate limited keys, shared by all processes using the database
+CREATE TABLE IF NOT EXISTS rate_limits (
+    key TEXT PRIMARY KEY,
+    -- Theoretical arrival time, when the key's bucket is full again, in
+    -- microseconds since the Unix epoch
+    tat INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_rate_limits_tat ON rate_limits(tat);
@@ -0,0 +1,3 @@
+-- When the password was last changed, and hashes of earlier passwords (most recent first)
+ALTER TABLE users ADD COLUMN password_changed_at TEXT;
+ALTER TABLE users ADD COLUMN password_history TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- Incremented to revoke every token issued to the user
+ALTER TABLE users ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0;
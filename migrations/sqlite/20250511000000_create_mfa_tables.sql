@@ -0,0 +1,15 @@
+-- TOTP authenticators, at most one per user; unconfirmed while enrolling
+CREATE TABLE IF NOT EXISTS totp_enrollments (
+    user_id TEXT PRIMARY KEY,
+    secret TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    confirmed_at TEXT,
+    last_used_step INTEGER
+);
+
+-- Unused recovery codes, stored by their hash
+CREATE TABLE IF NOT EXISTS mfa_recovery_codes (
+    user_id TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -1,7 +1,7 @@
 #!/bin/bash
 
 # Set environment variables to use in-memory repository
-export USE_MEMORY_REPO=true
+export STORAGE_BACKEND=memory
 
 # Run the server
 cargo run
@@ -1,7 +1,7 @@
 #!/bin/bash
 
 # Set environment variables to use PostgreSQL repository
-export USE_MEMORY_REPO=false
+export STORAGE_BACKEND=postgres
 
 # Run the server
 cargo run
@@ -0,0 +1,8 @@
+#!/bin/bash
+
+# Set environment variables to use SQLite repository
+export STORAGE_BACKEND=sqlite
+export SQLITE_PATH=${SQLITE_PATH:-rust_server.db}
+
+# Run the server
+cargo run